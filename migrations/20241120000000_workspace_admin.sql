@@ -0,0 +1,39 @@
+-- workspace roles and account status
+CREATE TYPE ws_role AS ENUM(
+  'admin',
+  'member'
+);
+
+CREATE TYPE user_status AS ENUM(
+  'active',
+  'deactivated'
+);
+
+ALTER TABLE users
+  ADD COLUMN role ws_role NOT NULL DEFAULT 'member',
+  ADD COLUMN status user_status NOT NULL DEFAULT 'active';
+
+-- workspaces created before this migration were saved with owner_id = 0,
+-- hand them to their earliest member
+UPDATE workspaces w
+SET owner_id = u.id
+FROM (SELECT ws_id, min(id) AS id FROM users GROUP BY ws_id) u
+WHERE w.id = u.ws_id AND w.owner_id = 0;
+
+UPDATE users u
+SET role = 'admin'
+FROM workspaces w
+WHERE w.owner_id = u.id;
+
+-- append-only record of admin actions
+CREATE TABLE IF NOT EXISTS audit_events(
+  id bigserial PRIMARY KEY,
+  ws_id bigint NOT NULL,
+  actor_id bigint NOT NULL,
+  action varchar(64) NOT NULL,
+  target varchar(128) NOT NULL,
+  detail jsonb NOT NULL DEFAULT '{}',
+  created_at timestamptz DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS audit_events_ws_idx ON audit_events(ws_id, created_at DESC);
@@ -0,0 +1,20 @@
+-- a user can belong to several workspaces, role and status are per membership
+CREATE TABLE IF NOT EXISTS ws_members(
+  ws_id bigint NOT NULL,
+  user_id bigint NOT NULL,
+  role ws_role NOT NULL DEFAULT 'member',
+  status user_status NOT NULL DEFAULT 'active',
+  created_at timestamptz DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (ws_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS ws_members_user_idx ON ws_members(user_id);
+
+INSERT INTO ws_members (ws_id, user_id, role, status, created_at)
+SELECT ws_id, id, role, status, created_at
+FROM users;
+
+-- users.ws_id is now the workspace picked when signing in without choosing one
+ALTER TABLE users
+  DROP COLUMN role,
+  DROP COLUMN status;
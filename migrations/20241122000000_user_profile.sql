@@ -0,0 +1,40 @@
+-- editable user profile
+ALTER TABLE users
+  ADD COLUMN display_name varchar(64),
+  ADD COLUMN avatar_url varchar(256),
+  ADD COLUMN title varchar(64),
+  ADD COLUMN status_text varchar(100),
+  ADD COLUMN status_emoji varchar(32),
+  ADD COLUMN status_expires_at timestamptz,
+  ADD COLUMN timezone varchar(64);
+
+CREATE OR REPLACE FUNCTION user_profile_updated()
+    RETURNS TRIGGER
+    AS $$
+DECLARE
+    WORKSPACES bigint[];
+BEGIN
+    IF (OLD.fullname, OLD.display_name, OLD.avatar_url, OLD.title, OLD.status_text,
+        OLD.status_emoji, OLD.status_expires_at, OLD.timezone)
+        IS DISTINCT FROM
+       (NEW.fullname, NEW.display_name, NEW.avatar_url, NEW.title, NEW.status_text,
+        NEW.status_emoji, NEW.status_expires_at, NEW.timezone) THEN
+        RAISE NOTICE 'user_profile_updated: %', NEW.id;
+        SELECT
+            array_agg(ws_id) INTO WORKSPACES
+        FROM
+            ws_members
+        WHERE
+            user_id = NEW.id;
+        PERFORM
+            pg_notify('user_updated', json_build_object('user', to_jsonb(NEW) - 'password_hash', 'workspaces', WORKSPACES)::text);
+    END IF;
+    RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE TRIGGER user_profile_updated_trigger
+    AFTER UPDATE ON users
+    FOR EACH ROW
+        EXECUTE FUNCTION user_profile_updated();
@@ -0,0 +1,19 @@
+-- account level status, bumping token_version revokes every issued token
+ALTER TABLE users
+  ADD COLUMN status user_status NOT NULL DEFAULT 'active',
+  ADD COLUMN token_version integer NOT NULL DEFAULT 0;
+
+-- uploaded files and who uploaded them
+CREATE TABLE IF NOT EXISTS files(
+  id bigserial PRIMARY KEY,
+  ws_id bigint NOT NULL,
+  owner_id bigint NOT NULL,
+  url varchar(256) NOT NULL,
+  path varchar(256) NOT NULL,
+  created_at timestamptz DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS files_owner_idx ON files(owner_id);
+CREATE INDEX IF NOT EXISTS files_path_idx ON files(path);
+
+CREATE INDEX IF NOT EXISTS messages_sender_idx ON messages(sender_id);
@@ -0,0 +1,121 @@
+-- chat membership as a join table instead of chats.members
+CREATE TABLE IF NOT EXISTS chat_members(
+  chat_id bigint NOT NULL REFERENCES chats(id),
+  user_id bigint NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  joined_at timestamptz DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (chat_id, user_id)
+);
+
+-- list the chats of a user without touching other members
+CREATE INDEX IF NOT EXISTS chat_members_user_idx ON chat_members(user_id, chat_id);
+
+INSERT INTO chat_members (chat_id, user_id, joined_at)
+SELECT c.id, m.user_id, c.created_at
+FROM chats c, unnest(c.members) AS m(user_id)
+WHERE EXISTS (SELECT 1 FROM users u WHERE u.id = m.user_id)
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE chats DROP COLUMN members;
+
+-- the chat row as the api serializes it
+CREATE OR REPLACE FUNCTION chat_json(_chat chats)
+    RETURNS jsonb
+    AS $$
+    SELECT
+        to_jsonb(_chat) || jsonb_build_object('members', ARRAY(
+                SELECT
+                    user_id FROM chat_members
+                WHERE
+                    chat_id = _chat.id ORDER BY user_id));
+$$
+LANGUAGE sql
+STABLE;
+
+-- new chats and membership changes are notified by chat_members triggers
+DROP TRIGGER IF EXISTS add_to_chat_trigger ON chats;
+
+CREATE OR REPLACE FUNCTION add_to_chat()
+    RETURNS TRIGGER
+    AS $$
+BEGIN
+    RAISE NOTICE 'add_to_chat: %', NEW;
+    PERFORM
+        pg_notify('chat_updated', json_build_object('op', TG_OP, 'old', chat_json(OLD), 'new', chat_json(NEW))::text);
+    RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE TRIGGER add_to_chat_trigger
+    AFTER UPDATE ON chats
+    FOR EACH ROW
+    WHEN (OLD.* IS DISTINCT FROM NEW.*)
+        EXECUTE FUNCTION add_to_chat();
+
+CREATE OR REPLACE FUNCTION chat_members_changed()
+    RETURNS TRIGGER
+    AS $$
+DECLARE
+    REC record;
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        FOR REC IN
+        SELECT
+            c, array_agg(a.user_id ORDER BY a.user_id) AS user_ids
+        FROM
+            added a
+            JOIN chats c ON c.id = a.chat_id
+        GROUP BY
+            c.id LOOP
+                PERFORM
+                    pg_notify('chat_members_changed', json_build_object('op', TG_OP, 'chat', chat_json(REC.c), 'user_ids', REC.user_ids)::text);
+            END LOOP;
+    ELSE
+        FOR REC IN
+        SELECT
+            c, array_agg(d.user_id ORDER BY d.user_id) AS user_ids
+        FROM
+            removed d
+            JOIN chats c ON c.id = d.chat_id
+        GROUP BY
+            c.id LOOP
+                PERFORM
+                    pg_notify('chat_members_changed', json_build_object('op', TG_OP, 'chat', chat_json(REC.c), 'user_ids', REC.user_ids)::text);
+            END LOOP;
+    END IF;
+    RETURN NULL;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE TRIGGER chat_members_added_trigger
+    AFTER INSERT ON chat_members REFERENCING NEW TABLE AS added
+    FOR EACH STATEMENT
+        EXECUTE FUNCTION chat_members_changed();
+
+CREATE TRIGGER chat_members_removed_trigger
+    AFTER DELETE ON chat_members REFERENCING OLD TABLE AS removed
+    FOR EACH STATEMENT
+        EXECUTE FUNCTION chat_members_changed();
+
+CREATE OR REPLACE FUNCTION add_to_message()
+    RETURNS TRIGGER
+    AS $$
+DECLARE
+    USERS bigint[];
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        RAISE NOTICE 'add_to_message: %', NEW;
+        SELECT
+            array_agg(user_id) INTO USERS
+        FROM
+            chat_members
+        WHERE
+            chat_id = NEW.chat_id;
+        PERFORM
+            pg_notify('chat_message_created', json_build_object('message', NEW, 'members', COALESCE(USERS, '{}'))::text);
+    END IF;
+    RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
@@ -0,0 +1,14 @@
+-- channel names are unique per workspace, rename existing duplicates first
+UPDATE chats c SET name = c.name || '-' || c.id
+FROM (
+    SELECT id, row_number() OVER (PARTITION BY ws_id, lower(name) ORDER BY id) AS rn
+    FROM chats
+    WHERE chat_type IN ('private_channel', 'public_channel') AND status = 1
+) d
+WHERE c.id = d.id AND d.rn > 1;
+
+CREATE UNIQUE INDEX IF NOT EXISTS chats_channel_name_idx ON chats(ws_id, lower(name))
+WHERE chat_type IN ('private_channel', 'public_channel') AND status = 1;
+
+-- find the direct message between two members
+CREATE INDEX IF NOT EXISTS chats_ws_type_idx ON chats(ws_id, chat_type);
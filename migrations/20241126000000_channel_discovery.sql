@@ -0,0 +1,6 @@
+-- shown when browsing public channels
+ALTER TABLE chats ADD COLUMN IF NOT EXISTS topic varchar(256);
+
+-- browse the public channels of a workspace
+CREATE INDEX IF NOT EXISTS chats_public_channel_idx ON chats(ws_id, name)
+WHERE chat_type = 'public_channel' AND status = 1;
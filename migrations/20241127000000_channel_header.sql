@@ -0,0 +1,75 @@
+-- channel header: topic (added with channel discovery), description, pins and bookmarks
+ALTER TABLE chats ADD COLUMN IF NOT EXISTS description text;
+
+CREATE TABLE IF NOT EXISTS chat_pins(
+  chat_id bigint NOT NULL REFERENCES chats(id),
+  msg_id bigint NOT NULL REFERENCES messages(id),
+  pinned_by bigint NOT NULL REFERENCES users(id),
+  created_at timestamptz DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (chat_id, msg_id)
+);
+
+CREATE TABLE IF NOT EXISTS chat_bookmarks(
+  id bigserial PRIMARY KEY,
+  chat_id bigint NOT NULL REFERENCES chats(id),
+  title varchar(128) NOT NULL,
+  url varchar(2048) NOT NULL,
+  created_by bigint NOT NULL REFERENCES users(id),
+  created_at timestamptz DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS chat_bookmarks_chat_idx ON chat_bookmarks(chat_id, id);
+
+CREATE OR REPLACE FUNCTION chat_pin_changed()
+    RETURNS TRIGGER
+    AS $$
+DECLARE
+    REC chat_pins;
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        REC := NEW;
+    ELSE
+        REC := OLD;
+    END IF;
+    PERFORM
+        pg_notify('chat_pin_changed', json_build_object('op', TG_OP, 'pin', REC, 'members', ARRAY(
+                    SELECT
+                        user_id FROM chat_members
+                    WHERE
+                        chat_id = REC.chat_id))::text);
+    RETURN REC;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE TRIGGER chat_pin_changed_trigger
+    AFTER INSERT OR DELETE ON chat_pins
+    FOR EACH ROW
+        EXECUTE FUNCTION chat_pin_changed();
+
+CREATE OR REPLACE FUNCTION chat_bookmark_changed()
+    RETURNS TRIGGER
+    AS $$
+DECLARE
+    REC chat_bookmarks;
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        REC := NEW;
+    ELSE
+        REC := OLD;
+    END IF;
+    PERFORM
+        pg_notify('chat_bookmark_changed', json_build_object('op', TG_OP, 'bookmark', REC, 'members', ARRAY(
+                    SELECT
+                        user_id FROM chat_members
+                    WHERE
+                        chat_id = REC.chat_id))::text);
+    RETURN REC;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE TRIGGER chat_bookmark_changed_trigger
+    AFTER INSERT OR DELETE ON chat_bookmarks
+    FOR EACH ROW
+        EXECUTE FUNCTION chat_bookmark_changed();
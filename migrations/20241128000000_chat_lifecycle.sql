@@ -0,0 +1,31 @@
+-- explicit chat lifecycle instead of the status flag
+CREATE TYPE chat_status AS ENUM(
+  'active',
+  'archived',
+  'deleted',
+  'purged'
+);
+
+ALTER TABLE chats RENAME COLUMN status TO status_flag;
+
+ALTER TABLE chats
+  ADD COLUMN status chat_status NOT NULL DEFAULT 'active',
+  ADD COLUMN status_changed_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+UPDATE chats SET status = 'deleted' WHERE status_flag = 0;
+
+-- drops the partial indexes on the flag as well
+ALTER TABLE chats DROP COLUMN status_flag;
+
+CREATE UNIQUE INDEX IF NOT EXISTS chats_channel_name_idx ON chats(ws_id, lower(name))
+WHERE chat_type IN ('private_channel', 'public_channel') AND status IN ('active', 'archived');
+
+CREATE INDEX IF NOT EXISTS chats_public_channel_idx ON chats(ws_id, name)
+WHERE chat_type = 'public_channel' AND status = 'active';
+
+-- find deleted chats due for purging
+CREATE INDEX IF NOT EXISTS chats_deleted_idx ON chats(status_changed_at)
+WHERE status = 'deleted';
+
+-- messages of a chat are looked up when it is purged
+CREATE INDEX IF NOT EXISTS messages_chat_idx ON messages(chat_id, id);
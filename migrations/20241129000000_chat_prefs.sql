@@ -0,0 +1,40 @@
+-- per member settings of a chat
+CREATE TYPE notif_level AS ENUM(
+  'all',
+  'mentions',
+  'none'
+);
+
+ALTER TABLE chat_members
+  ADD COLUMN muted_until timestamptz,
+  ADD COLUMN favorite boolean NOT NULL DEFAULT FALSE,
+  ADD COLUMN hidden boolean NOT NULL DEFAULT FALSE,
+  ADD COLUMN notif_level notif_level NOT NULL DEFAULT 'all';
+
+-- every member syncs the message, only alerts are subject to the settings
+CREATE OR REPLACE FUNCTION add_to_message()
+    RETURNS TRIGGER
+    AS $$
+DECLARE
+    USERS bigint[];
+    ALERTS bigint[];
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        RAISE NOTICE 'add_to_message: %', NEW;
+        SELECT
+            array_agg(user_id),
+            array_agg(user_id) FILTER (WHERE user_id <> NEW.sender_id
+                AND notif_level = 'all'
+                AND (muted_until IS NULL OR muted_until <= now())) INTO USERS,
+            ALERTS
+        FROM
+            chat_members
+        WHERE
+            chat_id = NEW.chat_id;
+        PERFORM
+            pg_notify('chat_message_created', json_build_object('message', NEW, 'members', COALESCE(USERS, '{}'), 'alerts', COALESCE(ALERTS, '{}'))::text);
+    END IF;
+    RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
@@ -0,0 +1,12 @@
+-- mentions resolved when the message is sent
+CREATE TYPE mention_broadcast AS ENUM(
+  'channel',
+  'here'
+);
+
+ALTER TABLE messages
+  ADD COLUMN mentions bigint[] NOT NULL DEFAULT '{}',
+  ADD COLUMN mention_broadcast mention_broadcast;
+
+-- recent mentions of a user
+CREATE INDEX IF NOT EXISTS messages_mentions_idx ON messages USING gin(mentions);
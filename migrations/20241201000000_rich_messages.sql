@@ -0,0 +1,99 @@
+-- declared format of a message, markdown is parsed into a sanitized tree when sent
+CREATE TYPE content_type AS ENUM(
+  'plain',
+  'markdown'
+);
+
+ALTER TABLE messages
+  ADD COLUMN content_type content_type NOT NULL DEFAULT 'plain',
+  ADD COLUMN rich jsonb,
+  ADD COLUMN previews jsonb NOT NULL DEFAULT '[]';
+
+-- previews cached per url, failed fetches too so a dead link is not fetched for every message
+CREATE TABLE IF NOT EXISTS link_previews(
+  url varchar(2048) PRIMARY KEY,
+  title varchar(256),
+  description varchar(1024),
+  image_url varchar(2048),
+  ok boolean NOT NULL,
+  fetched_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- links of a message waiting to be unfurled, a claimed job is leased
+-- so the job of a crashed worker is picked up again
+CREATE TABLE IF NOT EXISTS unfurl_jobs(
+  msg_id bigint PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+  urls text[] NOT NULL,
+  attempts int NOT NULL DEFAULT 0,
+  locked_until timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS unfurl_jobs_locked_idx ON unfurl_jobs(locked_until);
+
+-- pg_notify payloads are limited to 8000 bytes, listeners parse the content again
+-- when the tree is dropped
+CREATE OR REPLACE FUNCTION message_json(_msg messages)
+    RETURNS jsonb
+    AS $$
+    SELECT
+        CASE WHEN octet_length(to_jsonb(_msg)::text) > 6000 THEN
+            to_jsonb(_msg) - 'rich'
+        ELSE
+            to_jsonb(_msg)
+        END;
+$$
+LANGUAGE sql
+IMMUTABLE;
+
+CREATE OR REPLACE FUNCTION add_to_message()
+    RETURNS TRIGGER
+    AS $$
+DECLARE
+    USERS bigint[];
+    ALERTS bigint[];
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        RAISE NOTICE 'add_to_message: %', NEW;
+        SELECT
+            array_agg(user_id),
+            array_agg(user_id) FILTER (WHERE user_id <> NEW.sender_id
+                AND notif_level = 'all'
+                AND (muted_until IS NULL OR muted_until <= now())) INTO USERS,
+            ALERTS
+        FROM
+            chat_members
+        WHERE
+            chat_id = NEW.chat_id;
+        PERFORM
+            pg_notify('chat_message_created', json_build_object('message', message_json(NEW), 'members', COALESCE(USERS, '{}'), 'alerts', COALESCE(ALERTS, '{}'))::text);
+    END IF;
+    RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION message_unfurled()
+    RETURNS TRIGGER
+    AS $$
+DECLARE
+    USERS bigint[];
+BEGIN
+    SELECT
+        array_agg(user_id) INTO USERS
+    FROM
+        chat_members
+    WHERE
+        chat_id = NEW.chat_id;
+    PERFORM
+        pg_notify('chat_message_unfurled', json_build_object('message', message_json(NEW), 'members', COALESCE(USERS, '{}'))::text);
+    RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
+
+-- erasing previews is not announced
+CREATE TRIGGER message_unfurled_trigger
+    AFTER UPDATE OF previews ON messages
+    FOR EACH ROW
+    WHEN (OLD.previews IS DISTINCT FROM NEW.previews AND NEW.previews <> '[]'::jsonb)
+    EXECUTE FUNCTION message_unfurled();
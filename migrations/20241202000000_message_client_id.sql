@@ -0,0 +1,7 @@
+-- id generated by the client so a retried send stores the message once
+ALTER TABLE messages
+  ADD COLUMN client_id varchar(64);
+
+CREATE UNIQUE INDEX IF NOT EXISTS messages_client_id_idx ON messages(chat_id, sender_id, client_id)
+WHERE
+  client_id IS NOT NULL;
@@ -0,0 +1,64 @@
+-- messages sent and reminders fired at a later time
+CREATE TYPE schedule_kind AS ENUM(
+  'message',
+  'reminder'
+);
+
+CREATE TYPE schedule_status AS ENUM(
+  'pending',
+  'done',
+  'failed',
+  'canceled'
+);
+
+CREATE TABLE IF NOT EXISTS scheduled_items(
+  id bigserial PRIMARY KEY,
+  kind schedule_kind NOT NULL,
+  user_id bigint NOT NULL REFERENCES users(id),
+  ws_id bigint NOT NULL REFERENCES workspaces(id),
+  chat_id bigint NOT NULL REFERENCES chats(id),
+  -- the message of a reminder
+  msg_id bigint REFERENCES messages(id) ON DELETE CASCADE,
+  -- message to send or note of a reminder
+  content text NOT NULL DEFAULT '',
+  content_type content_type NOT NULL DEFAULT 'plain',
+  run_at timestamptz NOT NULL,
+  status schedule_status NOT NULL DEFAULT 'pending',
+  -- a claimed item is leased so the item of a crashed scheduler is picked up again
+  locked_until timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  attempts int NOT NULL DEFAULT 0,
+  -- the message sent for a scheduled message
+  sent_msg_id bigint,
+  error text,
+  created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS scheduled_items_due_idx ON scheduled_items(run_at)
+WHERE
+  status = 'pending';
+
+CREATE INDEX IF NOT EXISTS scheduled_items_user_idx ON scheduled_items(user_id, ws_id, run_at);
+
+-- fires once, the status leaves pending only once
+CREATE OR REPLACE FUNCTION reminder_due()
+    RETURNS TRIGGER
+    AS $$
+BEGIN
+    PERFORM
+        pg_notify('reminder_due', json_build_object('reminder', NEW, 'message', (
+                    SELECT
+                        message_json(m)
+                    FROM messages m
+                    WHERE
+                        m.id = NEW.msg_id))::text);
+    RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE TRIGGER reminder_due_trigger
+    AFTER UPDATE OF status ON scheduled_items
+    FOR EACH ROW
+    WHEN (NEW.kind = 'reminder' AND OLD.status = 'pending' AND NEW.status = 'done')
+    EXECUTE FUNCTION reminder_due();
@@ -0,0 +1,5 @@
+-- where a forwarded message comes from, kept when the original is gone
+ALTER TABLE messages
+  ADD COLUMN forwarded jsonb,
+  -- a message of the same chat quoted inline
+  ADD COLUMN quote_id bigint REFERENCES messages(id) ON DELETE SET NULL;
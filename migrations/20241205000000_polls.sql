@@ -0,0 +1,52 @@
+-- a poll message has the question as content
+ALTER TYPE content_type ADD VALUE IF NOT EXISTS 'poll';
+
+CREATE TABLE IF NOT EXISTS polls(
+  msg_id bigint PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+  chat_id bigint NOT NULL REFERENCES chats(id),
+  question varchar(300) NOT NULL,
+  options varchar(100)[] NOT NULL,
+  multiple boolean NOT NULL DEFAULT FALSE,
+  anonymous boolean NOT NULL DEFAULT FALSE,
+  closes_at timestamptz,
+  closed boolean NOT NULL DEFAULT FALSE,
+  created_by bigint NOT NULL REFERENCES users(id),
+  -- votes per option and number of voters, recounted with every vote
+  counts int[] NOT NULL,
+  voters int NOT NULL DEFAULT 0,
+  created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS poll_votes(
+  msg_id bigint NOT NULL REFERENCES polls(msg_id) ON DELETE CASCADE,
+  user_id bigint NOT NULL REFERENCES users(id),
+  -- index into the options of the poll
+  option smallint NOT NULL,
+  created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (msg_id, user_id, option)
+);
+
+CREATE OR REPLACE FUNCTION poll_updated()
+    RETURNS TRIGGER
+    AS $$
+DECLARE
+    USERS bigint[];
+BEGIN
+    SELECT
+        array_agg(user_id) INTO USERS
+    FROM
+        chat_members
+    WHERE
+        chat_id = NEW.chat_id;
+    PERFORM
+        pg_notify('poll_updated', json_build_object('poll', NEW, 'members', COALESCE(USERS, '{}'))::text);
+    RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE TRIGGER poll_updated_trigger
+    AFTER UPDATE ON polls
+    FOR EACH ROW
+    WHEN (OLD.counts IS DISTINCT FROM NEW.counts OR OLD.voters <> NEW.voters OR OLD.closed <> NEW.closed)
+    EXECUTE FUNCTION poll_updated();
@@ -0,0 +1,24 @@
+-- how long messages are kept, a chat inherits the policy of its workspace unless it sets its own
+CREATE TYPE retention_mode AS ENUM(
+  'inherit',
+  'forever',
+  'days'
+);
+
+-- a legal hold keeps every message whatever the policy
+ALTER TABLE workspaces
+  ADD COLUMN retention retention_mode NOT NULL DEFAULT 'forever',
+  ADD COLUMN retention_days int,
+  ADD COLUMN legal_hold boolean NOT NULL DEFAULT FALSE,
+  ADD CONSTRAINT workspaces_retention_check CHECK (retention <> 'inherit'
+    AND (retention = 'days') = (retention_days IS NOT NULL AND retention_days > 0));
+
+ALTER TABLE chats
+  ADD COLUMN retention retention_mode NOT NULL DEFAULT 'inherit',
+  ADD COLUMN retention_days int,
+  ADD COLUMN legal_hold boolean NOT NULL DEFAULT FALSE,
+  ADD CONSTRAINT chats_retention_check CHECK ((retention = 'days') = (retention_days IS NOT NULL
+    AND retention_days > 0));
+
+-- the sweeper deletes the oldest messages of a chat first
+CREATE INDEX IF NOT EXISTS messages_chat_created_idx ON messages(chat_id, created_at);
@@ -0,0 +1,51 @@
+-- members report messages to the workspace admins, who moderate from a queue
+CREATE TYPE report_status AS ENUM(
+  'open',
+  'resolved',
+  'dismissed'
+);
+
+-- the reported message is copied so the report outlives it
+CREATE TABLE IF NOT EXISTS message_reports(
+  id bigserial PRIMARY KEY,
+  ws_id bigint NOT NULL REFERENCES workspaces(id),
+  chat_id bigint NOT NULL REFERENCES chats(id),
+  msg_id bigint REFERENCES messages(id) ON DELETE SET NULL,
+  sender_id bigint NOT NULL REFERENCES users(id),
+  content text NOT NULL,
+  reporter_id bigint NOT NULL REFERENCES users(id),
+  reason varchar(500) NOT NULL,
+  status report_status NOT NULL DEFAULT 'open',
+  resolved_by bigint REFERENCES users(id),
+  note varchar(500),
+  created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  resolved_at timestamptz
+);
+
+CREATE INDEX IF NOT EXISTS message_reports_queue_idx ON message_reports(ws_id, status, id);
+
+-- a member reports a message once while the report is open
+CREATE UNIQUE INDEX IF NOT EXISTS message_reports_open_idx ON message_reports(msg_id, reporter_id)
+WHERE
+  status = 'open';
+
+-- muted members can read but not post until the mute is over
+CREATE TABLE IF NOT EXISTS chat_mutes(
+  chat_id bigint NOT NULL REFERENCES chats(id),
+  user_id bigint NOT NULL REFERENCES users(id),
+  muted_until timestamptz NOT NULL,
+  muted_by bigint NOT NULL REFERENCES users(id),
+  reason varchar(500) NOT NULL DEFAULT '',
+  created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (chat_id, user_id)
+);
+
+-- banned users are removed from the channel and can not join it again
+CREATE TABLE IF NOT EXISTS chat_bans(
+  chat_id bigint NOT NULL REFERENCES chats(id),
+  user_id bigint NOT NULL REFERENCES users(id),
+  banned_by bigint NOT NULL REFERENCES users(id),
+  reason varchar(500) NOT NULL DEFAULT '',
+  created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (chat_id, user_id)
+);
@@ -0,0 +1,10 @@
+-- token buckets shared by the replicas, a bucket is the theoretical arrival time of the next
+-- request (GCRA), it is full again once that time has passed
+CREATE UNLOGGED TABLE IF NOT EXISTS rate_buckets(
+  key varchar(256) PRIMARY KEY,
+  tat timestamptz NOT NULL
+);
+
+-- members of a channel in slow mode post once per interval
+ALTER TABLE chats
+  ADD COLUMN slow_mode_secs int NOT NULL DEFAULT 0 CHECK (slow_mode_secs BETWEEN 0 AND 21600);
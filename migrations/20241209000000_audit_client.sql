@@ -0,0 +1,25 @@
+-- who did what from where, audit events are never changed once written
+ALTER TABLE audit_events
+  ADD COLUMN ip varchar(64),
+  ADD COLUMN user_agent varchar(256);
+
+CREATE INDEX IF NOT EXISTS audit_events_ws_id_idx ON audit_events(ws_id, id);
+
+CREATE OR REPLACE FUNCTION audit_events_append_only()
+  RETURNS TRIGGER
+  AS $$
+BEGIN
+  RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_append_only_trigger
+  BEFORE UPDATE OR DELETE ON audit_events
+  FOR EACH ROW
+  EXECUTE FUNCTION audit_events_append_only();
+
+CREATE TRIGGER audit_events_no_truncate_trigger
+  BEFORE TRUNCATE ON audit_events
+  FOR EACH STATEMENT
+  EXECUTE FUNCTION audit_events_append_only();
@@ -0,0 +1,41 @@
+-- workspace admins register endpoints that receive workspace events
+CREATE TABLE IF NOT EXISTS webhooks(
+  id bigserial PRIMARY KEY,
+  ws_id bigint NOT NULL REFERENCES workspaces(id),
+  url varchar(2048) NOT NULL,
+  -- signs every delivery, shown once when the webhook is created
+  secret varchar(128) NOT NULL,
+  events text[] NOT NULL,
+  active boolean NOT NULL DEFAULT TRUE,
+  created_by bigint NOT NULL REFERENCES users(id),
+  created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS webhooks_ws_idx ON webhooks(ws_id);
+
+CREATE TYPE webhook_delivery_status AS ENUM(
+  'pending',
+  'delivered',
+  'dead'
+);
+
+-- one row per event and webhook, retried with backoff until delivered or dead
+CREATE TABLE IF NOT EXISTS webhook_deliveries(
+  id bigserial PRIMARY KEY,
+  webhook_id bigint NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+  event varchar(64) NOT NULL,
+  payload jsonb NOT NULL,
+  status webhook_delivery_status NOT NULL DEFAULT 'pending',
+  attempts int NOT NULL DEFAULT 0,
+  next_attempt_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  last_status smallint,
+  last_error varchar(500),
+  created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  delivered_at timestamptz
+);
+
+CREATE INDEX IF NOT EXISTS webhook_deliveries_due_idx ON webhook_deliveries(next_attempt_at)
+WHERE
+  status = 'pending';
+
+CREATE INDEX IF NOT EXISTS webhook_deliveries_log_idx ON webhook_deliveries(webhook_id, id);
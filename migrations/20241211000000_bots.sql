@@ -0,0 +1,56 @@
+-- bot accounts are workspace members that authenticate with api tokens, never a password
+ALTER TABLE users
+  ADD COLUMN is_bot boolean NOT NULL DEFAULT FALSE;
+
+-- clients render messages of bots differently
+ALTER TABLE messages
+  ADD COLUMN bot boolean NOT NULL DEFAULT FALSE;
+
+-- taken from the sender so no insert path can get it wrong
+CREATE OR REPLACE FUNCTION message_bot()
+  RETURNS TRIGGER
+  AS $$
+BEGIN
+  NEW.bot := coalesce((
+    SELECT
+      is_bot
+    FROM users
+    WHERE
+      id = NEW.sender_id), FALSE);
+  RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE TRIGGER message_bot_trigger
+  BEFORE INSERT ON messages
+  FOR EACH ROW
+  EXECUTE FUNCTION message_bot();
+
+-- only the sha256 of a token is kept, the token is shown once when it is made
+CREATE TABLE IF NOT EXISTS bot_tokens(
+  id bigserial PRIMARY KEY,
+  bot_id bigint NOT NULL REFERENCES users(id),
+  name varchar(64) NOT NULL,
+  token_hash varchar(64) NOT NULL UNIQUE,
+  created_by bigint NOT NULL REFERENCES users(id),
+  created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  last_used_at timestamptz,
+  revoked_at timestamptz
+);
+
+CREATE INDEX IF NOT EXISTS bot_tokens_bot_idx ON bot_tokens(bot_id);
+
+-- a secret url posting as the bot into one chat
+CREATE TABLE IF NOT EXISTS incoming_webhooks(
+  id bigserial PRIMARY KEY,
+  ws_id bigint NOT NULL REFERENCES workspaces(id),
+  bot_id bigint NOT NULL REFERENCES users(id),
+  chat_id bigint NOT NULL REFERENCES chats(id),
+  token_hash varchar(64) NOT NULL UNIQUE,
+  created_by bigint NOT NULL REFERENCES users(id),
+  created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  revoked_at timestamptz
+);
+
+CREATE INDEX IF NOT EXISTS incoming_webhooks_bot_idx ON incoming_webhooks(bot_id);
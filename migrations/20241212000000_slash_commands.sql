@@ -0,0 +1,13 @@
+-- workspace admins register commands answered by an external endpoint
+CREATE TABLE IF NOT EXISTS slash_commands(
+  id bigserial PRIMARY KEY,
+  ws_id bigint NOT NULL REFERENCES workspaces(id),
+  name varchar(32) NOT NULL,
+  url varchar(2048) NOT NULL,
+  description varchar(256) NOT NULL DEFAULT '',
+  -- signs every call, shown once when the command is created
+  secret varchar(128) NOT NULL,
+  created_by bigint NOT NULL REFERENCES users(id),
+  created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE (ws_id, name)
+);
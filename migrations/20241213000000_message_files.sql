@@ -0,0 +1,11 @@
+-- the files attached to a message, recorded when it is sent so cleanups join instead of
+-- searching every message content; rows outlive their message until its files are cleaned up
+CREATE TABLE IF NOT EXISTS message_files(
+  msg_id bigint NOT NULL,
+  chat_id bigint NOT NULL,
+  file_id bigint NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+  PRIMARY KEY (msg_id, file_id)
+);
+
+CREATE INDEX IF NOT EXISTS message_files_file_idx ON message_files(file_id);
+CREATE INDEX IF NOT EXISTS message_files_chat_idx ON message_files(chat_id);
@@ -0,0 +1,4 @@
+-- forwarded copies are erased along with the messages of their original sender
+CREATE INDEX IF NOT EXISTS messages_forwarded_sender_idx
+  ON messages (((forwarded->>'sender_id')::bigint))
+  WHERE forwarded IS NOT NULL;
@@ -0,0 +1,60 @@
+-- every replica queues the events it is notified of, the key of the notification
+-- lets only the first of them queue a delivery
+ALTER TABLE webhook_deliveries
+  ADD COLUMN event_key varchar(64);
+
+UPDATE webhook_deliveries SET event_key = id::text;
+
+ALTER TABLE webhook_deliveries
+  ALTER COLUMN event_key SET NOT NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS webhook_deliveries_event_idx ON webhook_deliveries(webhook_id, event, event_key);
+
+-- the transaction tells apart the same change made twice, such as a member added again
+CREATE OR REPLACE FUNCTION add_to_chat()
+    RETURNS TRIGGER
+    AS $$
+BEGIN
+    RAISE NOTICE 'add_to_chat: %', NEW;
+    PERFORM
+        pg_notify('chat_updated', json_build_object('op', TG_OP, 'old', chat_json(OLD), 'new', chat_json(NEW), 'txid', txid_current())::text);
+    RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION chat_members_changed()
+    RETURNS TRIGGER
+    AS $$
+DECLARE
+    REC record;
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        FOR REC IN
+        SELECT
+            c, array_agg(a.user_id ORDER BY a.user_id) AS user_ids
+        FROM
+            added a
+            JOIN chats c ON c.id = a.chat_id
+        GROUP BY
+            c.id LOOP
+                PERFORM
+                    pg_notify('chat_members_changed', json_build_object('op', TG_OP, 'chat', chat_json(REC.c), 'user_ids', REC.user_ids, 'txid', txid_current())::text);
+            END LOOP;
+    ELSE
+        FOR REC IN
+        SELECT
+            c, array_agg(d.user_id ORDER BY d.user_id) AS user_ids
+        FROM
+            removed d
+            JOIN chats c ON c.id = d.chat_id
+        GROUP BY
+            c.id LOOP
+                PERFORM
+                    pg_notify('chat_members_changed', json_build_object('op', TG_OP, 'chat', chat_json(REC.c), 'user_ids', REC.user_ids, 'txid', txid_current())::text);
+            END LOOP;
+    END IF;
+    RETURN NULL;
+END;
+$$
+LANGUAGE plpgsql;
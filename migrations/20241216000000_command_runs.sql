@@ -0,0 +1,10 @@
+-- a command sent with a client message id runs once, a retry gets the output of the first run
+CREATE TABLE IF NOT EXISTS command_runs(
+  chat_id bigint NOT NULL,
+  user_id bigint NOT NULL,
+  client_id varchar(64) NOT NULL,
+  -- null while the command runs
+  output jsonb,
+  started_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (chat_id, user_id, client_id)
+);
@@ -0,0 +1,9 @@
+-- every replica is notified of every event, the replica that claims the alert of a user sends it
+CREATE TABLE IF NOT EXISTS alert_claims(
+  event_key varchar(64) NOT NULL,
+  user_id bigint NOT NULL,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  PRIMARY KEY (event_key, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS alert_claims_created_at_idx ON alert_claims(created_at);
@@ -0,0 +1,9 @@
+-- the member who created the chat manages it along with the workspace admins
+ALTER TABLE chats
+  ADD COLUMN IF NOT EXISTS created_by bigint REFERENCES users(id);
+
+-- chats created before this migration take the actor of their creation in the audit log
+UPDATE chats c
+SET created_by = a.actor_id
+FROM audit_events a
+WHERE a.action = 'chat_create' AND a.target = 'chat:' || c.id AND c.created_by IS NULL;
@@ -0,0 +1,18 @@
+-- every replica forgets the removed member when it fans out the events of the workspace
+CREATE OR REPLACE FUNCTION ws_member_removed()
+    RETURNS TRIGGER
+    AS $$
+BEGIN
+    PERFORM
+        pg_notify('ws_member_removed', json_build_object('ws_id', OLD.ws_id, 'user_id', OLD.user_id)::text);
+    RETURN OLD;
+END;
+$$
+LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS ws_member_removed_trigger ON ws_members;
+
+CREATE TRIGGER ws_member_removed_trigger
+    AFTER DELETE ON ws_members
+    FOR EACH ROW
+        EXECUTE FUNCTION ws_member_removed();
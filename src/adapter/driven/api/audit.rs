@@ -0,0 +1,42 @@
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+
+use crate::{
+    error::AppError,
+    service::{audit::AuditQueryDto, auth::ClaimUser},
+    AppState,
+};
+
+pub async fn list(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Query(input): Query<AuditQueryDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state.audit.list(user.ws_id, input).await.map(Json)
+}
+
+pub async fn export(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Query(input): Query<AuditQueryDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let format = input.format;
+    let body = state.audit.export(&user, input).await?;
+    let disposition = format!(
+        "attachment; filename=\"audit-{}.{}\"",
+        user.ws_id,
+        format.extension()
+    );
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, format.content_type().to_owned()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    ))
+}
@@ -2,6 +2,7 @@ use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use serde::Serialize;
 
 use crate::{
+    domain::audit::ClientInfo,
     error::AppError,
     service::auth::{SigninUserDto, SignupUserDto},
     AppState,
@@ -18,16 +19,18 @@ impl TokenDto {
 }
 pub async fn signin_handler(
     State(state): State<AppState>,
+    client: ClientInfo,
     Json(input): Json<SigninUserDto>,
 ) -> Result<impl IntoResponse, AppError> {
-    let token = state.auth.signin(input).await?;
+    let token = state.auth.signin(input, &client).await?;
     Ok((StatusCode::OK, Json(TokenDto::new(token))))
 }
 
 pub async fn signup_handler(
     State(state): State<AppState>,
+    client: ClientInfo,
     Json(input): Json<SignupUserDto>,
 ) -> Result<impl IntoResponse, AppError> {
-    let token = state.auth.signup(input).await?;
+    let token = state.auth.signup(input, &client).await?;
     Ok((StatusCode::CREATED, Json(TokenDto::new(token))))
 }
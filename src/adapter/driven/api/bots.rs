@@ -0,0 +1,115 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+use crate::{
+    domain::audit::ClientInfo,
+    error::AppError,
+    service::{
+        auth::ClaimUser,
+        bot::{CreateBotDto, CreateBotTokenDto, CreateIncomingWebhookDto},
+        chat::SendMsgDto,
+    },
+    AppState,
+};
+
+pub async fn create(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Json(input): Json<CreateBotDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let bot = state.bot.create(&user, input).await?;
+    Ok((StatusCode::CREATED, Json(bot)))
+}
+
+pub async fn list_all(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+) -> Result<impl IntoResponse, AppError> {
+    state.bot.list(user.ws_id).await.map(Json)
+}
+
+pub async fn deactivate(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    state.bot.deactivate(&user, id).await.map(Json)
+}
+
+pub async fn create_token(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+    Json(input): Json<CreateBotTokenDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let token = state.bot.create_token(&user, id, input).await?;
+    Ok((StatusCode::CREATED, Json(token)))
+}
+
+pub async fn list_tokens(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    state.bot.list_tokens(user.ws_id, id).await.map(Json)
+}
+
+pub async fn revoke_token(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path((id, token_id)): Path<(u64, u64)>,
+) -> Result<impl IntoResponse, AppError> {
+    state.bot.revoke_token(&user, id, token_id).await.map(Json)
+}
+
+pub async fn create_hook(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+    Json(input): Json<CreateIncomingWebhookDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let hook = state.bot.create_hook(&state.chat, &user, id, input).await?;
+    Ok((StatusCode::CREATED, Json(hook)))
+}
+
+pub async fn list_hooks(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    state.bot.list_hooks(user.ws_id, id).await.map(Json)
+}
+
+pub async fn revoke_hook(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path((id, hook_id)): Path<(u64, u64)>,
+) -> Result<impl IntoResponse, AppError> {
+    state.bot.revoke_hook(&user, id, hook_id).await.map(Json)
+}
+
+/// the token in the path is the only credential, the message is posted as the bot
+pub async fn incoming(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    client: ClientInfo,
+    Json(input): Json<SendMsgDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let (mut bot, chat_id) = state.bot.resolve_hook(&token).await?;
+    bot.client = client;
+    // the bot may have been removed from the chat since the hook was made
+    if !state.chat.can_access(&bot, chat_id).await? {
+        return Err(AppError::NotFound("incoming webhook".to_owned()));
+    }
+    state
+        .rate_limit
+        .check(&bot, "POST /api/chats/:id", Some(chat_id))
+        .await?;
+    // hooks post plain text, commands are run for members only
+    let msg = state.chat.send_msg(&bot, chat_id, input).await?;
+    Ok((StatusCode::CREATED, Json(msg)))
+}
@@ -0,0 +1,29 @@
+use crate::{
+    error::AppError,
+    service::{auth::ClaimUser, chat::SearchChannelsDto},
+    AppState,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+pub async fn list_all(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Query(input): Query<SearchChannelsDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state.chat.list_channels(&user, input).await.map(Json)
+}
+
+pub async fn join(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    let chat = state.chat.join(&user, id).await?;
+    Ok((StatusCode::OK, Json(chat)))
+}
@@ -4,52 +4,202 @@ use crate::{
     error::AppError,
     service::{
         auth::ClaimUser,
-        chat::{CreateChatDto, ListOptionsDto, SendMsgDto, UpdateChatDto},
+        chat::{
+            AddBookmarkDto, AddMembersDto, CreateChatDto, ForwardMsgDto, ListChatsDto,
+            ListMessagesDto, ListOptionsDto, PinMsgDto, PostOutput, SendMsgDto, SlowModeDto,
+            UpdateChatDto, UpdateChatPrefsDto, UpdateHeaderDto,
+        },
     },
     AppState,
 };
 
 use axum::{
     extract::{Multipart, Path, Query, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::{
         sse::{Event, KeepAlive},
-        IntoResponse, Sse,
+        IntoResponse, Response, Sse,
     },
     Extension, Json,
 };
 use futures::Stream;
 use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
 pub async fn get(
     State(state): State<AppState>,
     Path(id): Path<u64>,
 ) -> Result<impl IntoResponse, AppError> {
-    let chat = state.chat.get_by_id(id).await?;
+    let chat = state
+        .chat
+        .get_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("chat {}", id)))?;
     Ok((StatusCode::CREATED, Json(chat)))
 }
 
 pub async fn update(
     State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
     Path(id): Path<u64>,
     Json(input): Json<UpdateChatDto>,
 ) -> Result<impl IntoResponse, AppError> {
-    let msg = state.chat.update(id, input).await?;
+    let msg = state.chat.update(&user, id, input).await?;
     Ok((StatusCode::CREATED, Json(msg)))
 }
 
 pub async fn delete(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    let chat = state.chat.delete(&user, id).await?;
+    Ok((StatusCode::OK, Json(chat)))
+}
+
+pub async fn archive(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    let chat = state.chat.archive(&user, id).await?;
+    Ok((StatusCode::OK, Json(chat)))
+}
+
+pub async fn restore(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    let chat = state.chat.restore(&user, id).await?;
+    Ok((StatusCode::OK, Json(chat)))
+}
+
+pub async fn purge(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    let chat = state.chat.purge(&state.file, &user, id).await?;
+    Ok((StatusCode::OK, Json(chat)))
+}
+
+pub async fn add_members(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+    Json(input): Json<AddMembersDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let chat = state.chat.add_members(&user, id, input).await?;
+    Ok((StatusCode::OK, Json(chat)))
+}
+
+pub async fn remove_member(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path((id, uid)): Path<(u64, u64)>,
+) -> Result<impl IntoResponse, AppError> {
+    let chat = state.chat.remove_member(&user, id, uid).await?;
+    Ok((StatusCode::OK, Json(chat)))
+}
+
+pub async fn leave(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    state.chat.leave(&user, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn update_prefs(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+    Json(input): Json<UpdateChatPrefsDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let prefs = state.chat.update_prefs(&user, id, input).await?;
+    Ok((StatusCode::OK, Json(prefs)))
+}
+
+pub async fn update_header(
     State(state): State<AppState>,
     Path(id): Path<u64>,
+    Json(input): Json<UpdateHeaderDto>,
 ) -> Result<impl IntoResponse, AppError> {
-    let chat = state.chat.delete(id).await?;
+    let chat = state.chat.update_header(id, input).await?;
     Ok((StatusCode::OK, Json(chat)))
 }
 
+pub async fn get_slow_mode(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    let seconds = state.chat.slow_mode(id).await?;
+    Ok((StatusCode::OK, Json(SlowModeDto { seconds })))
+}
+
+pub async fn list_pins(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    state.chat.list_pins(id).await.map(Json)
+}
+
+pub async fn pin(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+    Json(input): Json<PinMsgDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let pin = state.chat.pin(&user, id, input).await?;
+    Ok((StatusCode::CREATED, Json(pin)))
+}
+
+pub async fn unpin(
+    State(state): State<AppState>,
+    Path((id, msg_id)): Path<(u64, u64)>,
+) -> Result<impl IntoResponse, AppError> {
+    state.chat.unpin(id, msg_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_bookmarks(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    state.chat.list_bookmarks(id).await.map(Json)
+}
+
+pub async fn add_bookmark(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+    Json(input): Json<AddBookmarkDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let bookmark = state.chat.add_bookmark(&user, id, input).await?;
+    Ok((StatusCode::CREATED, Json(bookmark)))
+}
+
+pub async fn remove_bookmark(
+    State(state): State<AppState>,
+    Path((id, bid)): Path<(u64, u64)>,
+) -> Result<impl IntoResponse, AppError> {
+    state.chat.remove_bookmark(id, bid).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub async fn list_all(
     State(state): State<AppState>,
     Extension(user): Extension<ClaimUser>,
+    Query(input): Query<ListChatsDto>,
 ) -> Result<impl IntoResponse, AppError> {
-    state.chat.list_all(user.id, user.ws_id).await.map(Json)
+    state
+        .chat
+        .list_all(user.id, user.ws_id, input)
+        .await
+        .map(Json)
 }
 
 pub async fn create(
@@ -57,7 +207,7 @@ pub async fn create(
     Extension(user): Extension<ClaimUser>,
     Json(input): Json<CreateChatDto>,
 ) -> Result<impl IntoResponse, AppError> {
-    let chat = state.chat.create(user.id, user.ws_id, input).await?;
+    let chat = state.chat.create(&user, input).await?;
     Ok((StatusCode::CREATED, Json(chat)))
 }
 
@@ -65,20 +215,64 @@ pub async fn send_msg(
     State(state): State<AppState>,
     Extension(user): Extension<ClaimUser>,
     Path(id): Path<u64>,
-    Json(input): Json<SendMsgDto>,
-) -> Result<impl IntoResponse, AppError> {
-    let msg = state.chat.send_msg(&user, id, input).await?;
-    Ok((StatusCode::CREATED, Json(msg)))
+    headers: HeaderMap,
+    Json(mut input): Json<SendMsgDto>,
+) -> Result<Response, AppError> {
+    if let Some(key) = headers.get(IDEMPOTENCY_KEY) {
+        let key = key
+            .to_str()
+            .map_err(|_| AppError::InvalidError("invalid idempotency key".to_owned()))?;
+        match &input.client_id {
+            Some(client_id) if client_id != key => {
+                return Err(AppError::InvalidError(
+                    "idempotency key and client message id differ".to_owned(),
+                ))
+            }
+            _ => input.client_id = Some(key.to_owned()),
+        }
+    }
+    // a command is run instead of being posted, its reply may be posted
+    match state.chat.post_msg(&user, id, input).await? {
+        PostOutput::Posted(msg) => Ok((StatusCode::CREATED, Json(msg)).into_response()),
+        PostOutput::Ephemeral(msg) => Ok((StatusCode::OK, Json(msg)).into_response()),
+    }
 }
 
 pub async fn list_messages(
     State(state): State<AppState>,
     Path(id): Path<u64>,
+    Query(input): Query<ListMessagesDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let page = state.chat.list_messages(id, input).await?;
+    Ok((StatusCode::OK, Json(page)))
+}
+
+pub async fn forward_msg(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path((id, msg_id)): Path<(u64, u64)>,
+    Json(input): Json<ForwardMsgDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let msg = state.chat.forward_msg(&user, id, msg_id, input).await?;
+    Ok((StatusCode::CREATED, Json(msg)))
+}
+
+pub async fn get_msg(
+    State(state): State<AppState>,
+    Path((id, msg_id)): Path<(u64, u64)>,
+) -> Result<impl IntoResponse, AppError> {
+    let msg = state.chat.get_msg(id, msg_id).await?;
+    Ok((StatusCode::OK, Json(msg)))
+}
+
+pub async fn list_mentions(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
     Query(input): Query<ListOptionsDto>,
 ) -> Result<impl IntoResponse, AppError> {
     let last_id = input.last_id.unwrap_or(i64::MAX as _);
     let limit = input.limit.unwrap_or(10);
-    let msgs = state.chat.list_messages(id, last_id, limit as _).await?;
+    let msgs = state.chat.list_mentions(&user, last_id, limit as _).await?;
     Ok((StatusCode::OK, Json(msgs)))
 }
 
@@ -89,7 +283,7 @@ pub async fn upload(
 ) -> Result<impl IntoResponse, AppError> {
     let mut urls = vec![];
     while let Some(field) = multipart.next_field().await.unwrap() {
-        if let Some(url) = state.file.save(user.ws_id, field).await? {
+        if let Some(url) = state.file.save(&user, field).await? {
             urls.push(url);
         }
     }
@@ -100,7 +294,7 @@ pub async fn notif_handler(
     State(state): State<AppState>,
     Extension(user): Extension<ClaimUser>,
 ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
-    let rx = state.notif.register(user.id);
+    let rx = state.notif.register(&user);
     let stream = BroadcastStream::new(rx)
         .filter_map(|v| v.ok())
         .map(|ref v| {
@@ -0,0 +1,47 @@
+use std::{convert::Infallible, net::SocketAddr};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header::USER_AGENT, request::Parts},
+};
+
+use crate::{domain::audit::ClientInfo, AppState};
+
+const FORWARDED_FOR: &str = "x-forwarded-for";
+const MAX_USER_AGENT: usize = 256;
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        // the proxy appends the address it saw, the first one is the client's
+        let forwarded = match state.config.server.behind_proxy {
+            true => parts
+                .headers
+                .get(FORWARDED_FOR)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.split(',').next())
+                .map(|v| v.trim().to_owned())
+                .filter(|v| !v.is_empty() && v.len() <= 64),
+            false => None,
+        };
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string());
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.chars().take(MAX_USER_AGENT).collect());
+        Ok(Self {
+            ip: forwarded.or(peer),
+            user_agent,
+        })
+    }
+}
@@ -0,0 +1,36 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+use crate::{
+    error::AppError,
+    service::{auth::ClaimUser, command::CreateCommandDto},
+    AppState,
+};
+
+pub async fn create(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Json(input): Json<CreateCommandDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let command = state.command.create(&user, input).await?;
+    Ok((StatusCode::CREATED, Json(command)))
+}
+
+pub async fn list_all(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+) -> Result<impl IntoResponse, AppError> {
+    state.command.list(user.ws_id).await.map(Json)
+}
+
+pub async fn delete(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    state.command.delete(&user, id).await.map(Json)
+}
@@ -0,0 +1,25 @@
+use crate::{error::AppError, service::auth::ClaimUser, AppState};
+use anyhow::anyhow;
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse as _, Response},
+    Extension,
+};
+
+pub async fn check_ws_admin(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    req: Request,
+    next: Next,
+) -> Response {
+    match state.workspace.is_admin(&user).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return AppError::PermissionDenyError("workspace admin required".to_owned())
+                .into_response()
+        }
+        Err(_) => return AppError::AnyError(anyhow!("system error")).into_response(),
+    };
+    next.run(req).await
+}
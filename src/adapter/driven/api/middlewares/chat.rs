@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use crate::{error::AppError, service::auth::ClaimUser, AppState};
 use anyhow::anyhow;
 use axum::{
@@ -9,22 +11,39 @@ use axum::{
 
 pub async fn check_msg_perm(
     State(state): State<AppState>,
-    Path(id): Path<u64>,
+    Path(params): Path<HashMap<String, u64>>,
     Extension(user): Extension<ClaimUser>,
     req: Request,
     next: Next,
 ) -> Response {
+    let id = match params.get("id") {
+        Some(id) => *id,
+        None => return AppError::NotFound("chat not exist".to_owned()).into_response(),
+    };
     // check user in chat and chat belongs to ws
-    match state.chat.get_by_id(id).await {
-        Ok(Some(chat)) => {
-            if chat.status == 0
-                || user.ws_id as i64 != chat.ws_id
-                || !chat.members.contains(&(user.id as i64))
-            {
-                return AppError::NotFound("chat not found".to_owned()).into_response();
-            }
-        }
-        Ok(None) => return AppError::NotFound("chat not exist".to_owned()).into_response(),
+    match state.chat.can_access(&user, id).await {
+        Ok(true) => {}
+        Ok(false) => return AppError::NotFound("chat not found".to_owned()).into_response(),
+        Err(_) => return AppError::AnyError(anyhow!("system error")).into_response(),
+    };
+    next.run(req).await
+}
+
+pub async fn check_channel_perm(
+    State(state): State<AppState>,
+    Path(params): Path<HashMap<String, u64>>,
+    Extension(user): Extension<ClaimUser>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let id = match params.get("id") {
+        Some(id) => *id,
+        None => return AppError::NotFound("channel not exist".to_owned()).into_response(),
+    };
+    // check chat is a public channel of the ws
+    match state.chat.can_preview(&user, id).await {
+        Ok(true) => {}
+        Ok(false) => return AppError::NotFound("channel not found".to_owned()).into_response(),
         Err(_) => return AppError::AnyError(anyhow!("system error")).into_response(),
     };
     next.run(req).await
@@ -1,4 +1,8 @@
 mod token;
 pub use token::verify_token;
 mod chat;
-pub use chat::check_msg_perm;
+pub use chat::{check_channel_perm, check_msg_perm};
+mod admin;
+pub use admin::check_ws_admin;
+mod rate_limit;
+pub use rate_limit::rate_limit;
@@ -0,0 +1,40 @@
+use crate::{service::auth::ClaimUser, AppState};
+use axum::{
+    extract::{MatchedPath, RawPathParams, Request, State},
+    middleware::Next,
+    response::{IntoResponse as _, Response},
+    Extension,
+};
+
+/// routes whose `id` is a chat, every chat gets its own buckets
+const CHAT_ROUTES: [&str; 3] = [
+    "/api/chats/:id",
+    "/api/channels/:id",
+    "/api/workspace/chats/:id",
+];
+
+pub async fn rate_limit(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    matched: Option<MatchedPath>,
+    params: Option<RawPathParams>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let path = matched
+        .as_ref()
+        .map(MatchedPath::as_str)
+        .unwrap_or_else(|| req.uri().path());
+    let route = format!("{} {}", req.method(), path);
+    let chat_id = match params {
+        Some(params) if CHAT_ROUTES.iter().any(|v| path.starts_with(v)) => params
+            .iter()
+            .find(|(k, _)| *k == "id")
+            .and_then(|(_, v)| v.parse().ok()),
+        _ => None,
+    };
+    if let Err(e) = state.rate_limit.check(&user, &route, chat_id).await {
+        return e.into_response();
+    }
+    next.run(req).await
+}
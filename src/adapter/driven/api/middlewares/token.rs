@@ -8,12 +8,13 @@ use axum::{
 use axum_extra::{headers::authorization::Bearer, TypedHeader};
 use serde_json::json;
 
-use crate::{common::AuthInfo, error::ErrorOutput, AppState};
+use crate::{common::AuthInfo, domain::audit::ClientInfo, error::ErrorOutput, AppState};
 
 pub async fn verify_token(
     State(state): State<AppState>,
     bearer: Option<TypedHeader<axum_extra::headers::Authorization<Bearer>>>,
     query: Option<Query<AuthInfo>>,
+    client: ClientInfo,
     mut req: Request,
     next: Next,
 ) -> Response {
@@ -28,8 +29,11 @@ pub async fn verify_token(
                 .into_response()
         }
     };
-    match state.auth.verify_token(token) {
-        Ok(user) => req.extensions_mut().insert(user),
+    match state.auth.verify_token(token).await {
+        Ok(mut user) => {
+            user.client = client;
+            req.extensions_mut().insert(user)
+        }
         Err(e) => {
             return (
                 StatusCode::UNAUTHORIZED,
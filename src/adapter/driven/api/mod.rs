@@ -1,4 +1,15 @@
+pub mod audit;
 pub mod auth;
+pub mod bots;
+pub mod channels;
 pub mod chats;
+mod client;
+pub mod commands;
 pub mod middlewares;
+pub mod moderation;
+pub mod polls;
+pub mod retention;
+pub mod scheduled;
 pub mod users;
+pub mod webhooks;
+pub mod workspace;
@@ -0,0 +1,144 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+use crate::{
+    error::AppError,
+    service::{
+        auth::ClaimUser,
+        chat::SlowModeDto,
+        moderation::{
+            BanDto, ListReportsDto, ModerateDto, MuteDto, ReportMsgDto, ResolveReportDto,
+        },
+    },
+    AppState,
+};
+
+pub async fn report(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path((id, msg_id)): Path<(u64, u64)>,
+    Json(input): Json<ReportMsgDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let report = state
+        .moderation
+        .report(&state.chat, &user, id, msg_id, input)
+        .await?;
+    Ok((StatusCode::CREATED, Json(report)))
+}
+
+pub async fn list_reports(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Query(input): Query<ListReportsDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state
+        .moderation
+        .list_reports(user.ws_id, input)
+        .await
+        .map(Json)
+}
+
+pub async fn resolve_report(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+    Json(input): Json<ResolveReportDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state
+        .moderation
+        .resolve_report(&user, id, input)
+        .await
+        .map(Json)
+}
+
+pub async fn delete_msg(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path((id, msg_id)): Path<(u64, u64)>,
+    Query(input): Query<ModerateDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state
+        .moderation
+        .delete_msg(&state.chat, &state.file, &user, id, msg_id, input)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_mutes(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    state
+        .moderation
+        .list_mutes(&state.chat, &user, id)
+        .await
+        .map(Json)
+}
+
+pub async fn mute(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+    Json(input): Json<MuteDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let mute = state.moderation.mute(&state.chat, &user, id, input).await?;
+    Ok((StatusCode::CREATED, Json(mute)))
+}
+
+pub async fn unmute(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path((id, uid)): Path<(u64, u64)>,
+) -> Result<impl IntoResponse, AppError> {
+    state.moderation.unmute(&state.chat, &user, id, uid).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_bans(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    state
+        .moderation
+        .list_bans(&state.chat, &user, id)
+        .await
+        .map(Json)
+}
+
+pub async fn ban(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+    Json(input): Json<BanDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let ban = state.moderation.ban(&state.chat, &user, id, input).await?;
+    Ok((StatusCode::CREATED, Json(ban)))
+}
+
+pub async fn unban(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path((id, uid)): Path<(u64, u64)>,
+) -> Result<impl IntoResponse, AppError> {
+    state.moderation.unban(&state.chat, &user, id, uid).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn set_slow_mode(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+    Json(input): Json<SlowModeDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state
+        .moderation
+        .set_slow_mode(&state.chat, &user, id, input)
+        .await
+        .map(Json)
+}
@@ -0,0 +1,69 @@
+use crate::{
+    error::AppError,
+    service::{
+        auth::ClaimUser,
+        poll::{CreatePollDto, VoteDto},
+    },
+    AppState,
+};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+pub async fn create(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+    Json(input): Json<CreatePollDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let poll = state.poll.create(&state.chat, &user, id, input).await?;
+    Ok((StatusCode::CREATED, Json(poll)))
+}
+
+pub async fn get(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path((id, msg_id)): Path<(u64, u64)>,
+) -> Result<impl IntoResponse, AppError> {
+    let poll = state.poll.get(&user, id, msg_id).await?;
+    Ok((StatusCode::OK, Json(poll)))
+}
+
+pub async fn vote(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path((id, msg_id)): Path<(u64, u64)>,
+    Json(input): Json<VoteDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let poll = state
+        .poll
+        .vote(&state.chat, &user, id, msg_id, input)
+        .await?;
+    Ok((StatusCode::OK, Json(poll)))
+}
+
+pub async fn retract(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path((id, msg_id)): Path<(u64, u64)>,
+) -> Result<impl IntoResponse, AppError> {
+    let input = VoteDto { options: vec![] };
+    let poll = state
+        .poll
+        .vote(&state.chat, &user, id, msg_id, input)
+        .await?;
+    Ok((StatusCode::OK, Json(poll)))
+}
+
+pub async fn close(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path((id, msg_id)): Path<(u64, u64)>,
+) -> Result<impl IntoResponse, AppError> {
+    let poll = state.poll.close(&state.chat, &user, id, msg_id).await?;
+    Ok((StatusCode::OK, Json(poll)))
+}
@@ -0,0 +1,55 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Extension, Json,
+};
+
+use crate::{
+    error::AppError,
+    service::{auth::ClaimUser, retention::UpdateRetentionDto},
+    AppState,
+};
+
+pub async fn get_ws_policy(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+) -> Result<impl IntoResponse, AppError> {
+    state.retention.get_ws_policy(user.ws_id).await.map(Json)
+}
+
+pub async fn update_ws_policy(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Json(input): Json<UpdateRetentionDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state
+        .retention
+        .update_ws_policy(&user, input)
+        .await
+        .map(Json)
+}
+
+pub async fn get_chat_policy(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    state
+        .retention
+        .get_chat_policy(user.ws_id, id)
+        .await
+        .map(Json)
+}
+
+pub async fn update_chat_policy(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+    Json(input): Json<UpdateRetentionDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state
+        .retention
+        .update_chat_policy(&user, id, input)
+        .await
+        .map(Json)
+}
@@ -0,0 +1,62 @@
+use crate::{
+    error::AppError,
+    service::{
+        auth::ClaimUser,
+        schedule::{ListScheduledDto, RemindDto, ScheduleMsgDto, UpdateScheduledDto},
+    },
+    AppState,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+pub async fn schedule_msg(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+    Json(input): Json<ScheduleMsgDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let item = state.schedule.schedule_msg(&user, id, input).await?;
+    Ok((StatusCode::CREATED, Json(item)))
+}
+
+pub async fn remind(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+    Json(input): Json<RemindDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let item = state.schedule.remind(&state.chat, &user, id, input).await?;
+    Ok((StatusCode::CREATED, Json(item)))
+}
+
+pub async fn list_all(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Query(input): Query<ListScheduledDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state.schedule.list(&user, input).await.map(Json)
+}
+
+pub async fn update(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+    Json(input): Json<UpdateScheduledDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let item = state.schedule.update(&user, id, input).await?;
+    Ok((StatusCode::OK, Json(item)))
+}
+
+pub async fn cancel(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    let item = state.schedule.cancel(&user, id).await?;
+    Ok((StatusCode::OK, Json(item)))
+}
@@ -1,6 +1,15 @@
-use axum::{extract::State, response::IntoResponse, Extension, Json};
+use axum::{
+    extract::{Multipart, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
 
-use crate::{error::AppError, service::auth::ClaimUser, AppState};
+use crate::{
+    error::AppError,
+    service::{account::ConfirmPasswordDto, auth::ClaimUser, user::UpdateProfileDto},
+    AppState,
+};
 
 pub async fn list_all_users(
     State(state): State<AppState>,
@@ -13,8 +22,77 @@ pub async fn get_user_info(
     State(state): State<AppState>,
     Extension(user): Extension<ClaimUser>,
 ) -> Result<impl IntoResponse, AppError> {
-    match state.user.get_user_info(user.id).await? {
+    match state.user.get_user_info(user.id, user.ws_id).await? {
         Some(info) => Ok(Json(info)),
         None => Err(AppError::NotFound(user.id.to_string())),
     }
 }
+
+pub async fn update_user_info(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Json(input): Json<UpdateProfileDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let profile = state.user.update_profile(user.id, input).await?;
+    Ok((StatusCode::OK, Json(profile)))
+}
+
+pub async fn upload_avatar(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, AppError> {
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|_| AppError::GeneralError("multipart error".to_owned()))?
+    {
+        if !field
+            .content_type()
+            .is_some_and(|ct| ct.starts_with("image/"))
+        {
+            continue;
+        }
+        if let Some(url) = state.file.save(&user, field).await? {
+            let profile = state.user.set_avatar(user.id, url).await?;
+            return Ok((StatusCode::OK, Json(profile)));
+        }
+    }
+    Err(AppError::InvalidError(
+        "avatar image is required".to_owned(),
+    ))
+}
+
+pub async fn deactivate(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Json(input): Json<ConfirmPasswordDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state.account.deactivate(&user, input).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn export(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let archive = state.account.export(&user, &state.file).await?;
+    let disposition = format!("attachment; filename=\"export-{}.zip\"", user.id);
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "application/zip".to_owned()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        archive,
+    ))
+}
+
+pub async fn erase(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Json(input): Json<ConfirmPasswordDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state.account.erase(&user, input, &state.file).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,73 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+use crate::{
+    error::AppError,
+    service::{
+        auth::ClaimUser,
+        webhook::{CreateWebhookDto, ListDeliveriesDto, UpdateWebhookDto},
+    },
+    AppState,
+};
+
+pub async fn create(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Json(input): Json<CreateWebhookDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let webhook = state.webhook.create(&user, input).await?;
+    Ok((StatusCode::CREATED, Json(webhook)))
+}
+
+pub async fn list_all(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+) -> Result<impl IntoResponse, AppError> {
+    state.webhook.list(user.ws_id).await.map(Json)
+}
+
+pub async fn update(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+    Json(input): Json<UpdateWebhookDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state.webhook.update(&user, id, input).await.map(Json)
+}
+
+pub async fn delete(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    state.webhook.delete(&user, id).await.map(Json)
+}
+
+pub async fn list_deliveries(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+    Query(input): Query<ListDeliveriesDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state
+        .webhook
+        .list_deliveries(user.ws_id, id, input)
+        .await
+        .map(Json)
+}
+
+pub async fn redeliver(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path((id, delivery_id)): Path<(u64, u64)>,
+) -> Result<impl IntoResponse, AppError> {
+    state
+        .webhook
+        .redeliver(user.ws_id, id, delivery_id)
+        .await
+        .map(Json)
+}
@@ -0,0 +1,104 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+use crate::{
+    common::AuthInfo,
+    error::AppError,
+    service::{
+        auth::ClaimUser,
+        workspace::{AddMemberDto, CreateWsDto, RenameWsDto, TransferOwnerDto, UpdateMemberDto},
+    },
+    AppState,
+};
+
+pub async fn list_user_ws(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+) -> Result<impl IntoResponse, AppError> {
+    state.workspace.list_user_ws(user.id).await.map(Json)
+}
+
+pub async fn create(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Json(input): Json<CreateWsDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let ws = state.workspace.create(&user, input).await?;
+    Ok((StatusCode::CREATED, Json(ws)))
+}
+
+pub async fn switch(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    let token = state.auth.switch_ws(&user, id).await?;
+    if id != user.ws_id {
+        state.notif.unregister(user.ws_id, user.id);
+    }
+    Ok((StatusCode::OK, Json(AuthInfo { token })))
+}
+
+pub async fn get(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+) -> Result<impl IntoResponse, AppError> {
+    state.workspace.get(user.ws_id).await.map(Json)
+}
+
+pub async fn rename(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Json(input): Json<RenameWsDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let ws = state.workspace.rename(&user, input).await?;
+    Ok((StatusCode::OK, Json(ws)))
+}
+
+pub async fn transfer_owner(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Json(input): Json<TransferOwnerDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let ws = state.workspace.transfer_owner(&user, input).await?;
+    Ok((StatusCode::OK, Json(ws)))
+}
+
+pub async fn list_members(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+) -> Result<impl IntoResponse, AppError> {
+    state.workspace.list_members(user.ws_id).await.map(Json)
+}
+
+pub async fn add_member(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Json(input): Json<AddMemberDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let member = state.workspace.add_member(&user, input).await?;
+    Ok((StatusCode::CREATED, Json(member)))
+}
+
+pub async fn update_member(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(uid): Path<u64>,
+    Json(input): Json<UpdateMemberDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let member = state.workspace.update_member(&user, uid, input).await?;
+    Ok((StatusCode::OK, Json(member)))
+}
+
+pub async fn remove_member(
+    State(state): State<AppState>,
+    Extension(user): Extension<ClaimUser>,
+    Path(uid): Path<u64>,
+) -> Result<impl IntoResponse, AppError> {
+    state.workspace.remove_member(&user, uid).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,146 @@
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use crate::AppState;
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const UNFURL_INTERVAL: Duration = Duration::from_secs(2);
+const SCHEDULE_INTERVAL: Duration = Duration::from_secs(1);
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const RATE_LIMIT_PRUNE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const WEBHOOK_INTERVAL: Duration = Duration::from_secs(1);
+const ALERT_PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// purge chats whose restore window has passed, every replica may run it
+/// since purging a chat twice is a no-op
+pub fn spawn_purge_chats(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match state.chat.purge_expired(&state.file).await {
+                Ok(0) => {}
+                Ok(purged) => info!("Purged {} expired chats", purged),
+                Err(e) => warn!("Failed to purge expired chats, error: {}", e),
+            }
+        }
+    });
+}
+
+/// fetch link previews of new messages, replicas claim different jobs
+pub fn spawn_unfurl_links(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(UNFURL_INTERVAL);
+        loop {
+            interval.tick().await;
+            // keep going while there is a backlog
+            loop {
+                match state.unfurl.unfurl_pending().await {
+                    Ok(0) => break,
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!("Failed to unfurl links, error: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// send scheduled messages and fire reminders once they are due,
+/// replicas claim different items
+pub fn spawn_deliver_scheduled(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SCHEDULE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match state.schedule.deliver_due(&state.chat).await {
+                Ok(0) => {}
+                Ok(delivered) => info!("Delivered {} scheduled items", delivered),
+                Err(e) => warn!("Failed to deliver scheduled items, error: {}", e),
+            }
+        }
+    });
+}
+
+/// delete messages past the retention of their chat, chats and workspaces
+/// on legal hold are skipped
+pub fn spawn_sweep_retention(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RETENTION_INTERVAL);
+        loop {
+            interval.tick().await;
+            match state.retention.sweep(&state.file).await {
+                Ok(0) => {}
+                Ok(deleted) => info!("Deleted {} expired messages", deleted),
+                Err(e) => warn!("Failed to sweep expired messages, error: {}", e),
+            }
+        }
+    });
+}
+
+/// forget the claims of sent alerts so they do not pile up
+pub fn spawn_prune_alerts(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ALERT_PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = state.notif.prune_alerts().await {
+                warn!("Failed to prune alert claims, error: {}", e);
+            }
+        }
+    });
+}
+
+/// forget full rate limit buckets so they do not pile up
+pub fn spawn_prune_rate_limits(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RATE_LIMIT_PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = state.rate_limit.prune().await {
+                warn!("Failed to prune rate limits, error: {}", e);
+            }
+        }
+    });
+}
+
+/// queue the events webhooks are subscribed to, every replica listens to the same
+/// notifications and the key of the event lets only one of them queue it
+pub fn spawn_dispatch_webhooks(state: AppState) {
+    let mut events = state.notif.tap();
+    tokio::spawn(async move {
+        while let Some(tapped) = events.recv().await {
+            if let Err(e) = state.webhook.enqueue(&tapped.key, &tapped.event).await {
+                warn!(
+                    "Failed to queue {} for webhooks, error: {}",
+                    tapped.event.get_name(),
+                    e
+                );
+            }
+        }
+    });
+}
+
+/// send due webhook deliveries, replicas claim different deliveries
+pub fn spawn_deliver_webhooks(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(WEBHOOK_INTERVAL);
+        loop {
+            interval.tick().await;
+            // keep going while there is a backlog
+            loop {
+                match state.webhook.deliver_due().await {
+                    Ok(0) => break,
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!("Failed to deliver webhooks, error: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,65 @@
+use axum::async_trait;
+use sqlx::PgPool;
+
+use crate::{domain::alert::AlertRepo, error::AppError};
+
+#[derive(Clone)]
+pub struct AlertRepoImpl {
+    pool: PgPool,
+}
+
+impl AlertRepoImpl {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl AlertRepo for AlertRepoImpl {
+    async fn claim(&self, event_key: &str, user_ids: &[u64]) -> Result<Vec<u64>, AppError> {
+        let user_ids: Vec<i64> = user_ids.iter().map(|id| *id as i64).collect();
+        // the primary key lets one replica win each alert
+        let claimed: Vec<i64> = sqlx::query_scalar(
+            r#"
+            INSERT INTO alert_claims (event_key, user_id)
+            SELECT $1, user_id FROM unnest($2::bigint[]) AS u(user_id)
+            ON CONFLICT DO NOTHING
+            RETURNING user_id
+            "#,
+        )
+        .bind(event_key)
+        .bind(&user_ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(claimed.into_iter().map(|id| id as u64).collect())
+    }
+
+    async fn prune(&self) -> Result<u64, AppError> {
+        let ret =
+            sqlx::query("DELETE FROM alert_claims WHERE created_at < now() - interval '1 day'")
+                .execute(&self.pool)
+                .await?;
+
+        Ok(ret.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[sqlx::test]
+    async fn claim_should_give_each_alert_to_one_replica(pool: PgPool) {
+        let replica_a = AlertRepoImpl::new(pool.clone());
+        let replica_b = AlertRepoImpl::new(pool);
+
+        let mut claimed = replica_a.claim("key", &[1, 2]).await.unwrap();
+        claimed.sort();
+        assert_eq!(claimed, vec![1, 2]);
+        let claimed = replica_b.claim("key", &[1, 2, 3]).await.unwrap();
+        assert_eq!(claimed, vec![3]);
+        let claimed = replica_b.claim("other key", &[1]).await.unwrap();
+        assert_eq!(claimed, vec![1]);
+    }
+}
@@ -0,0 +1,75 @@
+use axum::async_trait;
+use sqlx::PgPool;
+
+use crate::{
+    domain::audit::{AuditEvent, AuditFilter, AuditRepo},
+    error::AppError,
+};
+
+#[derive(Clone)]
+pub struct AuditRepoImpl {
+    pool: PgPool,
+}
+
+impl AuditRepoImpl {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl AuditRepo for AuditRepoImpl {
+    async fn append(&self, event: &AuditEvent) -> Result<AuditEvent, AppError> {
+        let event = sqlx::query_as(
+            r#"
+        INSERT INTO audit_events (ws_id, actor_id, action, target, detail, ip, user_agent)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING *
+        "#,
+        )
+        .bind(event.ws_id)
+        .bind(event.actor_id)
+        .bind(&event.action)
+        .bind(&event.target)
+        .bind(&event.detail)
+        .bind(&event.ip)
+        .bind(&event.user_agent)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(event)
+    }
+
+    async fn extract(
+        &self,
+        ws_id: i64,
+        filter: &AuditFilter,
+        before_id: i64,
+        limit: i64,
+    ) -> Result<Vec<AuditEvent>, AppError> {
+        let events = sqlx::query_as(
+            r#"
+        SELECT *
+        FROM audit_events
+        WHERE ws_id = $1 AND id < $2
+        AND ($3::bigint IS NULL OR actor_id = $3)
+        AND ($4::text IS NULL OR action = $4)
+        AND ($5::text IS NULL OR target = $5)
+        AND ($6::timestamptz IS NULL OR created_at >= $6)
+        AND ($7::timestamptz IS NULL OR created_at < $7)
+        ORDER BY id DESC
+        LIMIT $8
+        "#,
+        )
+        .bind(ws_id)
+        .bind(before_id)
+        .bind(filter.actor_id)
+        .bind(&filter.action)
+        .bind(&filter.target)
+        .bind(filter.since)
+        .bind(filter.until)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(events)
+    }
+}
@@ -0,0 +1,285 @@
+use axum::async_trait;
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+
+use crate::{
+    domain::{
+        bot::{BotRepo, BotToken, IncomingWebhook},
+        user::User,
+    },
+    error::AppError,
+};
+
+#[derive(Clone)]
+pub struct BotRepoImpl {
+    pool: PgPool,
+}
+
+impl BotRepoImpl {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl BotRepo for BotRepoImpl {
+    async fn create_bot(&self, ws_id: i64, name: &str, email: &str) -> Result<User, AppError> {
+        let mut tx = self.pool.begin().await?;
+        // an empty hash matches no password
+        let bot: User = sqlx::query_as(
+            r#"
+            INSERT INTO users (ws_id, email, fullname, password_hash, is_bot)
+            VALUES ($1, $2, $3, '', TRUE)
+            RETURNING *
+            "#,
+        )
+        .bind(ws_id)
+        .bind(email)
+        .bind(name)
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query("INSERT INTO ws_members (ws_id, user_id) VALUES ($1, $2)")
+            .bind(ws_id)
+            .bind(bot.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(bot)
+    }
+
+    async fn extract_bots(&self, ws_id: i64) -> Result<Vec<User>, AppError> {
+        let bots = sqlx::query_as(
+            r#"
+            SELECT u.*
+            FROM users u
+            JOIN ws_members m ON m.user_id = u.id
+            WHERE m.ws_id = $1 AND u.is_bot
+            ORDER BY u.id
+            "#,
+        )
+        .bind(ws_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(bots)
+    }
+
+    async fn find_bot(&self, ws_id: i64, id: i64) -> Result<Option<User>, AppError> {
+        let bot = sqlx::query_as(
+            r#"
+            SELECT u.*
+            FROM users u
+            JOIN ws_members m ON m.user_id = u.id
+            WHERE m.ws_id = $1 AND u.id = $2 AND u.is_bot
+            "#,
+        )
+        .bind(ws_id)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(bot)
+    }
+
+    async fn deactivate_bot(&self, id: i64) -> Result<User, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let bot = sqlx::query_as(
+            r#"
+            UPDATE users SET status = 'deactivated', token_version = token_version + 1
+            WHERE id = $1 AND is_bot
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query(
+            "UPDATE bot_tokens SET revoked_at = now() WHERE bot_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "UPDATE incoming_webhooks SET revoked_at = now() WHERE bot_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(bot)
+    }
+
+    async fn save_token(&self, token: &BotToken) -> Result<BotToken, AppError> {
+        let token = sqlx::query_as(
+            r#"
+            INSERT INTO bot_tokens (bot_id, name, token_hash, created_by)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+            "#,
+        )
+        .bind(token.bot_id)
+        .bind(&token.name)
+        .bind(&token.token_hash)
+        .bind(token.created_by)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    async fn extract_tokens(&self, bot_id: i64) -> Result<Vec<BotToken>, AppError> {
+        let tokens = sqlx::query_as("SELECT * FROM bot_tokens WHERE bot_id = $1 ORDER BY id")
+            .bind(bot_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(tokens)
+    }
+
+    async fn revoke_token(&self, bot_id: i64, id: i64) -> Result<Option<BotToken>, AppError> {
+        let token = sqlx::query_as(
+            r#"
+            UPDATE bot_tokens SET revoked_at = now()
+            WHERE id = $1 AND bot_id = $2 AND revoked_at IS NULL
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(bot_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    async fn use_token(&self, token_hash: &str) -> Result<Option<BotToken>, AppError> {
+        let token: Option<BotToken> = sqlx::query_as(
+            r#"
+            SELECT * FROM bot_tokens
+            WHERE token_hash = $1 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(token) = token else {
+            return Ok(None);
+        };
+        // a busy bot writes its last use once a minute, not on every request
+        if token
+            .last_used_at
+            .is_some_and(|t| t > Utc::now() - Duration::minutes(1))
+        {
+            return Ok(Some(token));
+        }
+        let used: Option<BotToken> = sqlx::query_as(
+            r#"
+            UPDATE bot_tokens SET last_used_at = now()
+            WHERE id = $1 AND revoked_at IS NULL
+            AND (last_used_at IS NULL OR last_used_at < now() - interval '1 minute')
+            RETURNING *
+            "#,
+        )
+        .bind(token.id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        // another request recorded the use meanwhile
+        Ok(Some(used.unwrap_or(token)))
+    }
+
+    async fn save_hook(&self, hook: &IncomingWebhook) -> Result<IncomingWebhook, AppError> {
+        let hook = sqlx::query_as(
+            r#"
+            INSERT INTO incoming_webhooks (ws_id, bot_id, chat_id, token_hash, created_by)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+            "#,
+        )
+        .bind(hook.ws_id)
+        .bind(hook.bot_id)
+        .bind(hook.chat_id)
+        .bind(&hook.token_hash)
+        .bind(hook.created_by)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(hook)
+    }
+
+    async fn extract_hooks(&self, bot_id: i64) -> Result<Vec<IncomingWebhook>, AppError> {
+        let hooks = sqlx::query_as("SELECT * FROM incoming_webhooks WHERE bot_id = $1 ORDER BY id")
+            .bind(bot_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(hooks)
+    }
+
+    async fn revoke_hook(&self, bot_id: i64, id: i64) -> Result<Option<IncomingWebhook>, AppError> {
+        let hook = sqlx::query_as(
+            r#"
+            UPDATE incoming_webhooks SET revoked_at = now()
+            WHERE id = $1 AND bot_id = $2 AND revoked_at IS NULL
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(bot_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(hook)
+    }
+
+    async fn find_hook(&self, token_hash: &str) -> Result<Option<IncomingWebhook>, AppError> {
+        let hook = sqlx::query_as(
+            "SELECT * FROM incoming_webhooks WHERE token_hash = $1 AND revoked_at IS NULL",
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(hook)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::utils::sha256_hex,
+        service::bot::{CreateBotDto, CreateBotTokenDto},
+        test_util::{signup, state},
+    };
+
+    #[sqlx::test]
+    async fn use_token_should_record_the_last_use_once_a_minute(pool: PgPool) {
+        let state = state(pool.clone()).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let input = CreateBotDto {
+            name: "deploy".to_owned(),
+        };
+        let bot = state.bot.create(&alice, input).await.unwrap();
+        let input = CreateBotTokenDto {
+            name: "ci".to_owned(),
+        };
+        let token = state.bot.create_token(&alice, bot.id, input).await.unwrap();
+        let hash = sha256_hex(&token.token.unwrap());
+        let repo = BotRepoImpl::new(pool.clone());
+
+        let first = repo.use_token(&hash).await.unwrap().unwrap();
+        let used_at = first.last_used_at.unwrap();
+        let second = repo.use_token(&hash).await.unwrap().unwrap();
+        assert_eq!(second.last_used_at, Some(used_at));
+
+        sqlx::query("UPDATE bot_tokens SET last_used_at = now() - interval '2 minutes'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let third = repo.use_token(&hash).await.unwrap().unwrap();
+        assert!(third.last_used_at.unwrap() >= used_at);
+    }
+}
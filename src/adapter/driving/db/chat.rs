@@ -1,8 +1,12 @@
 use axum::async_trait;
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgPool};
 
 use crate::{
-    domain::chat::{Chat, ChatRepo, Msg},
+    domain::chat::{
+        Bookmark, ChannelSummary, Chat, ChatPrefs, ChatRepo, ChatStatus, Msg, Pin, PinnedMsg,
+        UserChat,
+    },
     error::AppError,
 };
 
@@ -15,28 +19,123 @@ impl ChatRepoImpl {
     pub fn new(pool: PgPool) -> Self {
         Self { pool }
     }
+
+    async fn insert_chat(conn: &mut PgConnection, input: &Chat) -> Result<i64, AppError> {
+        let chat_id = sqlx::query_scalar(
+            r#"
+            INSERT INTO chats (ws_id, name, chat_type, created_by)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+            "#,
+        )
+        .bind(input.ws_id)
+        .bind(&input.name)
+        .bind(&input.chat_type)
+        .bind(input.created_by)
+        .fetch_one(conn)
+        .await?;
+        Ok(chat_id)
+    }
+
+    async fn insert_members(
+        conn: &mut PgConnection,
+        chat_id: i64,
+        members: &[i64],
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            INSERT INTO chat_members (chat_id, user_id)
+            SELECT $1, unnest($2::bigint[])
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(chat_id)
+        .bind(members)
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
 }
 
 #[async_trait]
 impl ChatRepo for ChatRepoImpl {
-    async fn delete_by_id(&self, chat_id: i64) -> Result<Chat, AppError> {
+    async fn update_status(&self, chat_id: i64, status: ChatStatus) -> Result<Chat, AppError> {
         let chat = sqlx::query_as(
             r#"
-            UPDATE chats set status = 0
+            UPDATE chats c SET status = $2, status_changed_at = now()
             WHERE id = $1
-            RETURNING *
+            RETURNING c.*, ARRAY(
+                SELECT user_id FROM chat_members WHERE chat_id = c.id ORDER BY user_id
+            ) AS members
             "#,
         )
         .bind(chat_id)
+        .bind(status)
         .fetch_one(&self.pool)
         .await?;
         Ok(chat)
     }
+    async fn extract_expired(
+        &self,
+        status: ChatStatus,
+        before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<Chat>, AppError> {
+        let chats = sqlx::query_as(
+            r#"
+            SELECT c.*, ARRAY(
+                SELECT user_id FROM chat_members WHERE chat_id = c.id ORDER BY user_id
+            ) AS members
+            FROM chats c
+            WHERE c.status = $1 AND c.status_changed_at < $2
+            ORDER BY c.status_changed_at
+            LIMIT $3
+            "#,
+        )
+        .bind(status)
+        .bind(before)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(chats)
+    }
+    async fn purge(&self, chat_id: i64) -> Result<Option<Chat>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        // members are still there so the status change reaches them
+        let purged: Option<i64> = sqlx::query_scalar(
+            r#"
+            UPDATE chats SET status = 'purged', status_changed_at = now()
+            WHERE id = $1 AND status <> 'purged'
+            RETURNING id
+            "#,
+        )
+        .bind(chat_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if purged.is_none() {
+            return Ok(None);
+        }
+        // members go first so removing pins and bookmarks notifies nobody
+        for sql in [
+            "DELETE FROM chat_members WHERE chat_id = $1",
+            "DELETE FROM chat_pins WHERE chat_id = $1",
+            "DELETE FROM chat_bookmarks WHERE chat_id = $1",
+            "DELETE FROM messages WHERE chat_id = $1",
+        ] {
+            sqlx::query(sql).bind(chat_id).execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+
+        self.extract_by_id(chat_id).await
+    }
     async fn extract_by_id(&self, chat_id: i64) -> Result<Option<Chat>, AppError> {
         let chats = sqlx::query_as(
             r#"
-            SELECT id, ws_id, name, chat_type, members, status, created_at
-            FROM chats
+            SELECT id, ws_id, name, chat_type, topic, description, status, status_changed_at,
+                created_by, created_at, ARRAY(
+                SELECT user_id FROM chat_members WHERE chat_id = c.id ORDER BY user_id
+            ) AS members
+            FROM chats c
             WHERE id = $1
             "#,
         )
@@ -46,87 +145,670 @@ impl ChatRepo for ChatRepoImpl {
 
         Ok(chats)
     }
-    async fn store_msg(&self, input: &Msg) -> Result<Msg, AppError> {
-        let message: Msg = sqlx::query_as(
+    async fn is_member(&self, chat_id: i64, ws_id: i64, user_id: i64) -> Result<bool, AppError> {
+        let is_member: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(
+                SELECT 1
+                FROM chats c
+                JOIN chat_members m ON m.chat_id = c.id
+                WHERE c.id = $1 AND c.ws_id = $2 AND m.user_id = $3 AND c.status <> 'purged'
+            )
+            "#,
+        )
+        .bind(chat_id)
+        .bind(ws_id)
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(is_member)
+    }
+    async fn is_public_channel(&self, chat_id: i64, ws_id: i64) -> Result<bool, AppError> {
+        let is_public: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(
+                SELECT 1
+                FROM chats
+                WHERE id = $1 AND ws_id = $2 AND chat_type = 'public_channel'
+                AND status IN ('active', 'archived')
+            )
+            "#,
+        )
+        .bind(chat_id)
+        .bind(ws_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(is_public)
+    }
+    async fn extract_public_channels(
+        &self,
+        ws_id: i64,
+        user_id: i64,
+        query: Option<&str>,
+    ) -> Result<Vec<ChannelSummary>, AppError> {
+        let channels = sqlx::query_as(
+            r#"
+            SELECT c.id, c.ws_id, c.name, c.topic, c.created_at,
+                (SELECT count(*) FROM chat_members WHERE chat_id = c.id) AS member_count,
+                EXISTS(
+                    SELECT 1 FROM chat_members WHERE chat_id = c.id AND user_id = $2
+                ) AS joined
+            FROM chats c
+            WHERE c.ws_id = $1 AND c.chat_type = 'public_channel' AND c.status = 'active'
+            AND ($3::text IS NULL OR c.name ILIKE '%' || $3 || '%' OR c.topic ILIKE '%' || $3 || '%')
+            ORDER BY c.name
+            "#,
+        )
+        .bind(ws_id)
+        .bind(user_id)
+        .bind(query)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(channels)
+    }
+    /// a handle is the local part of the member's email
+    async fn resolve_handles(
+        &self,
+        ws_id: i64,
+        handles: &[String],
+    ) -> Result<Vec<(i64, String)>, AppError> {
+        let users = sqlx::query_as(
+            r#"
+            SELECT u.id, lower(split_part(u.email, '@', 1)) AS handle
+            FROM ws_members m
+            JOIN users u ON u.id = m.user_id
+            WHERE m.ws_id = $1 AND m.status = 'active'
+            AND lower(split_part(u.email, '@', 1)) = ANY($2)
+            ORDER BY u.id
+            "#,
+        )
+        .bind(ws_id)
+        .bind(handles)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(users)
+    }
+
+    async fn resolve_mentions(
+        &self,
+        chat_id: i64,
+        handles: &[String],
+    ) -> Result<Vec<i64>, AppError> {
+        let user_ids = sqlx::query_scalar(
+            r#"
+            SELECT m.user_id
+            FROM chat_members m
+            JOIN users u ON u.id = m.user_id
+            WHERE m.chat_id = $1 AND lower(split_part(u.email, '@', 1)) = ANY($2)
+            ORDER BY m.user_id
+            "#,
+        )
+        .bind(chat_id)
+        .bind(handles)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(user_ids)
+    }
+    async fn extract_mentions(
+        &self,
+        user_id: i64,
+        ws_id: i64,
+        last_id: i64,
+        limit: i64,
+    ) -> Result<Vec<Msg>, AppError> {
+        let msgs = sqlx::query_as(
+            r#"
+            SELECT m.*
+            FROM messages m
+            JOIN chats c ON c.id = m.chat_id
+            JOIN chat_members me ON me.chat_id = m.chat_id AND me.user_id = $1
+            WHERE c.ws_id = $2 AND c.status IN ('active', 'archived')
+            AND m.sender_id <> $1 AND m.id < $3
+            AND ($1 = ANY(m.mentions) OR m.mention_broadcast IS NOT NULL)
+            ORDER BY m.id DESC
+            LIMIT $4
+            "#,
+        )
+        .bind(user_id)
+        .bind(ws_id)
+        .bind(last_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(msgs)
+    }
+    async fn store_msg(
+        &self,
+        input: &Msg,
+        links: &[String],
+        files: &[i64],
+    ) -> Result<Msg, AppError> {
+        let message: Option<Msg> = sqlx::query_as(
             r#"
-          INSERT INTO messages (chat_id, sender_id, content)
-          VALUES ($1, $2, $3)
-          RETURNING *
+          WITH msg AS (
+            INSERT INTO messages (chat_id, sender_id, content, content_type, rich, mentions, mention_broadcast, client_id,
+                previews, forwarded, quote_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $9, $10, $11, $12)
+            ON CONFLICT (chat_id, sender_id, client_id) WHERE client_id IS NOT NULL DO NOTHING
+            RETURNING *
+          ), job AS (
+            INSERT INTO unfurl_jobs (msg_id, urls)
+            SELECT id, $8 FROM msg WHERE cardinality($8::text[]) > 0
+          ), attached AS (
+            INSERT INTO message_files (msg_id, chat_id, file_id)
+            SELECT msg.id, msg.chat_id, f.file_id FROM msg, unnest($13::bigint[]) AS f(file_id)
+          )
+          SELECT * FROM msg
           "#,
         )
-        .bind(input.chat_id as i64)
-        .bind(input.sender_id as i64)
+        .bind(input.chat_id)
+        .bind(input.sender_id)
         .bind(&input.content)
-        .fetch_one(&self.pool)
+        .bind(input.content_type)
+        .bind(&input.rich)
+        .bind(&input.mentions)
+        .bind(input.mention_broadcast)
+        .bind(links)
+        .bind(&input.client_id)
+        .bind(&input.previews)
+        .bind(&input.forwarded)
+        .bind(input.quote_id)
+        .bind(files)
+        .fetch_optional(&self.pool)
+        .await?;
+        if let Some(message) = message {
+            return Ok(message);
+        }
+
+        // a retry, the message stored by the first attempt is committed by now
+        let client_id = input.client_id.as_deref().unwrap_or_default();
+        self.extract_by_client_id(input.chat_id, input.sender_id, client_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("message {}", client_id)))
+    }
+    async fn resolve_files(
+        &self,
+        ws_id: i64,
+        owner_id: i64,
+        urls: &[String],
+    ) -> Result<Vec<(String, i64)>, AppError> {
+        let files = sqlx::query_as(
+            r#"
+            SELECT url, id FROM files
+            WHERE ws_id = $1 AND owner_id = $2 AND url = ANY($3)
+            "#,
+        )
+        .bind(ws_id)
+        .bind(owner_id)
+        .bind(urls)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(files)
+    }
+    async fn extract_msg_files(&self, msg_id: i64) -> Result<Vec<i64>, AppError> {
+        let files = sqlx::query_scalar("SELECT file_id FROM message_files WHERE msg_id = $1")
+            .bind(msg_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(files)
+    }
+    async fn extract_by_client_id(
+        &self,
+        chat_id: i64,
+        sender_id: i64,
+        client_id: &str,
+    ) -> Result<Option<Msg>, AppError> {
+        let message = sqlx::query_as(
+            r#"
+            SELECT * FROM messages
+            WHERE chat_id = $1 AND sender_id = $2 AND client_id = $3
+            "#,
+        )
+        .bind(chat_id)
+        .bind(sender_id)
+        .bind(client_id)
+        .fetch_optional(&self.pool)
         .await?;
 
         Ok(message)
     }
     async fn save(&self, input: &Chat) -> Result<Chat, AppError> {
-        let chat = if input.id == -1 {
-            sqlx::query_as(
+        let mut tx = self.pool.begin().await?;
+        let chat_id: i64 = if input.id == -1 {
+            Self::insert_chat(&mut tx, input).await?
+        } else {
+            sqlx::query(
                 r#"
-        INSERT INTO chats (ws_id, name, chat_type, members)
-            VALUES ($1, $2, $3, $4)
-            RETURNING *
-        "#,
+                DELETE FROM chat_members
+                WHERE chat_id = $1 AND user_id <> ALL($2)
+                "#,
             )
-            .bind(&input.ws_id)
-            .bind(&input.name)
-            .bind(&input.chat_type)
+            .bind(input.id)
             .bind(&input.members)
-            .fetch_one(&self.pool)
-            .await?
-        } else {
-            sqlx::query_as(
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query_scalar(
                 r#"
                 UPDATE chats SET name=$1,chat_type=$2
                 WHERE id=$3
-                RETURNING *
+                RETURNING id
                 "#,
             )
             .bind(&input.name)
             .bind(&input.chat_type)
-            .bind(&input.id)
-            .fetch_one(&self.pool)
+            .bind(input.id)
+            .fetch_one(&mut *tx)
             .await?
         };
-        Ok(chat)
+        Self::insert_members(&mut tx, chat_id, &input.members).await?;
+        tx.commit().await?;
+
+        self.extract_by_id(chat_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("chat {}", chat_id)))
+    }
+    async fn find_or_create_single(&self, input: &Chat) -> Result<Chat, AppError> {
+        let mut tx = self.pool.begin().await?;
+        // serialize concurrent attempts to open the same direct message
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+            .bind(format!("single:{}:{:?}", input.ws_id, input.members))
+            .execute(&mut *tx)
+            .await?;
+        let existing: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT c.id
+            FROM chats c
+            WHERE c.ws_id = $1 AND c.chat_type = 'single' AND c.status = 'active'
+            AND ARRAY(
+                SELECT user_id FROM chat_members WHERE chat_id = c.id ORDER BY user_id
+            ) = $2
+            LIMIT 1
+            "#,
+        )
+        .bind(input.ws_id)
+        .bind(&input.members)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let chat_id = match existing {
+            Some(chat_id) => chat_id,
+            None => {
+                let chat_id = Self::insert_chat(&mut tx, input).await?;
+                Self::insert_members(&mut tx, chat_id, &input.members).await?;
+                chat_id
+            }
+        };
+        tx.commit().await?;
+
+        self.extract_by_id(chat_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("chat {}", chat_id)))
+    }
+    async fn is_channel_name_taken(
+        &self,
+        ws_id: i64,
+        name: &str,
+        except_id: i64,
+    ) -> Result<bool, AppError> {
+        let taken: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(
+                SELECT 1
+                FROM chats
+                WHERE ws_id = $1 AND lower(name) = lower($2) AND id <> $3
+                AND chat_type IN ('private_channel', 'public_channel')
+                AND status IN ('active', 'archived')
+            )
+            "#,
+        )
+        .bind(ws_id)
+        .bind(name)
+        .bind(except_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(taken)
+    }
+    async fn add_members(&self, chat_id: i64, members: Vec<i64>) -> Result<Chat, AppError> {
+        let mut conn = self.pool.acquire().await?;
+        Self::insert_members(&mut conn, chat_id, &members).await?;
+        self.extract_by_id(chat_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("chat {}", chat_id)))
+    }
+
+    async fn remove_member(&self, chat_id: i64, user_id: i64) -> Result<Chat, AppError> {
+        sqlx::query("DELETE FROM chat_members WHERE chat_id = $1 AND user_id = $2")
+            .bind(chat_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        self.extract_by_id(chat_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("chat {}", chat_id)))
+    }
+    async fn extract_muted_until(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+    ) -> Result<Option<DateTime<Utc>>, AppError> {
+        let muted_until = sqlx::query_scalar(
+            r#"
+        SELECT muted_until
+        FROM chat_mutes
+        WHERE chat_id = $1 AND user_id = $2 AND muted_until > now()
+        "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(muted_until)
+    }
+    async fn extract_slow_mode(&self, chat_id: i64) -> Result<Option<i32>, AppError> {
+        let secs = sqlx::query_scalar("SELECT slow_mode_secs FROM chats WHERE id = $1")
+            .bind(chat_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(secs)
+    }
+    async fn extract_banned(&self, chat_id: i64, user_ids: &[i64]) -> Result<Vec<i64>, AppError> {
+        let banned = sqlx::query_scalar(
+            r#"
+        SELECT user_id
+        FROM chat_bans
+        WHERE chat_id = $1 AND user_id = ANY($2)
+        ORDER BY user_id
+        "#,
+        )
+        .bind(chat_id)
+        .bind(user_ids)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(banned)
     }
-    async fn is_members_exist(&self, members: Vec<i64>) -> Result<bool, AppError> {
+    async fn is_members_exist(&self, ws_id: i64, members: Vec<i64>) -> Result<bool, AppError> {
         let count: i64 = sqlx::query_scalar(
             r#"
         SELECT count(*) as count
-        FROM users
-        WHERE id = ANY($1)
+        FROM ws_members
+        WHERE ws_id = $1 AND user_id = ANY($2) AND status = 'active'
         "#,
         )
+        .bind(ws_id)
         .bind(&members)
         .fetch_one(&self.pool)
         .await?;
         Ok(count as usize == members.len())
     }
 
-    async fn extract_all_chat(&self, user_id: i64, ws_id: i64) -> Result<Vec<Chat>, AppError> {
+    async fn extract_all_chat(
+        &self,
+        user_id: i64,
+        ws_id: i64,
+        statuses: &[ChatStatus],
+    ) -> Result<Vec<UserChat>, AppError> {
         let chats = sqlx::query_as(
             r#"
-            SELECT *
-            FROM chats
-            WHERE ws_id = $1 AND $2 = ANY(members) AND status = 1
+            SELECT c.*, me.muted_until, me.favorite, me.hidden, me.notif_level, ARRAY(
+                SELECT user_id FROM chat_members WHERE chat_id = c.id ORDER BY user_id
+            ) AS members
+            FROM chat_members me
+            JOIN chats c ON c.id = me.chat_id
+            WHERE me.user_id = $2 AND c.ws_id = $1 AND c.status = ANY($3)
+            ORDER BY me.favorite DESC, c.id
             "#,
         )
-        .bind(ws_id as i64)
-        .bind(user_id as i64)
+        .bind(ws_id)
+        .bind(user_id)
+        .bind(statuses)
         .fetch_all(&self.pool)
         .await?;
 
         Ok(chats)
     }
 
+    async fn extract_prefs(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+    ) -> Result<Option<ChatPrefs>, AppError> {
+        let prefs = sqlx::query_as(
+            r#"
+            SELECT muted_until, favorite, hidden, notif_level
+            FROM chat_members
+            WHERE chat_id = $1 AND user_id = $2
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(prefs)
+    }
+
+    async fn update_prefs(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        prefs: &ChatPrefs,
+    ) -> Result<ChatPrefs, AppError> {
+        let prefs = sqlx::query_as(
+            r#"
+            UPDATE chat_members
+            SET muted_until = $3, favorite = $4, hidden = $5, notif_level = $6
+            WHERE chat_id = $1 AND user_id = $2
+            RETURNING muted_until, favorite, hidden, notif_level
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(prefs.muted_until)
+        .bind(prefs.favorite)
+        .bind(prefs.hidden)
+        .bind(prefs.notif_level)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(prefs)
+    }
+
+    async fn extract_user_chats(&self, user_id: i64) -> Result<Vec<Chat>, AppError> {
+        let chats = sqlx::query_as(
+            r#"
+            SELECT c.*, ARRAY(
+                SELECT user_id FROM chat_members WHERE chat_id = c.id ORDER BY user_id
+            ) AS members
+            FROM chat_members me
+            JOIN chats c ON c.id = me.chat_id
+            WHERE me.user_id = $1
+            ORDER BY c.id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(chats)
+    }
+    async fn extract_user_messages(&self, user_id: i64) -> Result<Vec<Msg>, AppError> {
+        let msgs = sqlx::query_as(
+            r#"
+            SELECT *
+            FROM messages
+            WHERE sender_id = $1
+            ORDER BY id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(msgs)
+    }
+
+    /// blank the messages the user sent and the copies others forwarded of them
+    async fn erase_user_messages(&self, user_id: i64) -> Result<u64, AppError> {
+        let ret = sqlx::query(
+            r#"
+            WITH erased AS (
+                SELECT id FROM messages
+                WHERE sender_id = $1
+                UNION
+                SELECT id FROM messages
+                WHERE forwarded IS NOT NULL AND (forwarded->>'sender_id')::bigint = $1
+            ), jobs AS (
+                DELETE FROM unfurl_jobs j USING erased e
+                WHERE e.id = j.msg_id
+            ), attached AS (
+                DELETE FROM message_files f USING erased e
+                WHERE e.id = f.msg_id
+            )
+            UPDATE messages SET content = '', rich = NULL, previews = '[]'
+            WHERE id IN (SELECT id FROM erased)
+            "#,
+        )
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(ret.rows_affected())
+    }
+
+    async fn update_header(
+        &self,
+        chat_id: i64,
+        topic: Option<String>,
+        description: Option<String>,
+    ) -> Result<Chat, AppError> {
+        sqlx::query("UPDATE chats SET topic = $1, description = $2 WHERE id = $3")
+            .bind(topic)
+            .bind(description)
+            .bind(chat_id)
+            .execute(&self.pool)
+            .await?;
+        self.extract_by_id(chat_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("chat {}", chat_id)))
+    }
+
+    async fn extract_msg(&self, chat_id: i64, msg_id: i64) -> Result<Option<Msg>, AppError> {
+        let msg = sqlx::query_as("SELECT * FROM messages WHERE chat_id = $1 AND id = $2")
+            .bind(chat_id)
+            .bind(msg_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(msg)
+    }
+
+    async fn save_pin(&self, pin: &Pin) -> Result<Pin, AppError> {
+        let saved: Option<Pin> = sqlx::query_as(
+            r#"
+            INSERT INTO chat_pins (chat_id, msg_id, pinned_by)
+            VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING
+            RETURNING *
+            "#,
+        )
+        .bind(pin.chat_id)
+        .bind(pin.msg_id)
+        .bind(pin.pinned_by)
+        .fetch_optional(&self.pool)
+        .await?;
+        // already pinned
+        match saved {
+            Some(pin) => Ok(pin),
+            None => {
+                let pin =
+                    sqlx::query_as("SELECT * FROM chat_pins WHERE chat_id = $1 AND msg_id = $2")
+                        .bind(pin.chat_id)
+                        .bind(pin.msg_id)
+                        .fetch_one(&self.pool)
+                        .await?;
+                Ok(pin)
+            }
+        }
+    }
+
+    async fn delete_pin(&self, chat_id: i64, msg_id: i64) -> Result<Option<Pin>, AppError> {
+        let pin =
+            sqlx::query_as("DELETE FROM chat_pins WHERE chat_id = $1 AND msg_id = $2 RETURNING *")
+                .bind(chat_id)
+                .bind(msg_id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(pin)
+    }
+
+    async fn extract_pins(&self, chat_id: i64) -> Result<Vec<PinnedMsg>, AppError> {
+        let pins = sqlx::query_as(
+            r#"
+            SELECT m.*, p.pinned_by, p.created_at AS pinned_at
+            FROM chat_pins p
+            JOIN messages m ON m.id = p.msg_id
+            WHERE p.chat_id = $1
+            ORDER BY p.created_at DESC
+            "#,
+        )
+        .bind(chat_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(pins)
+    }
+
+    async fn save_bookmark(&self, bookmark: &Bookmark) -> Result<Bookmark, AppError> {
+        let bookmark = sqlx::query_as(
+            r#"
+            INSERT INTO chat_bookmarks (chat_id, title, url, created_by)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+            "#,
+        )
+        .bind(bookmark.chat_id)
+        .bind(&bookmark.title)
+        .bind(&bookmark.url)
+        .bind(bookmark.created_by)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(bookmark)
+    }
+
+    async fn delete_bookmark(&self, chat_id: i64, id: i64) -> Result<Option<Bookmark>, AppError> {
+        let bookmark =
+            sqlx::query_as("DELETE FROM chat_bookmarks WHERE chat_id = $1 AND id = $2 RETURNING *")
+                .bind(chat_id)
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(bookmark)
+    }
+
+    async fn extract_bookmarks(&self, chat_id: i64) -> Result<Vec<Bookmark>, AppError> {
+        let bookmarks =
+            sqlx::query_as("SELECT * FROM chat_bookmarks WHERE chat_id = $1 ORDER BY id")
+                .bind(chat_id)
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(bookmarks)
+    }
+
     async fn extract_messages(
         &self,
         chat_id: i64,
-        last_id: i64,
+        before_id: i64,
         limit: i64,
     ) -> Result<Vec<Msg>, AppError> {
         let msgs = sqlx::query_as(
@@ -140,11 +822,90 @@ impl ChatRepo for ChatRepoImpl {
             "#,
         )
         .bind(chat_id)
-        .bind(last_id)
+        .bind(before_id)
         .bind(limit)
         .fetch_all(&self.pool)
         .await?;
 
         Ok(msgs)
     }
+
+    async fn extract_messages_after(
+        &self,
+        chat_id: i64,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<Msg>, AppError> {
+        let msgs = sqlx::query_as(
+            r#"
+            SELECT *
+            FROM messages
+            WHERE chat_id = $1
+            AND id > $2
+            ORDER BY id
+            limit $3
+            "#,
+        )
+        .bind(chat_id)
+        .bind(after_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(msgs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::chat::ChatType;
+
+    async fn seed_users(pool: &PgPool) -> (i64, i64) {
+        let ids: Vec<i64> = sqlx::query_scalar(
+            r#"
+            INSERT INTO users (ws_id, fullname, email, password_hash)
+            VALUES (1, 'alice', 'alice@acme.org', ''), (1, 'bob', 'bob@acme.org', '')
+            RETURNING id
+            "#,
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap();
+        (ids[0], ids[1])
+    }
+
+    #[sqlx::test]
+    async fn erase_user_messages_should_blank_forwarded_copies(pool: PgPool) {
+        let (alice, bob) = seed_users(&pool).await;
+        let repo = ChatRepoImpl::new(pool);
+        let members = vec![alice as u64, bob as u64];
+        let source = Chat::new(
+            1,
+            1,
+            Some("source".into()),
+            ChatType::Group,
+            members.clone(),
+        );
+        let source = repo.save(&source).await.unwrap();
+        let target = Chat::new(1, 1, Some("target".into()), ChatType::Group, members);
+        let target = repo.save(&target).await.unwrap();
+
+        let original = Msg::new(source.id, alice, "a secret".into());
+        let original = repo.store_msg(&original, &[], &[]).await.unwrap();
+        let copy = repo
+            .store_msg(&original.forward(target.id, bob), &[], &[])
+            .await
+            .unwrap();
+        let own = Msg::new(target.id, bob, "bob's own".into());
+        let own = repo.store_msg(&own, &[], &[]).await.unwrap();
+
+        assert_eq!(repo.erase_user_messages(alice).await.unwrap(), 2);
+
+        let copy = repo.extract_msg(target.id, copy.id).await.unwrap().unwrap();
+        assert_eq!(copy.content, "");
+        assert_eq!(copy.sender_id, bob);
+        let own = repo.extract_msg(target.id, own.id).await.unwrap().unwrap();
+        assert_eq!(own.content, "bob's own");
+    }
 }
@@ -0,0 +1,169 @@
+use axum::async_trait;
+use sqlx::{types::Json, PgPool};
+
+use crate::{
+    domain::command::{Command, CommandRepo, CommandRun, EphemeralMsg},
+    error::AppError,
+};
+
+#[derive(Clone)]
+pub struct CommandRepoImpl {
+    pool: PgPool,
+}
+
+impl CommandRepoImpl {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl CommandRepo for CommandRepoImpl {
+    async fn save(&self, command: &Command) -> Result<Command, AppError> {
+        let saved = sqlx::query_as(
+            r#"
+            INSERT INTO slash_commands (ws_id, name, url, description, secret, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (ws_id, name) DO NOTHING
+            RETURNING *
+            "#,
+        )
+        .bind(command.ws_id)
+        .bind(&command.name)
+        .bind(&command.url)
+        .bind(&command.description)
+        .bind(&command.secret)
+        .bind(command.created_by)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        saved.ok_or_else(|| AppError::ConflictError(format!("command /{}", command.name)))
+    }
+
+    async fn extract_by_ws(&self, ws_id: i64) -> Result<Vec<Command>, AppError> {
+        let commands =
+            sqlx::query_as("SELECT * FROM slash_commands WHERE ws_id = $1 ORDER BY name")
+                .bind(ws_id)
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(commands)
+    }
+
+    async fn find_by_name(&self, ws_id: i64, name: &str) -> Result<Option<Command>, AppError> {
+        let command = sqlx::query_as("SELECT * FROM slash_commands WHERE ws_id = $1 AND name = $2")
+            .bind(ws_id)
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(command)
+    }
+
+    async fn delete(&self, ws_id: i64, id: i64) -> Result<Option<Command>, AppError> {
+        let command =
+            sqlx::query_as("DELETE FROM slash_commands WHERE id = $1 AND ws_id = $2 RETURNING *")
+                .bind(id)
+                .bind(ws_id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(command)
+    }
+
+    async fn notify_ephemeral(&self, msg: &EphemeralMsg) -> Result<(), AppError> {
+        sqlx::query("SELECT pg_notify('ephemeral_message', $1)")
+            .bind(serde_json::to_string(msg)?)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn claim_run(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        client_id: &str,
+        lease_secs: i64,
+    ) -> Result<Option<CommandRun>, AppError> {
+        // a run left unfinished by a crashed replica is claimed again once its lease is over
+        let claimed: Option<i64> = sqlx::query_scalar(
+            r#"
+            INSERT INTO command_runs (chat_id, user_id, client_id)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (chat_id, user_id, client_id) DO UPDATE SET started_at = CURRENT_TIMESTAMP
+            WHERE command_runs.output IS NULL
+            AND command_runs.started_at < CURRENT_TIMESTAMP - make_interval(secs => $4)
+            RETURNING chat_id
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(client_id)
+        .bind(lease_secs as f64)
+        .fetch_optional(&self.pool)
+        .await?;
+        if claimed.is_some() {
+            return Ok(None);
+        }
+
+        let run = sqlx::query_as(
+            r#"
+            SELECT * FROM command_runs
+            WHERE chat_id = $1 AND user_id = $2 AND client_id = $3
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(client_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(run)
+    }
+
+    async fn finish_run(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        client_id: &str,
+        output: &serde_json::Value,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            UPDATE command_runs SET output = $4
+            WHERE chat_id = $1 AND user_id = $2 AND client_id = $3
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(client_id)
+        .bind(Json(output))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn release_run(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        client_id: &str,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            DELETE FROM command_runs
+            WHERE chat_id = $1 AND user_id = $2 AND client_id = $3 AND output IS NULL
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(client_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
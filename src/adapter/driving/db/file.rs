@@ -0,0 +1,125 @@
+use axum::async_trait;
+use sqlx::PgPool;
+
+use crate::{
+    domain::file::{FileMeta, FileRepo},
+    error::AppError,
+};
+
+#[derive(Clone)]
+pub struct FileRepoImpl {
+    pool: PgPool,
+}
+
+impl FileRepoImpl {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl FileRepo for FileRepoImpl {
+    async fn save(&self, file: &FileMeta) -> Result<FileMeta, AppError> {
+        let file = sqlx::query_as(
+            r#"
+        INSERT INTO files (ws_id, owner_id, url, path)
+        VALUES ($1, $2, $3, $4)
+        RETURNING *
+        "#,
+        )
+        .bind(file.ws_id)
+        .bind(file.owner_id)
+        .bind(&file.url)
+        .bind(&file.path)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(file)
+    }
+
+    async fn extract_by_owner(&self, owner_id: i64) -> Result<Vec<FileMeta>, AppError> {
+        let files = sqlx::query_as(
+            r#"
+        SELECT *
+        FROM files
+        WHERE owner_id = $1
+        ORDER BY id
+        "#,
+        )
+        .bind(owner_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(files)
+    }
+
+    async fn delete_by_owner(&self, owner_id: i64) -> Result<Vec<FileMeta>, AppError> {
+        let files = sqlx::query_as(
+            r#"
+        DELETE FROM files
+        WHERE owner_id = $1
+        RETURNING *
+        "#,
+        )
+        .bind(owner_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(files)
+    }
+
+    /// files only linked from messages of the chat
+    async fn delete_by_chat(&self, chat_id: i64, ws_id: i64) -> Result<Vec<FileMeta>, AppError> {
+        let files = sqlx::query_as(
+            r#"
+        WITH links AS (
+            DELETE FROM message_files WHERE chat_id = $1 RETURNING file_id
+        )
+        DELETE FROM files f
+        WHERE f.ws_id = $2
+        AND f.id IN (SELECT file_id FROM links)
+        AND NOT EXISTS (
+            SELECT 1 FROM message_files mf WHERE mf.file_id = f.id AND mf.chat_id <> $1
+        )
+        RETURNING *
+        "#,
+        )
+        .bind(chat_id)
+        .bind(ws_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(files)
+    }
+
+    /// files linked from the given messages and from no other message
+    async fn delete_unreferenced(
+        &self,
+        ws_id: i64,
+        msg_ids: &[i64],
+    ) -> Result<Vec<FileMeta>, AppError> {
+        let files = sqlx::query_as(
+            r#"
+        WITH links AS (
+            DELETE FROM message_files WHERE msg_id = ANY($2) RETURNING file_id
+        )
+        DELETE FROM files f
+        WHERE f.ws_id = $1
+        AND f.id IN (SELECT file_id FROM links)
+        AND NOT EXISTS (
+            SELECT 1 FROM message_files mf WHERE mf.file_id = f.id AND mf.msg_id <> ALL($2)
+        )
+        RETURNING *
+        "#,
+        )
+        .bind(ws_id)
+        .bind(msg_ids)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(files)
+    }
+
+    async fn is_path_used(&self, path: &str) -> Result<bool, AppError> {
+        let used: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM files WHERE path = $1)")
+            .bind(path)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(used)
+    }
+}
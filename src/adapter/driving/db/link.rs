@@ -0,0 +1,111 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{types::Json, PgPool};
+
+use crate::{
+    domain::link::{LinkPreview, LinkRepo, UnfurlJob},
+    error::AppError,
+};
+
+#[derive(Clone)]
+pub struct LinkRepoImpl {
+    pool: PgPool,
+}
+
+impl LinkRepoImpl {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl LinkRepo for LinkRepoImpl {
+    async fn claim_jobs(&self, limit: i64, lease_secs: i64) -> Result<Vec<UnfurlJob>, AppError> {
+        let jobs = sqlx::query_as(
+            r#"
+            UPDATE unfurl_jobs
+            SET locked_until = now() + make_interval(secs => $2), attempts = attempts + 1
+            WHERE msg_id IN (
+                SELECT msg_id FROM unfurl_jobs
+                WHERE locked_until <= now()
+                ORDER BY msg_id
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING msg_id, urls, attempts
+            "#,
+        )
+        .bind(limit)
+        .bind(lease_secs as f64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(jobs)
+    }
+
+    async fn extract_previews(
+        &self,
+        urls: &[String],
+        fetched_after: DateTime<Utc>,
+    ) -> Result<Vec<LinkPreview>, AppError> {
+        let previews = sqlx::query_as(
+            r#"
+            SELECT * FROM link_previews
+            WHERE url = ANY($1) AND fetched_at > $2
+            "#,
+        )
+        .bind(urls)
+        .bind(fetched_after)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(previews)
+    }
+
+    async fn save_preview(&self, preview: &LinkPreview) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            INSERT INTO link_previews (url, title, description, image_url, ok, fetched_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (url) DO UPDATE
+            SET title = EXCLUDED.title, description = EXCLUDED.description,
+                image_url = EXCLUDED.image_url, ok = EXCLUDED.ok, fetched_at = EXCLUDED.fetched_at
+            "#,
+        )
+        .bind(&preview.url)
+        .bind(&preview.title)
+        .bind(&preview.description)
+        .bind(&preview.image_url)
+        .bind(preview.ok)
+        .bind(preview.fetched_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn complete_job(&self, msg_id: i64, previews: &[LinkPreview]) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE messages SET previews = $2 WHERE id = $1")
+            .bind(msg_id)
+            .bind(Json(previews))
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM unfurl_jobs WHERE msg_id = $1")
+            .bind(msg_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn delete_job(&self, msg_id: i64) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM unfurl_jobs WHERE msg_id = $1")
+            .bind(msg_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+use axum::async_trait;
+use sqlx::PgPool;
+
+use crate::{
+    domain::{
+        chat::Msg,
+        moderation::{ChatBan, ChatMute, ModerationRepo, Report, ReportStatus},
+    },
+    error::AppError,
+};
+
+#[derive(Clone)]
+pub struct ModerationRepoImpl {
+    pool: PgPool,
+}
+
+impl ModerationRepoImpl {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ModerationRepo for ModerationRepoImpl {
+    async fn save_report(&self, report: &Report) -> Result<Option<Report>, AppError> {
+        let report = sqlx::query_as(
+            r#"
+            INSERT INTO message_reports (ws_id, chat_id, msg_id, sender_id, content, reporter_id, reason)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (msg_id, reporter_id) WHERE status = 'open' DO NOTHING
+            RETURNING *
+            "#,
+        )
+        .bind(report.ws_id)
+        .bind(report.chat_id)
+        .bind(report.msg_id)
+        .bind(report.sender_id)
+        .bind(&report.content)
+        .bind(report.reporter_id)
+        .bind(&report.reason)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(report)
+    }
+
+    async fn extract_reports(
+        &self,
+        ws_id: i64,
+        status: ReportStatus,
+        last_id: i64,
+        limit: i64,
+    ) -> Result<Vec<Report>, AppError> {
+        let reports = sqlx::query_as(
+            r#"
+            SELECT * FROM message_reports
+            WHERE ws_id = $1 AND status = $2 AND id > $3
+            ORDER BY id
+            LIMIT $4
+            "#,
+        )
+        .bind(ws_id)
+        .bind(status)
+        .bind(last_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(reports)
+    }
+
+    async fn resolve_report(
+        &self,
+        ws_id: i64,
+        id: i64,
+        status: ReportStatus,
+        resolved_by: i64,
+        note: Option<&str>,
+    ) -> Result<Option<Report>, AppError> {
+        let report = sqlx::query_as(
+            r#"
+            UPDATE message_reports
+            SET status = $3, resolved_by = $4, note = $5, resolved_at = now()
+            WHERE id = $1 AND ws_id = $2 AND status = 'open'
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(ws_id)
+        .bind(status)
+        .bind(resolved_by)
+        .bind(note)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(report)
+    }
+
+    async fn delete_msg(
+        &self,
+        chat_id: i64,
+        msg_id: i64,
+        moderator_id: i64,
+        note: Option<&str>,
+    ) -> Result<Option<Msg>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            r#"
+            UPDATE message_reports
+            SET status = 'resolved', resolved_by = $3, note = $4, resolved_at = now()
+            WHERE chat_id = $1 AND msg_id = $2 AND status = 'open'
+            "#,
+        )
+        .bind(chat_id)
+        .bind(msg_id)
+        .bind(moderator_id)
+        .bind(note)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM chat_pins WHERE chat_id = $1 AND msg_id = $2")
+            .bind(chat_id)
+            .bind(msg_id)
+            .execute(&mut *tx)
+            .await?;
+        let msg: Option<Msg> =
+            sqlx::query_as("DELETE FROM messages WHERE chat_id = $1 AND id = $2 RETURNING *")
+                .bind(chat_id)
+                .bind(msg_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+        if msg.is_none() {
+            return Ok(None);
+        }
+        // sent on commit, purges and retention delete messages without telling anyone
+        sqlx::query(
+            r#"
+            SELECT pg_notify('chat_message_deleted', json_build_object(
+                'chat_id', $1::bigint, 'msg_id', $2::bigint,
+                'members', ARRAY(SELECT user_id FROM chat_members WHERE chat_id = $1)
+            )::text)
+            "#,
+        )
+        .bind(chat_id)
+        .bind(msg_id)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(msg)
+    }
+
+    async fn save_mute(&self, mute: &ChatMute) -> Result<ChatMute, AppError> {
+        let mute = sqlx::query_as(
+            r#"
+            INSERT INTO chat_mutes (chat_id, user_id, muted_until, muted_by, reason)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (chat_id, user_id) DO UPDATE
+            SET muted_until = EXCLUDED.muted_until, muted_by = EXCLUDED.muted_by,
+                reason = EXCLUDED.reason, created_at = now()
+            RETURNING *
+            "#,
+        )
+        .bind(mute.chat_id)
+        .bind(mute.user_id)
+        .bind(mute.muted_until)
+        .bind(mute.muted_by)
+        .bind(&mute.reason)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(mute)
+    }
+
+    async fn delete_mute(&self, chat_id: i64, user_id: i64) -> Result<Option<ChatMute>, AppError> {
+        let mute = sqlx::query_as(
+            r#"
+            DELETE FROM chat_mutes
+            WHERE chat_id = $1 AND user_id = $2 AND muted_until > now()
+            RETURNING *
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(mute)
+    }
+
+    async fn extract_mutes(&self, chat_id: i64) -> Result<Vec<ChatMute>, AppError> {
+        let mutes = sqlx::query_as(
+            r#"
+            SELECT * FROM chat_mutes
+            WHERE chat_id = $1 AND muted_until > now()
+            ORDER BY muted_until
+            "#,
+        )
+        .bind(chat_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(mutes)
+    }
+
+    async fn save_ban(&self, ban: &ChatBan) -> Result<ChatBan, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let saved = sqlx::query_as(
+            r#"
+            INSERT INTO chat_bans (chat_id, user_id, banned_by, reason)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (chat_id, user_id) DO UPDATE
+            SET banned_by = EXCLUDED.banned_by, reason = EXCLUDED.reason, created_at = now()
+            RETURNING *
+            "#,
+        )
+        .bind(ban.chat_id)
+        .bind(ban.user_id)
+        .bind(ban.banned_by)
+        .bind(&ban.reason)
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM chat_members WHERE chat_id = $1 AND user_id = $2")
+            .bind(ban.chat_id)
+            .bind(ban.user_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(saved)
+    }
+
+    async fn delete_ban(&self, chat_id: i64, user_id: i64) -> Result<Option<ChatBan>, AppError> {
+        let ban =
+            sqlx::query_as("DELETE FROM chat_bans WHERE chat_id = $1 AND user_id = $2 RETURNING *")
+                .bind(chat_id)
+                .bind(user_id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(ban)
+    }
+
+    async fn update_slow_mode(&self, chat_id: i64, secs: i32) -> Result<Option<i32>, AppError> {
+        let secs = sqlx::query_scalar(
+            "UPDATE chats SET slow_mode_secs = $2 WHERE id = $1 RETURNING slow_mode_secs",
+        )
+        .bind(chat_id)
+        .bind(secs)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(secs)
+    }
+
+    async fn extract_bans(&self, chat_id: i64) -> Result<Vec<ChatBan>, AppError> {
+        let bans = sqlx::query_as("SELECT * FROM chat_bans WHERE chat_id = $1 ORDER BY created_at")
+            .bind(chat_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(bans)
+    }
+}
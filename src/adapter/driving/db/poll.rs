@@ -0,0 +1,154 @@
+use axum::async_trait;
+use sqlx::PgPool;
+
+use crate::{
+    domain::{
+        chat::Msg,
+        poll::{Poll, PollRepo, PollVote},
+    },
+    error::AppError,
+};
+
+#[derive(Clone)]
+pub struct PollRepoImpl {
+    pool: PgPool,
+}
+
+impl PollRepoImpl {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl PollRepo for PollRepoImpl {
+    async fn save(&self, msg: &Msg, poll: &Poll) -> Result<(Msg, Poll), AppError> {
+        let mut tx = self.pool.begin().await?;
+        let msg: Msg = sqlx::query_as(
+            r#"
+            INSERT INTO messages (chat_id, sender_id, content, content_type)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+            "#,
+        )
+        .bind(msg.chat_id)
+        .bind(msg.sender_id)
+        .bind(&msg.content)
+        .bind(msg.content_type)
+        .fetch_one(&mut *tx)
+        .await?;
+        let poll = sqlx::query_as(
+            r#"
+            INSERT INTO polls (msg_id, chat_id, question, options, multiple, anonymous, closes_at, created_by, counts)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING *
+            "#,
+        )
+        .bind(msg.id)
+        .bind(poll.chat_id)
+        .bind(&poll.question)
+        .bind(&poll.options)
+        .bind(poll.multiple)
+        .bind(poll.anonymous)
+        .bind(poll.closes_at)
+        .bind(poll.created_by)
+        .bind(&poll.counts)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok((msg, poll))
+    }
+
+    async fn extract_by_msg(&self, chat_id: i64, msg_id: i64) -> Result<Option<Poll>, AppError> {
+        let poll = sqlx::query_as("SELECT * FROM polls WHERE chat_id = $1 AND msg_id = $2")
+            .bind(chat_id)
+            .bind(msg_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(poll)
+    }
+
+    async fn extract_votes(&self, msg_id: i64) -> Result<Vec<PollVote>, AppError> {
+        let votes = sqlx::query_as(
+            "SELECT user_id, option FROM poll_votes WHERE msg_id = $1 ORDER BY option, created_at",
+        )
+        .bind(msg_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(votes)
+    }
+
+    async fn vote(
+        &self,
+        msg_id: i64,
+        user_id: i64,
+        options: &[i16],
+    ) -> Result<Option<Poll>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        // concurrent votes on the poll wait here, so every recount sees all earlier votes
+        let open: Option<bool> = sqlx::query_scalar(
+            r#"
+            SELECT NOT closed AND (closes_at IS NULL OR closes_at > now())
+            FROM polls WHERE msg_id = $1
+            FOR UPDATE
+            "#,
+        )
+        .bind(msg_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if open != Some(true) {
+            return Ok(None);
+        }
+        sqlx::query("DELETE FROM poll_votes WHERE msg_id = $1 AND user_id = $2")
+            .bind(msg_id)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO poll_votes (msg_id, user_id, option)
+            SELECT $1, $2, unnest($3::smallint[])
+            "#,
+        )
+        .bind(msg_id)
+        .bind(user_id)
+        .bind(options)
+        .execute(&mut *tx)
+        .await?;
+        let poll = sqlx::query_as(
+            r#"
+            UPDATE polls p
+            SET counts = ARRAY(
+                SELECT count(v.user_id)::int
+                FROM generate_subscripts(p.options, 1) AS i
+                LEFT JOIN poll_votes v ON v.msg_id = p.msg_id AND v.option = i - 1
+                GROUP BY i
+                ORDER BY i
+            ),
+            voters = (SELECT count(DISTINCT user_id) FROM poll_votes WHERE msg_id = p.msg_id)
+            WHERE msg_id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(msg_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(Some(poll))
+    }
+
+    async fn close(&self, msg_id: i64) -> Result<Option<Poll>, AppError> {
+        let poll = sqlx::query_as(
+            "UPDATE polls SET closed = TRUE WHERE msg_id = $1 AND NOT closed RETURNING *",
+        )
+        .bind(msg_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(poll)
+    }
+}
@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use axum::async_trait;
+use sqlx::PgPool;
+
+use crate::{
+    domain::rate_limit::{Quota, RateLimitRepo},
+    error::AppError,
+};
+
+#[derive(Clone)]
+pub struct RateLimitRepoImpl {
+    pool: PgPool,
+}
+
+impl RateLimitRepoImpl {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl RateLimitRepo for RateLimitRepoImpl {
+    async fn take(&self, key: &str, quota: &Quota) -> Result<Option<Duration>, AppError> {
+        let interval = quota.interval.as_secs_f64();
+        let tolerance = quota.tolerance().as_secs_f64();
+        // the row lock serializes the replicas, a refused request books nothing
+        let taken: Option<i32> = sqlx::query_scalar(
+            r#"
+            INSERT INTO rate_buckets AS b (key, tat)
+            VALUES ($1, now() + make_interval(secs => $2))
+            ON CONFLICT (key) DO UPDATE
+            SET tat = GREATEST(b.tat, now()) + make_interval(secs => $2)
+            WHERE GREATEST(b.tat, now()) + make_interval(secs => $2) <= now() + make_interval(secs => $3)
+            RETURNING 1
+            "#,
+        )
+        .bind(key)
+        .bind(interval)
+        .bind(tolerance)
+        .fetch_optional(&self.pool)
+        .await?;
+        if taken.is_some() {
+            return Ok(None);
+        }
+        let wait: Option<f64> = sqlx::query_scalar(
+            r#"
+            SELECT extract(epoch FROM GREATEST(tat, now()) + make_interval(secs => $2)
+                - now() - make_interval(secs => $3))::float8
+            FROM rate_buckets WHERE key = $1
+            "#,
+        )
+        .bind(key)
+        .bind(interval)
+        .bind(tolerance)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(Some(Duration::from_secs_f64(
+            wait.unwrap_or_default().max(0.0),
+        )))
+    }
+
+    async fn prune(&self) -> Result<u64, AppError> {
+        let ret = sqlx::query("DELETE FROM rate_buckets WHERE tat <= now()")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(ret.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[sqlx::test]
+    async fn take_should_share_the_bucket_across_replicas(pool: PgPool) {
+        let (first, second) = (
+            RateLimitRepoImpl::new(pool.clone()),
+            RateLimitRepoImpl::new(pool.clone()),
+        );
+        let quota = Quota {
+            burst: 2,
+            interval: Duration::from_secs(60),
+        };
+        let (a, b) = tokio::join!(first.take("key", &quota), second.take("key", &quota));
+        assert_eq!((a.unwrap(), b.unwrap()), (None, None));
+        let wait = first.take("key", &quota).await.unwrap().unwrap();
+        assert!(wait > Duration::from_secs(59) && wait <= Duration::from_secs(60));
+        assert!(second.take("key", &quota).await.unwrap().is_some());
+        assert_eq!(second.take("other", &quota).await.unwrap(), None);
+
+        // a booked bucket is kept, an idle one is forgotten
+        assert_eq!(first.prune().await.unwrap(), 0);
+        sqlx::query("UPDATE rate_buckets SET tat = now() WHERE key = 'key'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(first.prune().await.unwrap(), 1);
+        assert_eq!(first.take("key", &quota).await.unwrap(), None);
+    }
+}
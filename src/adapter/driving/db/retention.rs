@@ -0,0 +1,241 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::{
+    domain::{
+        chat::Msg,
+        retention::{ExpiringChat, RetentionPolicy, RetentionRepo},
+    },
+    error::AppError,
+};
+
+#[derive(Clone)]
+pub struct RetentionRepoImpl {
+    pool: PgPool,
+}
+
+impl RetentionRepoImpl {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl RetentionRepo for RetentionRepoImpl {
+    async fn extract_ws_policy(&self, ws_id: i64) -> Result<Option<RetentionPolicy>, AppError> {
+        let policy = sqlx::query_as(
+            "SELECT retention, retention_days, legal_hold FROM workspaces WHERE id = $1",
+        )
+        .bind(ws_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(policy)
+    }
+
+    async fn update_ws_policy(
+        &self,
+        ws_id: i64,
+        policy: &RetentionPolicy,
+    ) -> Result<Option<RetentionPolicy>, AppError> {
+        let policy = sqlx::query_as(
+            r#"
+            UPDATE workspaces SET retention = $2, retention_days = $3, legal_hold = $4
+            WHERE id = $1
+            RETURNING retention, retention_days, legal_hold
+            "#,
+        )
+        .bind(ws_id)
+        .bind(policy.retention)
+        .bind(policy.retention_days)
+        .bind(policy.legal_hold)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(policy)
+    }
+
+    async fn extract_chat_policy(
+        &self,
+        ws_id: i64,
+        chat_id: i64,
+    ) -> Result<Option<RetentionPolicy>, AppError> {
+        let policy = sqlx::query_as(
+            r#"
+            SELECT retention, retention_days, legal_hold FROM chats
+            WHERE id = $1 AND ws_id = $2 AND status <> 'purged'
+            "#,
+        )
+        .bind(chat_id)
+        .bind(ws_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(policy)
+    }
+
+    async fn update_chat_policy(
+        &self,
+        ws_id: i64,
+        chat_id: i64,
+        policy: &RetentionPolicy,
+    ) -> Result<Option<RetentionPolicy>, AppError> {
+        let policy = sqlx::query_as(
+            r#"
+            UPDATE chats SET retention = $3, retention_days = $4, legal_hold = $5
+            WHERE id = $1 AND ws_id = $2 AND status <> 'purged'
+            RETURNING retention, retention_days, legal_hold
+            "#,
+        )
+        .bind(chat_id)
+        .bind(ws_id)
+        .bind(policy.retention)
+        .bind(policy.retention_days)
+        .bind(policy.legal_hold)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(policy)
+    }
+
+    async fn extract_expiring_chats(&self, limit: i64) -> Result<Vec<ExpiringChat>, AppError> {
+        let chats = sqlx::query_as(
+            r#"
+            SELECT c.id AS chat_id, c.ws_id, p.before
+            FROM chats c
+            JOIN workspaces w ON w.id = c.ws_id
+            CROSS JOIN LATERAL (
+                SELECT now() - make_interval(days => CASE c.retention
+                    WHEN 'inherit' THEN w.retention_days ELSE c.retention_days END) AS before
+            ) p
+            CROSS JOIN LATERAL (
+                SELECT min(m.created_at) AS oldest FROM messages m WHERE m.chat_id = c.id
+            ) o
+            WHERE NOT c.legal_hold AND NOT w.legal_hold
+            AND (CASE c.retention WHEN 'inherit' THEN w.retention ELSE c.retention END) = 'days'
+            AND o.oldest < p.before
+            -- the most overdue first, so a chat left with a backlog does not starve the others
+            ORDER BY o.oldest - p.before, c.id
+            LIMIT $1
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(chats)
+    }
+
+    async fn delete_expired_messages(
+        &self,
+        chat_id: i64,
+        before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<Msg>, AppError> {
+        // pins hold a plain reference to their message, so they go in the same statement
+        let msgs = sqlx::query_as(
+            r#"
+            WITH doomed AS (
+                SELECT m.id FROM messages m
+                JOIN chats c ON c.id = m.chat_id
+                JOIN workspaces w ON w.id = c.ws_id
+                WHERE m.chat_id = $1 AND m.created_at < $2
+                AND NOT c.legal_hold AND NOT w.legal_hold
+                ORDER BY m.created_at
+                LIMIT $3
+                FOR UPDATE OF m SKIP LOCKED
+            ), pins AS (
+                DELETE FROM chat_pins WHERE chat_id = $1 AND msg_id IN (SELECT id FROM doomed)
+            )
+            DELETE FROM messages WHERE id IN (SELECT id FROM doomed)
+            RETURNING *
+            "#,
+        )
+        .bind(chat_id)
+        .bind(before)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(msgs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        domain::chat::{ChatType, ContentType},
+        service::{
+            auth::ClaimUser,
+            chat::{CreateChatDto, SendMsgDto},
+        },
+        test_util::{signup, state},
+        AppState,
+    };
+
+    /// a channel keeping its messages for a day, with a message sent `age_days` ago
+    async fn expiring_chat(
+        state: &AppState,
+        pool: &PgPool,
+        user: &ClaimUser,
+        name: &str,
+        age_days: i32,
+    ) -> i64 {
+        let input = CreateChatDto {
+            name: Some(name.to_owned()),
+            chat_type: ChatType::PublicChannel,
+            members: vec![user.id],
+        };
+        let chat = state.chat.create(user, input).await.unwrap();
+        let input = SendMsgDto {
+            content: "old news".to_owned(),
+            content_type: ContentType::Plain,
+            client_id: None,
+            quote_id: None,
+            files: vec![],
+        };
+        state
+            .chat
+            .send_msg(user, chat.id as _, input)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE chats SET retention = 'days', retention_days = 1 WHERE id = $1")
+            .bind(chat.id)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "UPDATE messages SET created_at = now() - make_interval(days => $2) WHERE chat_id = $1",
+        )
+        .bind(chat.id)
+        .bind(age_days)
+        .execute(pool)
+        .await
+        .unwrap();
+        chat.id
+    }
+
+    #[sqlx::test]
+    async fn extract_expiring_chats_should_start_with_the_most_overdue(pool: PgPool) {
+        let state = state(pool.clone()).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let recent = expiring_chat(&state, &pool, &alice, "recent", 2).await;
+        let overdue = expiring_chat(&state, &pool, &alice, "overdue", 10).await;
+        let repo = RetentionRepoImpl::new(pool.clone());
+
+        let chats = repo.extract_expiring_chats(1).await.unwrap();
+        assert_eq!(chats[0].chat_id, overdue);
+
+        // a chat on legal hold keeps its messages
+        sqlx::query("UPDATE chats SET legal_hold = TRUE WHERE id = $1")
+            .bind(overdue)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let chats = repo.extract_expiring_chats(10).await.unwrap();
+        let ids: Vec<i64> = chats.iter().map(|v| v.chat_id).collect();
+        assert_eq!(ids, vec![recent]);
+    }
+}
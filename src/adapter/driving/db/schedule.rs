@@ -0,0 +1,229 @@
+use axum::async_trait;
+use sqlx::PgPool;
+
+use crate::{
+    domain::schedule::{ScheduleRepo, ScheduleStatus, ScheduledItem},
+    error::AppError,
+};
+
+#[derive(Clone)]
+pub struct ScheduleRepoImpl {
+    pool: PgPool,
+}
+
+impl ScheduleRepoImpl {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ScheduleRepo for ScheduleRepoImpl {
+    async fn save(&self, item: &ScheduledItem) -> Result<ScheduledItem, AppError> {
+        let item = sqlx::query_as(
+            r#"
+            INSERT INTO scheduled_items (kind, user_id, ws_id, chat_id, msg_id, content, content_type, run_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING *
+            "#,
+        )
+        .bind(item.kind)
+        .bind(item.user_id)
+        .bind(item.ws_id)
+        .bind(item.chat_id)
+        .bind(item.msg_id)
+        .bind(&item.content)
+        .bind(item.content_type)
+        .bind(item.run_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(item)
+    }
+
+    async fn extract_by_id(&self, id: i64) -> Result<Option<ScheduledItem>, AppError> {
+        let item = sqlx::query_as("SELECT * FROM scheduled_items WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(item)
+    }
+
+    async fn extract_by_user(
+        &self,
+        user_id: i64,
+        ws_id: i64,
+        status: ScheduleStatus,
+    ) -> Result<Vec<ScheduledItem>, AppError> {
+        let items = sqlx::query_as(
+            r#"
+            SELECT * FROM scheduled_items
+            WHERE user_id = $1 AND ws_id = $2 AND status = $3
+            ORDER BY run_at, id
+            "#,
+        )
+        .bind(user_id)
+        .bind(ws_id)
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(items)
+    }
+
+    async fn update_pending(
+        &self,
+        item: &ScheduledItem,
+    ) -> Result<Option<ScheduledItem>, AppError> {
+        let item = sqlx::query_as(
+            r#"
+            UPDATE scheduled_items
+            SET content = $2, content_type = $3, run_at = $4, updated_at = now()
+            WHERE id = $1 AND status = 'pending' AND locked_until <= now()
+            RETURNING *
+            "#,
+        )
+        .bind(item.id)
+        .bind(&item.content)
+        .bind(item.content_type)
+        .bind(item.run_at)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(item)
+    }
+
+    async fn cancel(&self, id: i64) -> Result<Option<ScheduledItem>, AppError> {
+        let item = sqlx::query_as(
+            r#"
+            UPDATE scheduled_items
+            SET status = 'canceled', updated_at = now()
+            WHERE id = $1 AND status = 'pending' AND locked_until <= now()
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(item)
+    }
+
+    async fn claim_due(&self, limit: i64, lease_secs: i64) -> Result<Vec<ScheduledItem>, AppError> {
+        let items = sqlx::query_as(
+            r#"
+            UPDATE scheduled_items
+            SET locked_until = now() + make_interval(secs => $2), attempts = attempts + 1
+            WHERE id IN (
+                SELECT id FROM scheduled_items
+                WHERE status = 'pending' AND run_at <= now() AND locked_until <= now()
+                ORDER BY run_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING *
+            "#,
+        )
+        .bind(limit)
+        .bind(lease_secs as f64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(items)
+    }
+
+    async fn finish(
+        &self,
+        id: i64,
+        status: ScheduleStatus,
+        sent_msg_id: Option<i64>,
+        error: Option<&str>,
+    ) -> Result<bool, AppError> {
+        let ret = sqlx::query(
+            r#"
+            UPDATE scheduled_items
+            SET status = $2, sent_msg_id = $3, error = $4, updated_at = now()
+            WHERE id = $1 AND status = 'pending'
+            "#,
+        )
+        .bind(id)
+        .bind(status)
+        .bind(sent_msg_id)
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(ret.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::*;
+    use crate::{
+        domain::{chat::ChatType, schedule::ScheduleKind},
+        service::chat::CreateChatDto,
+        test_util::{signup, state},
+    };
+
+    #[sqlx::test]
+    async fn claim_due_should_lease_an_item_to_one_scheduler(pool: PgPool) {
+        let state = state(pool.clone()).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let input = CreateChatDto {
+            name: Some("general".to_owned()),
+            chat_type: ChatType::PublicChannel,
+            members: vec![alice.id],
+        };
+        let chat = state.chat.create(&alice, input).await.unwrap();
+        let (first, second) = (
+            ScheduleRepoImpl::new(pool.clone()),
+            ScheduleRepoImpl::new(pool.clone()),
+        );
+        let item = ScheduledItem::new(
+            ScheduleKind::Message,
+            alice.id as _,
+            alice.ws_id as _,
+            chat.id,
+            "hi".to_owned(),
+            Utc::now() - Duration::seconds(1),
+        );
+        let item = first.save(&item).await.unwrap();
+        let later = ScheduledItem {
+            run_at: Utc::now() + Duration::hours(1),
+            ..item.clone()
+        };
+        first.save(&later).await.unwrap();
+
+        let (a, b) = tokio::join!(first.claim_due(10, 60), second.claim_due(10, 60));
+        let mut claimed = a.unwrap();
+        claimed.extend(b.unwrap());
+        assert_eq!(claimed.len(), 1);
+        assert_eq!((claimed[0].id, claimed[0].attempts), (item.id, 1));
+        assert!(second.claim_due(10, 60).await.unwrap().is_empty());
+        // a leased item is being delivered
+        assert!(first.cancel(item.id).await.unwrap().is_none());
+
+        // the lease of a crashed scheduler runs out
+        sqlx::query("UPDATE scheduled_items SET locked_until = now() WHERE id = $1")
+            .bind(item.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let claimed = second.claim_due(10, 60).await.unwrap();
+        assert_eq!(claimed.len(), 1);
+        assert_eq!(claimed[0].attempts, 2);
+
+        let done = ScheduleStatus::Done;
+        assert!(second.finish(item.id, done, None, None).await.unwrap());
+        assert!(!first.finish(item.id, done, None, None).await.unwrap());
+        sqlx::query("UPDATE scheduled_items SET locked_until = now()")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(first.claim_due(10, 60).await.unwrap().is_empty());
+    }
+}
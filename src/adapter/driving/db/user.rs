@@ -2,7 +2,7 @@ use axum::async_trait;
 use sqlx::PgPool;
 
 use crate::{
-    domain::user::{User, UserRepo, Workspace},
+    domain::user::{User, UserRepo, Workspace, WsMember},
     error::AppError,
 };
 
@@ -22,9 +22,10 @@ impl UserRepo for UserRepoImpl {
     async fn extract_all_users(&self, ws_id: u64) -> Result<Vec<User>, AppError> {
         let users = sqlx::query_as(
             r#"
-        SELECT *
-        FROM users
-        WHERE ws_id = $1
+        SELECT u.*
+        FROM users u
+        JOIN ws_members m ON m.user_id = u.id
+        WHERE m.ws_id = $1
         "#,
         )
         .bind(ws_id as i64)
@@ -32,13 +33,25 @@ impl UserRepo for UserRepoImpl {
         .await?;
         Ok(users)
     }
-    async fn find_by_email(&self, email: &str) -> Result<Option<User>, AppError> {
-        let user = sqlx::query_as(
-            "select id, ws_id, fullname, email, password_hash, created_at from users where email = $1",
+    async fn extract_active_users(&self, ws_id: u64) -> Result<Vec<User>, AppError> {
+        let users = sqlx::query_as(
+            r#"
+        SELECT u.*
+        FROM users u
+        JOIN ws_members m ON m.user_id = u.id
+        WHERE m.ws_id = $1 AND m.status = 'active' AND u.status = 'active'
+        "#,
         )
-        .bind(email)
-        .fetch_optional(&self.pool)
+        .bind(ws_id as i64)
+        .fetch_all(&self.pool)
         .await?;
+        Ok(users)
+    }
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, AppError> {
+        let user = sqlx::query_as("select * from users where email = $1")
+            .bind(email)
+            .fetch_optional(&self.pool)
+            .await?;
         Ok(user)
     }
 
@@ -59,7 +72,7 @@ impl UserRepo for UserRepoImpl {
             r#"
         insert into users (ws_id, email, fullname, password_hash)
         values ($1, $2, $3, $4)
-        returning id, ws_id, email, fullname, password_hash, created_at
+        returning *
         "#,
         )
         .bind(user.ws_id)
@@ -71,6 +84,35 @@ impl UserRepo for UserRepoImpl {
         Ok(user)
     }
 
+    async fn update(&self, user: &User) -> Result<User, AppError> {
+        let user = sqlx::query_as(
+            r#"
+        UPDATE users SET fullname = $1, ws_id = $2, display_name = $3, avatar_url = $4,
+            title = $5, status_text = $6, status_emoji = $7, status_expires_at = $8,
+            timezone = $9, email = $10, password_hash = $11, status = $12, token_version = $13
+        WHERE id = $14
+        RETURNING *
+        "#,
+        )
+        .bind(&user.fullname)
+        .bind(user.ws_id)
+        .bind(&user.display_name)
+        .bind(&user.avatar_url)
+        .bind(&user.title)
+        .bind(&user.status_text)
+        .bind(&user.status_emoji)
+        .bind(user.status_expires_at)
+        .bind(&user.timezone)
+        .bind(&user.email)
+        .bind(&user.password_hash)
+        .bind(&user.status)
+        .bind(user.token_version)
+        .bind(user.id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(user)
+    }
+
     async fn find_ws_by_name(&self, name: &str) -> Result<Option<Workspace>, AppError> {
         let ws = sqlx::query_as(
             r#"
@@ -99,6 +141,22 @@ impl UserRepo for UserRepoImpl {
         Ok(ws)
     }
 
+    async fn extract_user_ws(&self, user_id: i64) -> Result<Vec<Workspace>, AppError> {
+        let ws = sqlx::query_as(
+            r#"
+        SELECT w.*
+        FROM workspaces w
+        JOIN ws_members m ON m.ws_id = w.id
+        WHERE m.user_id = $1 AND m.status = 'active'
+        ORDER BY w.id
+        "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(ws)
+    }
+
     async fn save_ws(&self, ws: &Workspace) -> Result<Workspace, AppError> {
         let ws = sqlx::query_as(
             r#"
@@ -113,4 +171,89 @@ impl UserRepo for UserRepoImpl {
         .await?;
         Ok(ws)
     }
+
+    async fn update_ws(&self, ws: &Workspace) -> Result<Workspace, AppError> {
+        let ws = sqlx::query_as(
+            r#"
+        UPDATE workspaces SET name = $1, owner_id = $2
+        WHERE id = $3
+        RETURNING id, name, owner_id, created_at
+        "#,
+        )
+        .bind(&ws.name)
+        .bind(ws.owner_id)
+        .bind(ws.id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(ws)
+    }
+
+    async fn find_member(&self, ws_id: i64, user_id: i64) -> Result<Option<WsMember>, AppError> {
+        let member = sqlx::query_as(
+            r#"
+        SELECT *
+        FROM ws_members
+        WHERE ws_id = $1 AND user_id = $2
+        "#,
+        )
+        .bind(ws_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(member)
+    }
+
+    async fn extract_members(&self, ws_id: i64) -> Result<Vec<WsMember>, AppError> {
+        let members = sqlx::query_as(
+            r#"
+        SELECT *
+        FROM ws_members
+        WHERE ws_id = $1
+        ORDER BY user_id
+        "#,
+        )
+        .bind(ws_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(members)
+    }
+
+    async fn save_member(&self, member: &WsMember) -> Result<WsMember, AppError> {
+        let member = sqlx::query_as(
+            r#"
+        INSERT INTO ws_members (ws_id, user_id, role, status)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (ws_id, user_id) DO UPDATE SET role = $3, status = $4
+        RETURNING *
+        "#,
+        )
+        .bind(member.ws_id)
+        .bind(member.user_id)
+        .bind(&member.role)
+        .bind(&member.status)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(member)
+    }
+
+    async fn delete_member(&self, ws_id: i64, user_id: i64) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM ws_members WHERE ws_id = $1 AND user_id = $2")
+            .bind(ws_id)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            r#"
+        DELETE FROM chat_members
+        WHERE user_id = $2 AND chat_id IN (SELECT id FROM chats WHERE ws_id = $1)
+        "#,
+        )
+        .bind(ws_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
 }
@@ -0,0 +1,294 @@
+use axum::async_trait;
+use sqlx::{types::Json, PgPool};
+
+use crate::{
+    domain::webhook::{Delivery, DeliveryJob, DeliveryStatus, Webhook, WebhookRepo},
+    error::AppError,
+};
+
+#[derive(Clone)]
+pub struct WebhookRepoImpl {
+    pool: PgPool,
+}
+
+impl WebhookRepoImpl {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl WebhookRepo for WebhookRepoImpl {
+    async fn save(&self, webhook: &Webhook) -> Result<Webhook, AppError> {
+        let saved = if webhook.id == -1 {
+            sqlx::query_as(
+                r#"
+                INSERT INTO webhooks (ws_id, url, secret, events, active, created_by)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                RETURNING *
+                "#,
+            )
+            .bind(webhook.ws_id)
+            .bind(&webhook.url)
+            .bind(&webhook.secret)
+            .bind(&webhook.events)
+            .bind(webhook.active)
+            .bind(webhook.created_by)
+            .fetch_one(&self.pool)
+            .await?
+        } else {
+            sqlx::query_as(
+                r#"
+                UPDATE webhooks SET url = $3, secret = $4, events = $5, active = $6
+                WHERE id = $1 AND ws_id = $2
+                RETURNING *
+                "#,
+            )
+            .bind(webhook.id)
+            .bind(webhook.ws_id)
+            .bind(&webhook.url)
+            .bind(&webhook.secret)
+            .bind(&webhook.events)
+            .bind(webhook.active)
+            .fetch_one(&self.pool)
+            .await?
+        };
+
+        Ok(saved)
+    }
+
+    async fn extract_by_ws(&self, ws_id: i64) -> Result<Vec<Webhook>, AppError> {
+        let webhooks = sqlx::query_as("SELECT * FROM webhooks WHERE ws_id = $1 ORDER BY id")
+            .bind(ws_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(webhooks)
+    }
+
+    async fn find_by_id(&self, ws_id: i64, id: i64) -> Result<Option<Webhook>, AppError> {
+        let webhook = sqlx::query_as("SELECT * FROM webhooks WHERE id = $1 AND ws_id = $2")
+            .bind(id)
+            .bind(ws_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(webhook)
+    }
+
+    async fn delete(&self, ws_id: i64, id: i64) -> Result<Option<Webhook>, AppError> {
+        let webhook =
+            sqlx::query_as("DELETE FROM webhooks WHERE id = $1 AND ws_id = $2 RETURNING *")
+                .bind(id)
+                .bind(ws_id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(webhook)
+    }
+
+    async fn enqueue(
+        &self,
+        chat_id: i64,
+        event: &str,
+        key: &str,
+        payload: &serde_json::Value,
+    ) -> Result<u64, AppError> {
+        let ret = sqlx::query(
+            r#"
+            INSERT INTO webhook_deliveries (webhook_id, event, event_key, payload)
+            SELECT w.id, $2, $3, $4
+            FROM webhooks w JOIN chats c ON c.ws_id = w.ws_id
+            WHERE c.id = $1 AND w.active AND $2 = ANY(w.events)
+            ON CONFLICT (webhook_id, event, event_key) DO NOTHING
+            "#,
+        )
+        .bind(chat_id)
+        .bind(event)
+        .bind(key)
+        .bind(Json(payload))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(ret.rows_affected())
+    }
+
+    async fn claim_deliveries(
+        &self,
+        limit: i64,
+        lease_secs: i64,
+    ) -> Result<Vec<DeliveryJob>, AppError> {
+        let jobs = sqlx::query_as(
+            r#"
+            UPDATE webhook_deliveries d
+            SET next_attempt_at = now() + make_interval(secs => $2), attempts = d.attempts + 1
+            FROM webhooks w
+            WHERE w.id = d.webhook_id AND d.id IN (
+                SELECT d.id FROM webhook_deliveries d JOIN webhooks w ON w.id = d.webhook_id
+                WHERE d.status = 'pending' AND d.next_attempt_at <= now() AND w.active
+                ORDER BY d.next_attempt_at
+                LIMIT $1
+                FOR UPDATE OF d SKIP LOCKED
+            )
+            RETURNING d.id, d.webhook_id, w.url, w.secret, d.event, d.payload, d.attempts
+            "#,
+        )
+        .bind(limit)
+        .bind(lease_secs as f64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(jobs)
+    }
+
+    async fn complete_delivery(&self, id: i64, status: i16) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = 'delivered', last_status = $2, last_error = NULL, delivered_at = now()
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(status)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn fail_delivery(
+        &self,
+        id: i64,
+        status: Option<i16>,
+        error: &str,
+        retry_secs: Option<i64>,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET last_status = $2, last_error = left($3, 500),
+                status = CASE WHEN $4::float8 IS NULL THEN 'dead' ELSE status END,
+                next_attempt_at = now() + make_interval(secs => coalesce($4::float8, 0))
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(status)
+        .bind(error)
+        .bind(retry_secs.map(|v| v as f64))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn extract_deliveries(
+        &self,
+        webhook_id: i64,
+        status: Option<DeliveryStatus>,
+        before_id: i64,
+        limit: i64,
+    ) -> Result<Vec<Delivery>, AppError> {
+        let deliveries = sqlx::query_as(
+            r#"
+            SELECT * FROM webhook_deliveries
+            WHERE webhook_id = $1 AND id < $2
+            AND ($3::webhook_delivery_status IS NULL OR status = $3)
+            ORDER BY id DESC
+            LIMIT $4
+            "#,
+        )
+        .bind(webhook_id)
+        .bind(before_id)
+        .bind(status)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    async fn redeliver(&self, webhook_id: i64, id: i64) -> Result<Option<Delivery>, AppError> {
+        let delivery = sqlx::query_as(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = 'pending', attempts = 0, next_attempt_at = now()
+            WHERE id = $1 AND webhook_id = $2 AND status = 'dead'
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(webhook_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(delivery)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// a workspace, its owner and a chat of the workspace
+    async fn seed_chat(pool: &PgPool) -> (i64, i64, i64) {
+        let (ws_id, user_id): (i64, i64) = sqlx::query_as(
+            r#"
+            WITH ws AS (
+                INSERT INTO workspaces (name, owner_id) VALUES ('acme', 0) RETURNING id
+            )
+            INSERT INTO users (ws_id, fullname, email, password_hash)
+            SELECT id, 'alice', 'alice@acme.org', '' FROM ws
+            RETURNING ws_id, id
+            "#,
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let chat_id = sqlx::query_scalar(
+            r#"
+            INSERT INTO chats (ws_id, name, chat_type)
+            VALUES ($1, 'general', 'public_channel')
+            RETURNING id
+            "#,
+        )
+        .bind(ws_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        (ws_id, user_id, chat_id)
+    }
+
+    #[sqlx::test]
+    async fn enqueue_should_queue_an_event_once_per_key(pool: PgPool) {
+        let (ws_id, user_id, chat_id) = seed_chat(&pool).await;
+        let repo = WebhookRepoImpl::new(pool);
+        let webhook = Webhook::new(
+            ws_id,
+            user_id,
+            "https://example.com/hook".into(),
+            "secret".into(),
+            vec!["new_message".into()],
+        );
+        let webhook = repo.save(&webhook).await.unwrap();
+        let payload = json!({ "event": "NewMessage", "id": 1 });
+
+        // two replicas notified of the same event
+        let first = repo.enqueue(chat_id, "new_message", "k1", &payload).await;
+        let second = repo.enqueue(chat_id, "new_message", "k1", &payload).await;
+        assert_eq!(first.unwrap(), 1);
+        assert_eq!(second.unwrap(), 0);
+        // another event
+        let other = repo.enqueue(chat_id, "new_message", "k2", &payload).await;
+        assert_eq!(other.unwrap(), 1);
+
+        let deliveries = repo
+            .extract_deliveries(webhook.id, None, i64::MAX, 10)
+            .await
+            .unwrap();
+        assert_eq!(deliveries.len(), 2);
+    }
+}
@@ -1,5 +1,8 @@
 use argon2::{
-    password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
+    password_hash::{
+        rand_core::{OsRng, RngCore},
+        PasswordHasher, SaltString,
+    },
     Argon2, PasswordHash, PasswordVerifier,
 };
 
@@ -20,3 +23,18 @@ pub fn verify_passwd(passwd: &str, passwd_hash: &str) -> Result<(), AppError> {
     hasher.verify_password(passwd.as_bytes(), &password_hash)?;
     Ok(())
 }
+
+/// hex of `len` random bytes, for secrets handed out once
+pub fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// hex sha256, tokens are looked up by it so a leaked table does not leak them
+pub fn sha256_hex(data: &str) -> String {
+    hmac_sha256::Hash::hash(data.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
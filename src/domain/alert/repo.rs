@@ -0,0 +1,12 @@
+use axum::async_trait;
+
+use crate::error::AppError;
+
+#[async_trait]
+pub trait AlertRepo {
+    /// claim the alert of an event for the users, returns the users whose alert was not claimed
+    /// by another replica before
+    async fn claim(&self, event_key: &str, user_ids: &[u64]) -> Result<Vec<u64>, AppError>;
+    /// forget the claims of events no replica is notified of anymore
+    async fn prune(&self) -> Result<u64, AppError>;
+}
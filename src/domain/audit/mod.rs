@@ -0,0 +1,189 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, types::Json};
+
+mod repo;
+
+pub use repo::AuditRepo;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuditAction {
+    Signup,
+    Signin,
+    SigninFailed,
+    WorkspaceSwitch,
+    ChatCreate,
+    ChatUpdate,
+    ChatArchive,
+    ChatRestore,
+    ChatDelete,
+    ChatPurge,
+    ChatJoin,
+    ChatLeave,
+    ChatMemberAdd,
+    ChatMemberRemove,
+    FileUpload,
+    AuditExport,
+    WorkspaceRename,
+    WorkspaceTransferOwner,
+    MemberAdd,
+    MemberUpdate,
+    MemberRemove,
+    RetentionUpdate,
+    MessageReport,
+    ReportResolve,
+    MessageDelete,
+    MemberMute,
+    MemberUnmute,
+    MemberBan,
+    MemberUnban,
+    SlowModeUpdate,
+    WebhookCreate,
+    WebhookUpdate,
+    WebhookDelete,
+    BotCreate,
+    BotDeactivate,
+    BotTokenCreate,
+    BotTokenRevoke,
+    IncomingWebhookCreate,
+    IncomingWebhookRevoke,
+    CommandCreate,
+    CommandDelete,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Signup => "signup",
+            AuditAction::Signin => "signin",
+            AuditAction::SigninFailed => "signin_failed",
+            AuditAction::WorkspaceSwitch => "workspace_switch",
+            AuditAction::ChatCreate => "chat_create",
+            AuditAction::ChatUpdate => "chat_update",
+            AuditAction::ChatArchive => "chat_archive",
+            AuditAction::ChatRestore => "chat_restore",
+            AuditAction::ChatDelete => "chat_delete",
+            AuditAction::ChatPurge => "chat_purge",
+            AuditAction::ChatJoin => "chat_join",
+            AuditAction::ChatLeave => "chat_leave",
+            AuditAction::ChatMemberAdd => "chat_member_add",
+            AuditAction::ChatMemberRemove => "chat_member_remove",
+            AuditAction::FileUpload => "file_upload",
+            AuditAction::AuditExport => "audit_export",
+            AuditAction::WorkspaceRename => "workspace_rename",
+            AuditAction::WorkspaceTransferOwner => "workspace_transfer_owner",
+            AuditAction::MemberAdd => "member_add",
+            AuditAction::MemberUpdate => "member_update",
+            AuditAction::MemberRemove => "member_remove",
+            AuditAction::RetentionUpdate => "retention_update",
+            AuditAction::MessageReport => "message_report",
+            AuditAction::ReportResolve => "report_resolve",
+            AuditAction::MessageDelete => "message_delete",
+            AuditAction::MemberMute => "member_mute",
+            AuditAction::MemberUnmute => "member_unmute",
+            AuditAction::MemberBan => "member_ban",
+            AuditAction::MemberUnban => "member_unban",
+            AuditAction::SlowModeUpdate => "slow_mode_update",
+            AuditAction::WebhookCreate => "webhook_create",
+            AuditAction::WebhookUpdate => "webhook_update",
+            AuditAction::WebhookDelete => "webhook_delete",
+            AuditAction::BotCreate => "bot_create",
+            AuditAction::BotDeactivate => "bot_deactivate",
+            AuditAction::BotTokenCreate => "bot_token_create",
+            AuditAction::BotTokenRevoke => "bot_token_revoke",
+            AuditAction::IncomingWebhookCreate => "incoming_webhook_create",
+            AuditAction::IncomingWebhookRevoke => "incoming_webhook_revoke",
+            AuditAction::CommandCreate => "command_create",
+            AuditAction::CommandDelete => "command_delete",
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub ws_id: i64,
+    pub actor_id: i64,
+    pub action: String,
+    pub target: String,
+    pub detail: Json<serde_json::Value>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// where a request came from
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// events matching every given field
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AuditFilter {
+    pub actor_id: Option<i64>,
+    pub action: Option<String>,
+    pub target: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl AuditEvent {
+    pub fn new(
+        ws_id: i64,
+        actor_id: i64,
+        action: AuditAction,
+        target: impl Into<String>,
+        detail: serde_json::Value,
+    ) -> Self {
+        Self {
+            id: -1,
+            ws_id,
+            actor_id,
+            action: action.as_str().to_owned(),
+            target: target.into(),
+            detail: Json(detail),
+            ip: None,
+            user_agent: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn with_client(mut self, client: &ClientInfo) -> Self {
+        self.ip = client.ip.clone();
+        self.user_agent = client.user_agent.clone();
+        self
+    }
+
+    pub const CSV_HEADER: &'static str =
+        "id,created_at,ws_id,actor_id,action,target,ip,user_agent,detail\r\n";
+
+    pub fn to_csv_row(&self) -> String {
+        let fields = [
+            self.id.to_string(),
+            self.created_at.to_rfc3339(),
+            self.ws_id.to_string(),
+            self.actor_id.to_string(),
+            csv_field(&self.action),
+            csv_field(&self.target),
+            csv_field(self.ip.as_deref().unwrap_or_default()),
+            csv_field(self.user_agent.as_deref().unwrap_or_default()),
+            csv_field(&self.detail.0.to_string()),
+        ];
+        fields.join(",") + "\r\n"
+    }
+}
+
+/// quoted when needed, cells a spreadsheet would run as a formula are neutralized
+fn csv_field(value: &str) -> String {
+    let value = match value.chars().next() {
+        Some('=' | '+' | '-' | '@' | '\t' | '\r') => format!("'{}", value),
+        _ => value.to_owned(),
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
@@ -0,0 +1,18 @@
+use axum::async_trait;
+
+use crate::error::AppError;
+
+use super::{AuditEvent, AuditFilter};
+
+#[async_trait]
+pub trait AuditRepo {
+    async fn append(&self, event: &AuditEvent) -> Result<AuditEvent, AppError>;
+    /// events of the workspace older than `before_id`, newest first
+    async fn extract(
+        &self,
+        ws_id: i64,
+        filter: &AuditFilter,
+        before_id: i64,
+        limit: i64,
+    ) -> Result<Vec<AuditEvent>, AppError>;
+}
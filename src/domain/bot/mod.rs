@@ -0,0 +1,89 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::error::AppError;
+
+mod repo;
+
+pub use repo::BotRepo;
+
+/// api tokens of bots start with it, so they are told apart from signed user tokens
+pub const BOT_TOKEN_PREFIX: &str = "xbot-";
+const MAX_NAME: usize = 64;
+
+/// a long-lived api token of a bot, only its hash is stored
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct BotToken {
+    pub id: i64,
+    pub bot_id: i64,
+    pub name: String,
+    #[serde(skip)]
+    pub token_hash: String,
+    pub created_by: i64,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl BotToken {
+    pub fn new(bot_id: i64, created_by: i64, name: String, token_hash: String) -> Self {
+        Self {
+            id: -1,
+            bot_id,
+            name: name.trim().to_owned(),
+            token_hash,
+            created_by,
+            created_at: Utc::now(),
+            last_used_at: None,
+            revoked_at: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), AppError> {
+        check_name(&self.name)
+    }
+}
+
+/// a secret url posting messages as the bot into one chat
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct IncomingWebhook {
+    pub id: i64,
+    pub ws_id: i64,
+    pub bot_id: i64,
+    pub chat_id: i64,
+    #[serde(skip)]
+    pub token_hash: String,
+    pub created_by: i64,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl IncomingWebhook {
+    pub fn new(ws_id: i64, bot_id: i64, chat_id: i64, created_by: i64, token_hash: String) -> Self {
+        Self {
+            id: -1,
+            ws_id,
+            bot_id,
+            chat_id,
+            token_hash,
+            created_by,
+            created_at: Utc::now(),
+            revoked_at: None,
+        }
+    }
+}
+
+/// names of bots and of their tokens
+pub fn check_name(name: &str) -> Result<(), AppError> {
+    if name.trim().is_empty() {
+        return Err(AppError::InvalidError("name is empty".to_owned()));
+    }
+    if name.chars().count() > MAX_NAME {
+        return Err(AppError::InvalidError(format!(
+            "name is longer than {} characters",
+            MAX_NAME
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,27 @@
+use axum::async_trait;
+
+use crate::{domain::user::User, error::AppError};
+
+use super::{BotToken, IncomingWebhook};
+
+#[async_trait]
+pub trait BotRepo {
+    /// a bot account and its workspace membership
+    async fn create_bot(&self, ws_id: i64, name: &str, email: &str) -> Result<User, AppError>;
+    async fn extract_bots(&self, ws_id: i64) -> Result<Vec<User>, AppError>;
+    async fn find_bot(&self, ws_id: i64, id: i64) -> Result<Option<User>, AppError>;
+    /// deactivate the bot, revoking its tokens and incoming webhooks
+    async fn deactivate_bot(&self, id: i64) -> Result<User, AppError>;
+    async fn save_token(&self, token: &BotToken) -> Result<BotToken, AppError>;
+    async fn extract_tokens(&self, bot_id: i64) -> Result<Vec<BotToken>, AppError>;
+    /// none unless the token is live
+    async fn revoke_token(&self, bot_id: i64, id: i64) -> Result<Option<BotToken>, AppError>;
+    /// the live token with this hash, its last use is recorded to the minute
+    async fn use_token(&self, token_hash: &str) -> Result<Option<BotToken>, AppError>;
+    async fn save_hook(&self, hook: &IncomingWebhook) -> Result<IncomingWebhook, AppError>;
+    async fn extract_hooks(&self, bot_id: i64) -> Result<Vec<IncomingWebhook>, AppError>;
+    /// none unless the hook is live
+    async fn revoke_hook(&self, bot_id: i64, id: i64) -> Result<Option<IncomingWebhook>, AppError>;
+    /// the live hook with this hash
+    async fn find_hook(&self, token_hash: &str) -> Result<Option<IncomingWebhook>, AppError>;
+}
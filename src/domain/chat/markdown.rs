@@ -0,0 +1,189 @@
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+
+/// sanitized markdown tree, text is never markup and links are http(s) or mailto only,
+/// so clients can render it without an html sanitizer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Node {
+    Paragraph {
+        children: Vec<Node>,
+    },
+    Heading {
+        level: u8,
+        children: Vec<Node>,
+    },
+    Quote {
+        children: Vec<Node>,
+    },
+    CodeBlock {
+        lang: Option<String>,
+        code: String,
+    },
+    List {
+        start: Option<u64>,
+        children: Vec<Node>,
+    },
+    Item {
+        children: Vec<Node>,
+    },
+    Rule,
+    Text {
+        text: String,
+    },
+    Code {
+        code: String,
+    },
+    Emphasis {
+        children: Vec<Node>,
+    },
+    Strong {
+        children: Vec<Node>,
+    },
+    Strike {
+        children: Vec<Node>,
+    },
+    Link {
+        url: String,
+        children: Vec<Node>,
+    },
+    Break,
+}
+
+enum Open {
+    Paragraph,
+    Heading(u8),
+    Quote,
+    CodeBlock(Option<String>),
+    List(Option<u64>),
+    Item,
+    Emphasis,
+    Strong,
+    Strike,
+    Link(String),
+    /// unsupported or unsafe, the children are kept as they are
+    Transparent,
+}
+
+pub fn parse(content: &str) -> Vec<Node> {
+    let mut stack: Vec<(Open, Vec<Node>)> = vec![(Open::Transparent, vec![])];
+    for event in Parser::new_ext(content, Options::ENABLE_STRIKETHROUGH) {
+        match event {
+            Event::Start(tag) => stack.push((open(tag), vec![])),
+            Event::End(TagEnd::Image) | Event::End(_) if stack.len() > 1 => {
+                let (open, children) = stack.pop().expect("stack should not be empty");
+                let parent = &mut stack.last_mut().expect("root should exist").1;
+                close(open, children, parent);
+            }
+            Event::End(_) => {}
+            Event::Text(text) | Event::Html(text) | Event::InlineHtml(text) => {
+                push_text(current(&mut stack), &text)
+            }
+            Event::Code(code) => current(&mut stack).push(Node::Code {
+                code: code.into_string(),
+            }),
+            Event::SoftBreak | Event::HardBreak => current(&mut stack).push(Node::Break),
+            Event::Rule => current(&mut stack).push(Node::Rule),
+            _ => {}
+        }
+    }
+    stack.pop().map(|(_, nodes)| nodes).unwrap_or_default()
+}
+
+fn current(stack: &mut [(Open, Vec<Node>)]) -> &mut Vec<Node> {
+    &mut stack.last_mut().expect("root should exist").1
+}
+
+fn push_text(nodes: &mut Vec<Node>, text: &str) {
+    if let Some(Node::Text { text: last }) = nodes.last_mut() {
+        last.push_str(text);
+    } else {
+        nodes.push(Node::Text {
+            text: text.to_owned(),
+        });
+    }
+}
+
+fn open(tag: Tag) -> Open {
+    match tag {
+        Tag::Paragraph => Open::Paragraph,
+        Tag::Heading { level, .. } => Open::Heading(heading_level(level)),
+        Tag::BlockQuote(_) => Open::Quote,
+        Tag::CodeBlock(CodeBlockKind::Fenced(lang)) => Open::CodeBlock(safe_lang(&lang)),
+        Tag::CodeBlock(CodeBlockKind::Indented) => Open::CodeBlock(None),
+        Tag::List(start) => Open::List(start),
+        Tag::Item => Open::Item,
+        Tag::Emphasis => Open::Emphasis,
+        Tag::Strong => Open::Strong,
+        Tag::Strikethrough => Open::Strike,
+        Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. } if is_safe_url(&dest_url) => {
+            Open::Link(dest_url.into_string())
+        }
+        _ => Open::Transparent,
+    }
+}
+
+fn close(open: Open, mut children: Vec<Node>, parent: &mut Vec<Node>) {
+    let node = match open {
+        Open::Paragraph => Node::Paragraph { children },
+        Open::Heading(level) => Node::Heading { level, children },
+        Open::Quote => Node::Quote { children },
+        Open::CodeBlock(lang) => Node::CodeBlock {
+            lang,
+            code: children
+                .into_iter()
+                .filter_map(|node| match node {
+                    Node::Text { text } => Some(text),
+                    _ => None,
+                })
+                .collect(),
+        },
+        Open::List(start) => Node::List { start, children },
+        Open::Item => Node::Item { children },
+        Open::Emphasis => Node::Emphasis { children },
+        Open::Strong => Node::Strong { children },
+        Open::Strike => Node::Strike { children },
+        Open::Link(url) => Node::Link { url, children },
+        Open::Transparent => {
+            for node in children.drain(..) {
+                match node {
+                    Node::Text { text } => push_text(parent, &text),
+                    node => parent.push(node),
+                }
+            }
+            return;
+        }
+    };
+    parent.push(node);
+}
+
+fn heading_level(level: HeadingLevel) -> u8 {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+fn is_safe_url(url: &str) -> bool {
+    let url = url.trim_start().to_ascii_lowercase();
+    ["http://", "https://", "mailto:"]
+        .iter()
+        .any(|scheme| url.starts_with(scheme))
+}
+
+/// fence info like `rust,ignore` keeps only the language
+fn safe_lang(info: &str) -> Option<String> {
+    let lang: String = info
+        .split([' ', ','])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '#' | '-' | '_'))
+        .take(32)
+        .collect();
+    (!lang.is_empty()).then_some(lang)
+}
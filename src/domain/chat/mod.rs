@@ -1,58 +1,241 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::prelude::FromRow;
+use sqlx::{prelude::FromRow, types::Json};
 
+use crate::error::AppError;
+
+use super::link::{LinkPreview, MAX_LINKS};
+
+pub mod markdown;
 mod repo;
 
 pub use repo::ChatRepo;
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "chat_type", rename_all = "snake_case")]
 #[serde(rename_all(serialize = "snake_case", deserialize = "snake_case"))]
 pub enum ChatType {
+    #[default]
     Single,
     Group,
     PrivateChannel,
     PublicChannel,
 }
 
-impl Default for ChatType {
-    fn default() -> Self {
-        ChatType::Single
+impl ChatType {
+    pub fn is_channel(&self) -> bool {
+        matches!(self, ChatType::PrivateChannel | ChatType::PublicChannel)
+    }
+
+    /// direct messages never change type, groups may become channels
+    /// and channels may switch visibility
+    pub fn can_convert_to(&self, to: &ChatType) -> bool {
+        match (self, to) {
+            (from, to) if from == to => true,
+            (ChatType::Single, _) | (_, ChatType::Single) => false,
+            (ChatType::Group, _) => true,
+            (_, to) => to.is_channel(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "chat_status", rename_all = "snake_case")]
+#[serde(rename_all(serialize = "snake_case", deserialize = "snake_case"))]
+pub enum ChatStatus {
+    #[default]
+    Active,
+    /// read-only, still listed
+    Archived,
+    /// hidden, restorable until purged
+    Deleted,
+    /// messages and files are gone, only the row is kept
+    Purged,
+}
+
+impl ChatStatus {
+    pub fn can_transit_to(&self, to: ChatStatus) -> bool {
+        matches!(
+            (self, to),
+            (ChatStatus::Active, ChatStatus::Archived)
+                | (ChatStatus::Archived, ChatStatus::Active)
+                | (
+                    ChatStatus::Active | ChatStatus::Archived,
+                    ChatStatus::Deleted
+                )
+                | (ChatStatus::Deleted, ChatStatus::Active)
+                | (
+                    ChatStatus::Active | ChatStatus::Archived | ChatStatus::Deleted,
+                    ChatStatus::Purged
+                )
+        )
     }
 }
 
-#[derive(Debug, Default, FromRow, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Serialize, Deserialize)]
 pub struct Chat {
     pub id: i64,
     pub ws_id: i64,
     pub name: Option<String>,
     pub chat_type: ChatType,
     pub members: Vec<i64>,
-    pub status: i16,
+    pub topic: Option<String>,
+    pub description: Option<String>,
+    pub status: ChatStatus,
+    pub status_changed_at: DateTime<Utc>,
+    /// none for chats whose creation was not recorded
+    pub created_by: Option<i64>,
     pub created_at: DateTime<Utc>,
 }
 
 impl Chat {
-    pub fn new(ws_id: i64, name: Option<String>, chat_type: ChatType, members: Vec<u64>) -> Self {
+    pub fn new(
+        ws_id: i64,
+        created_by: i64,
+        name: Option<String>,
+        chat_type: ChatType,
+        members: Vec<u64>,
+    ) -> Self {
         Self {
             id: -1,
             ws_id,
             name,
             chat_type,
             members: members.into_iter().map(|x| x as i64).collect(),
-            status: 1,
+            topic: None,
+            description: None,
+            status: ChatStatus::Active,
+            status_changed_at: Utc::now(),
+            created_by: Some(created_by),
             created_at: Utc::now(),
         }
     }
+
+    /// members can read active and archived chats
+    pub fn is_readable(&self) -> bool {
+        matches!(self.status, ChatStatus::Active | ChatStatus::Archived)
+    }
+
+    /// normalize name and members and check the rules of the chat type,
+    /// unique channel names need the repo and are checked by the service
+    pub fn validate(&mut self) -> Result<(), AppError> {
+        self.name = self
+            .name
+            .take()
+            .map(|name| name.trim().to_owned())
+            .filter(|name| !name.is_empty());
+        self.members.sort();
+        self.members.dedup();
+        match self.chat_type {
+            ChatType::Single => {
+                if self.members.len() != 2 {
+                    return Err(AppError::InvalidError(
+                        "single chat should have exactly two members".to_owned(),
+                    ));
+                }
+                self.name = None;
+            }
+            ChatType::Group | ChatType::PrivateChannel | ChatType::PublicChannel => {
+                if self.name.is_none() {
+                    return Err(AppError::InvalidError("chat name is required".to_owned()));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "notif_level", rename_all = "snake_case")]
+#[serde(rename_all(serialize = "snake_case", deserialize = "snake_case"))]
+pub enum NotifLevel {
+    #[default]
+    All,
+    Mentions,
+    None,
+}
+
+/// settings of a member for one chat
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct ChatPrefs {
+    pub muted_until: Option<DateTime<Utc>>,
+    pub favorite: bool,
+    pub hidden: bool,
+    pub notif_level: NotifLevel,
 }
 
-#[derive(Debug, Default, FromRow, PartialEq, Serialize, Deserialize)]
+/// a chat as listed to one of its members
+#[derive(Debug, Clone, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct UserChat {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub chat: Chat,
+    #[sqlx(flatten)]
+    pub prefs: ChatPrefs,
+}
+
+/// a public channel as listed to workspace members who may not have joined it
+#[derive(Debug, Clone, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct ChannelSummary {
+    pub id: i64,
+    pub ws_id: i64,
+    pub name: Option<String>,
+    pub topic: Option<String>,
+    pub member_count: i64,
+    pub joined: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "mention_broadcast", rename_all = "snake_case")]
+#[serde(rename_all(serialize = "snake_case", deserialize = "snake_case"))]
+pub enum MentionBroadcast {
+    /// every member
+    Channel,
+    /// members online when the message is sent
+    Here,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "content_type", rename_all = "snake_case")]
+#[serde(rename_all(serialize = "snake_case", deserialize = "snake_case"))]
+pub enum ContentType {
+    #[default]
+    Plain,
+    Markdown,
+    /// the question of a poll, created with the poll
+    Poll,
+}
+
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Serialize, Deserialize)]
 pub struct Msg {
     pub id: i64,
     pub chat_id: i64,
     pub sender_id: i64,
     pub content: String,
+    #[serde(default)]
+    pub content_type: ContentType,
+    /// sanitized tree of markdown content
+    #[serde(default)]
+    pub rich: Option<Json<Vec<markdown::Node>>>,
+    /// filled in by the unfurl worker after the message is sent
+    #[serde(default)]
+    pub previews: Json<Vec<LinkPreview>>,
+    /// set by the client to make sending idempotent, unique per sender and chat
+    #[serde(default)]
+    pub client_id: Option<String>,
+    #[serde(default)]
+    pub forwarded: Option<Json<ForwardRef>>,
+    /// a message of the same chat quoted inline
+    #[serde(default)]
+    pub quote_id: Option<i64>,
+    /// members mentioned by handle
+    #[serde(default)]
+    pub mentions: Vec<i64>,
+    pub mention_broadcast: Option<MentionBroadcast>,
+    /// sent by a bot, set by the database from the sender
+    #[serde(default)]
+    pub bot: bool,
     pub created_at: DateTime<Utc>,
 }
 
@@ -63,7 +246,183 @@ impl Msg {
             chat_id,
             sender_id,
             content,
+            content_type: ContentType::Plain,
+            rich: None,
+            previews: Json(vec![]),
+            client_id: None,
+            forwarded: None,
+            quote_id: None,
+            mentions: vec![],
+            mention_broadcast: None,
+            bot: false,
             created_at: Utc::now(),
         }
     }
+
+    /// a copy of this message to send to another chat, attachments are linked
+    /// in the content so the copy shares the files, mentions are not resolved again
+    pub fn forward(&self, chat_id: i64, sender_id: i64) -> Self {
+        let forwarded = self.forwarded.clone().unwrap_or(Json(ForwardRef {
+            msg_id: self.id,
+            chat_id: self.chat_id,
+            sender_id: self.sender_id,
+            created_at: self.created_at,
+        }));
+        let mut msg = Self::new(chat_id, sender_id, self.content.clone());
+        // the votes stay with the original poll, the copy shows the question
+        msg.content_type = match self.content_type {
+            ContentType::Poll => ContentType::Plain,
+            content_type => content_type,
+        };
+        msg.rich = self.rich.clone();
+        msg.previews = self.previews.clone();
+        msg.forwarded = Some(forwarded);
+        msg
+    }
+
+    /// parse markdown content, the tree may be missing from notifications of large messages
+    pub fn render(&mut self) {
+        if self.content_type == ContentType::Markdown && self.rich.is_none() {
+            self.rich = Some(Json(markdown::parse(&self.content)));
+        }
+    }
+
+    /// distinct http(s) urls in the content, in order of appearance
+    pub fn extract_links(&self) -> Vec<String> {
+        let mut links: Vec<String> = vec![];
+        for word in self
+            .content
+            .split(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"' | '(' | ')'))
+        {
+            let lower = word.to_ascii_lowercase();
+            let start = match (lower.find("https://"), lower.find("http://")) {
+                (Some(a), Some(b)) => a.min(b),
+                (a, b) => match a.or(b) {
+                    Some(start) => start,
+                    None => continue,
+                },
+            };
+            // punctuation after a link ends the sentence
+            let link =
+                word[start..].trim_end_matches(['.', ',', ';', ':', '!', '?', '*', '_', '`', ']']);
+            if link.len() > "https://".len()
+                && link.len() <= 2048
+                && !links.iter().any(|v| v == link)
+            {
+                links.push(link.to_owned());
+            }
+            if links.len() == MAX_LINKS {
+                break;
+            }
+        }
+        links
+    }
+
+    /// lowercased handles written as `@handle`, `@channel` and `@here` excluded,
+    /// along with the broadcast mention if any
+    pub fn parse_mentions(&self) -> (Vec<String>, Option<MentionBroadcast>) {
+        let mut handles: Vec<String> = vec![];
+        let mut broadcast = None;
+        let words = self
+            .content
+            .split(|c: char| !(c.is_alphanumeric() || matches!(c, '@' | '.' | '_' | '-')));
+        for word in words {
+            // emails and other text with an @ inside are not mentions
+            let handle = match word.strip_prefix('@') {
+                Some(handle) if !handle.contains('@') => handle,
+                _ => continue,
+            };
+            // trailing punctuation ends the sentence, not the handle
+            let handle = handle.trim_end_matches(['.', '-']).to_lowercase();
+            match handle.as_str() {
+                "" => {}
+                "channel" => broadcast = Some(MentionBroadcast::Channel),
+                "here" => broadcast = broadcast.or(Some(MentionBroadcast::Here)),
+                _ if !handles.contains(&handle) => handles.push(handle),
+                _ => {}
+            }
+        }
+        (handles, broadcast)
+    }
+}
+
+/// the original of a forwarded message, a forward of a forward refers to the first one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForwardRef {
+    pub msg_id: i64,
+    pub chat_id: i64,
+    pub sender_id: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct Pin {
+    pub chat_id: i64,
+    pub msg_id: i64,
+    pub pinned_by: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Pin {
+    pub fn new(chat_id: i64, msg_id: i64, pinned_by: i64) -> Self {
+        Self {
+            chat_id,
+            msg_id,
+            pinned_by,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// a pinned message together with who pinned it
+#[derive(Debug, Clone, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct PinnedMsg {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub message: Msg,
+    pub pinned_by: i64,
+    pub pinned_at: DateTime<Utc>,
+}
+
+/// a link saved to the header of a chat
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub id: i64,
+    pub chat_id: i64,
+    pub title: String,
+    pub url: String,
+    pub created_by: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Bookmark {
+    pub fn new(chat_id: i64, title: String, url: String, created_by: i64) -> Self {
+        Self {
+            id: -1,
+            chat_id,
+            title,
+            url,
+            created_by,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn validate(&mut self) -> Result<(), AppError> {
+        self.title = self.title.trim().to_owned();
+        self.url = self.url.trim().to_owned();
+        if self.title.is_empty() || self.title.chars().count() > 128 {
+            return Err(AppError::InvalidError(
+                "bookmark title should be 1 to 128 characters".to_owned(),
+            ));
+        }
+        if !(self.url.starts_with("http://") || self.url.starts_with("https://"))
+            || self.url.len() > 2048
+        {
+            return Err(AppError::InvalidError(format!(
+                "invalid bookmark url {}",
+                self.url
+            )));
+        }
+        Ok(())
+    }
 }
@@ -1,21 +1,132 @@
 use axum::async_trait;
+use chrono::{DateTime, Utc};
 
 use crate::error::AppError;
 
-use super::{Chat, Msg};
+use super::{Bookmark, ChannelSummary, Chat, ChatPrefs, ChatStatus, Msg, Pin, PinnedMsg, UserChat};
 
 #[async_trait]
 pub trait ChatRepo {
-    async fn delete_by_id(&self, chat_id: i64) -> Result<Chat, AppError>;
+    async fn update_status(&self, chat_id: i64, status: ChatStatus) -> Result<Chat, AppError>;
+    async fn extract_expired(
+        &self,
+        status: ChatStatus,
+        before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<Chat>, AppError>;
+    async fn purge(&self, chat_id: i64) -> Result<Option<Chat>, AppError>;
     async fn extract_by_id(&self, chat_id: i64) -> Result<Option<Chat>, AppError>;
-    async fn store_msg(&self, msg: &Msg) -> Result<Msg, AppError>;
+    async fn is_member(&self, chat_id: i64, ws_id: i64, user_id: i64) -> Result<bool, AppError>;
+    async fn is_public_channel(&self, chat_id: i64, ws_id: i64) -> Result<bool, AppError>;
+    async fn extract_public_channels(
+        &self,
+        ws_id: i64,
+        user_id: i64,
+        query: Option<&str>,
+    ) -> Result<Vec<ChannelSummary>, AppError>;
+    /// active members of the workspace with these handles, along with their handle
+    async fn resolve_handles(
+        &self,
+        ws_id: i64,
+        handles: &[String],
+    ) -> Result<Vec<(i64, String)>, AppError>;
+    async fn resolve_mentions(
+        &self,
+        chat_id: i64,
+        handles: &[String],
+    ) -> Result<Vec<i64>, AppError>;
+    async fn extract_mentions(
+        &self,
+        user_id: i64,
+        ws_id: i64,
+        last_id: i64,
+        limit: i64,
+    ) -> Result<Vec<Msg>, AppError>;
+    /// links are queued for unfurling along with the message,
+    /// a message with the client id of a stored one returns the stored one
+    /// store the message with the ids of its attached files
+    async fn store_msg(&self, msg: &Msg, links: &[String], files: &[i64]) -> Result<Msg, AppError>;
+    /// the url and id of the files of the owner among the urls
+    async fn resolve_files(
+        &self,
+        ws_id: i64,
+        owner_id: i64,
+        urls: &[String],
+    ) -> Result<Vec<(String, i64)>, AppError>;
+    async fn extract_msg_files(&self, msg_id: i64) -> Result<Vec<i64>, AppError>;
+    /// the message the sender already sent with the client message id
+    async fn extract_by_client_id(
+        &self,
+        chat_id: i64,
+        sender_id: i64,
+        client_id: &str,
+    ) -> Result<Option<Msg>, AppError>;
     async fn save(&self, input: &Chat) -> Result<Chat, AppError>;
-    async fn is_members_exist(&self, members: Vec<i64>) -> Result<bool, AppError>;
-    async fn extract_all_chat(&self, user_id: i64, ws_id: i64) -> Result<Vec<Chat>, AppError>;
+    async fn find_or_create_single(&self, input: &Chat) -> Result<Chat, AppError>;
+    async fn is_channel_name_taken(
+        &self,
+        ws_id: i64,
+        name: &str,
+        except_id: i64,
+    ) -> Result<bool, AppError>;
+    async fn add_members(&self, chat_id: i64, members: Vec<i64>) -> Result<Chat, AppError>;
+    async fn remove_member(&self, chat_id: i64, user_id: i64) -> Result<Chat, AppError>;
+    /// end of the moderator mute of the user in the chat, none once it is over
+    async fn extract_muted_until(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+    ) -> Result<Option<DateTime<Utc>>, AppError>;
+    /// seconds between two messages of a member, 0 when slow mode is off
+    async fn extract_slow_mode(&self, chat_id: i64) -> Result<Option<i32>, AppError>;
+    /// those of the users banned from the chat
+    async fn extract_banned(&self, chat_id: i64, user_ids: &[i64]) -> Result<Vec<i64>, AppError>;
+    async fn is_members_exist(&self, ws_id: i64, members: Vec<i64>) -> Result<bool, AppError>;
+    async fn extract_all_chat(
+        &self,
+        user_id: i64,
+        ws_id: i64,
+        statuses: &[ChatStatus],
+    ) -> Result<Vec<UserChat>, AppError>;
+    async fn extract_prefs(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+    ) -> Result<Option<ChatPrefs>, AppError>;
+    async fn update_prefs(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        prefs: &ChatPrefs,
+    ) -> Result<ChatPrefs, AppError>;
+    async fn extract_user_chats(&self, user_id: i64) -> Result<Vec<Chat>, AppError>;
+    async fn extract_user_messages(&self, user_id: i64) -> Result<Vec<Msg>, AppError>;
+    async fn erase_user_messages(&self, user_id: i64) -> Result<u64, AppError>;
+    async fn update_header(
+        &self,
+        chat_id: i64,
+        topic: Option<String>,
+        description: Option<String>,
+    ) -> Result<Chat, AppError>;
+    async fn extract_msg(&self, chat_id: i64, msg_id: i64) -> Result<Option<Msg>, AppError>;
+    async fn save_pin(&self, pin: &Pin) -> Result<Pin, AppError>;
+    async fn delete_pin(&self, chat_id: i64, msg_id: i64) -> Result<Option<Pin>, AppError>;
+    async fn extract_pins(&self, chat_id: i64) -> Result<Vec<PinnedMsg>, AppError>;
+    async fn save_bookmark(&self, bookmark: &Bookmark) -> Result<Bookmark, AppError>;
+    async fn delete_bookmark(&self, chat_id: i64, id: i64) -> Result<Option<Bookmark>, AppError>;
+    async fn extract_bookmarks(&self, chat_id: i64) -> Result<Vec<Bookmark>, AppError>;
+    /// older than `before_id`, newest first
     async fn extract_messages(
         &self,
         chat_id: i64,
-        last_id: i64,
+        before_id: i64,
+        limit: i64,
+    ) -> Result<Vec<Msg>, AppError>;
+    /// newer than `after_id`, oldest first
+    async fn extract_messages_after(
+        &self,
+        chat_id: i64,
+        after_id: i64,
         limit: i64,
     ) -> Result<Vec<Msg>, AppError>;
 }
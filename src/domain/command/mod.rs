@@ -0,0 +1,204 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, types::Json};
+
+use crate::error::AppError;
+
+mod repo;
+
+pub use repo::CommandRepo;
+
+/// commands answered by the server, with their usage, workspace commands can not shadow them
+pub const BUILTIN_COMMANDS: [(&str, &str); 6] = [
+    ("help", "/help"),
+    ("topic", "/topic [new topic]"),
+    ("invite", "/invite @handle..."),
+    ("leave", "/leave"),
+    ("remind", "/remind [in] <30m|2h|1d|rfc3339 time> <note>"),
+    (
+        "mute",
+        "/mute [duration|off] or /mute @handle <duration> [reason]",
+    ),
+];
+
+const MAX_NAME: usize = 32;
+const MAX_DESCRIPTION: usize = 256;
+
+/// a message starting with `/name`, the rest of the message is its arguments
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlashCommand {
+    pub name: String,
+    pub args: String,
+}
+
+impl SlashCommand {
+    /// none for text that merely starts with a slash, such as a path
+    pub fn parse(content: &str) -> Option<Self> {
+        let rest = content.trim_start().strip_prefix('/')?;
+        let (name, args) = match rest.split_once(char::is_whitespace) {
+            Some((name, args)) => (name, args.trim()),
+            None => (rest, ""),
+        };
+        if !is_valid_name(&name.to_lowercase()) {
+            return None;
+        }
+        Some(Self {
+            name: name.to_lowercase(),
+            args: args.to_owned(),
+        })
+    }
+}
+
+/// a workspace command, calls to it are posted to its url
+#[derive(Debug, Clone, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct Command {
+    pub id: i64,
+    pub ws_id: i64,
+    pub name: String,
+    pub url: String,
+    pub description: String,
+    /// only shown when the command is created
+    #[serde(skip)]
+    pub secret: String,
+    pub created_by: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Command {
+    pub fn new(
+        ws_id: i64,
+        created_by: i64,
+        name: String,
+        url: String,
+        description: String,
+        secret: String,
+    ) -> Self {
+        Self {
+            id: -1,
+            ws_id,
+            name: name.trim().trim_start_matches('/').to_lowercase(),
+            url: url.trim().to_owned(),
+            description: description.trim().to_owned(),
+            secret,
+            created_by,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), AppError> {
+        if !is_valid_name(&self.name) {
+            return Err(AppError::InvalidError(format!(
+                "command name should be at most {} lowercase letters, digits, _ or -",
+                MAX_NAME
+            )));
+        }
+        if is_builtin(&self.name) {
+            return Err(AppError::InvalidError(format!(
+                "/{} is a built-in command",
+                self.name
+            )));
+        }
+        if self.url.len() > 2048 {
+            return Err(AppError::InvalidError("url is too long".to_owned()));
+        }
+        if self.description.chars().count() > MAX_DESCRIPTION {
+            return Err(AppError::InvalidError(format!(
+                "description should be at most {} characters",
+                MAX_DESCRIPTION
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// a run of a command sent with a client message id
+#[derive(Debug, Clone, FromRow, PartialEq)]
+pub struct CommandRun {
+    pub chat_id: i64,
+    pub user_id: i64,
+    pub client_id: String,
+    /// none while the command runs
+    pub output: Option<Json<serde_json::Value>>,
+    pub started_at: DateTime<Utc>,
+}
+
+/// who sees the reply of a command
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all(serialize = "snake_case", deserialize = "snake_case"))]
+pub enum ResponseType {
+    /// only the member who ran the command
+    #[default]
+    Ephemeral,
+    /// posted to the chat as the member who ran the command
+    InChannel,
+}
+
+/// what a command endpoint answers, an empty text sends nothing back
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CommandReply {
+    #[serde(default)]
+    pub response_type: ResponseType,
+    #[serde(default)]
+    pub text: String,
+}
+
+impl CommandReply {
+    pub fn ephemeral(text: impl Into<String>) -> Self {
+        Self {
+            response_type: ResponseType::Ephemeral,
+            text: text.into(),
+        }
+    }
+}
+
+/// a reply only the member who ran the command sees, it is not stored
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EphemeralMsg {
+    pub chat_id: i64,
+    pub user_id: i64,
+    pub command: String,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl EphemeralMsg {
+    pub fn new(chat_id: i64, user_id: i64, command: String, text: String) -> Self {
+        Self {
+            chat_id,
+            user_id,
+            command,
+            text,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// `30m`, `2h`, `1d` or `1w`
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim().to_lowercase();
+    let unit = value.chars().last()?;
+    let count: i64 = value[..value.len() - unit.len_utf8()].parse().ok()?;
+    if count <= 0 {
+        return None;
+    }
+    match unit {
+        'm' => Duration::try_minutes(count),
+        'h' => Duration::try_hours(count),
+        'd' => Duration::try_days(count),
+        'w' => Duration::try_weeks(count),
+        _ => None,
+    }
+}
+
+fn is_builtin(name: &str) -> bool {
+    BUILTIN_COMMANDS.iter().any(|(v, _)| *v == name)
+}
+
+/// a letter then letters, digits, `_` or `-`
+fn is_valid_name(name: &str) -> bool {
+    name.len() <= MAX_NAME
+        && name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '-'))
+}
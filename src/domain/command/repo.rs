@@ -0,0 +1,38 @@
+use axum::async_trait;
+
+use crate::error::AppError;
+
+use super::{Command, CommandRun, EphemeralMsg};
+
+#[async_trait]
+pub trait CommandRepo {
+    async fn save(&self, command: &Command) -> Result<Command, AppError>;
+    async fn extract_by_ws(&self, ws_id: i64) -> Result<Vec<Command>, AppError>;
+    async fn find_by_name(&self, ws_id: i64, name: &str) -> Result<Option<Command>, AppError>;
+    async fn delete(&self, ws_id: i64, id: i64) -> Result<Option<Command>, AppError>;
+    /// hand the reply to whichever replica holds the member's connection
+    async fn notify_ephemeral(&self, msg: &EphemeralMsg) -> Result<(), AppError>;
+    /// claim the run of the client message id, the earlier run is returned when it is
+    /// done or still within its lease of `lease_secs`, none when this run is claimed
+    async fn claim_run(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        client_id: &str,
+        lease_secs: i64,
+    ) -> Result<Option<CommandRun>, AppError>;
+    async fn finish_run(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        client_id: &str,
+        output: &serde_json::Value,
+    ) -> Result<(), AppError>;
+    /// forget a failed run so a retry runs the command again
+    async fn release_run(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        client_id: &str,
+    ) -> Result<(), AppError>;
+}
@@ -0,0 +1,31 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+mod repo;
+
+pub use repo::FileRepo;
+
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct FileMeta {
+    pub id: i64,
+    pub ws_id: i64,
+    pub owner_id: i64,
+    pub url: String,
+    /// relative to the upload base dir
+    pub path: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl FileMeta {
+    pub fn new(ws_id: i64, owner_id: i64, url: String, path: String) -> Self {
+        Self {
+            id: -1,
+            ws_id,
+            owner_id,
+            url,
+            path,
+            created_at: Utc::now(),
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use axum::async_trait;
+
+use crate::error::AppError;
+
+use super::FileMeta;
+
+#[async_trait]
+pub trait FileRepo {
+    async fn save(&self, file: &FileMeta) -> Result<FileMeta, AppError>;
+    async fn extract_by_owner(&self, owner_id: i64) -> Result<Vec<FileMeta>, AppError>;
+    async fn delete_by_owner(&self, owner_id: i64) -> Result<Vec<FileMeta>, AppError>;
+    async fn delete_by_chat(&self, chat_id: i64, ws_id: i64) -> Result<Vec<FileMeta>, AppError>;
+    async fn delete_unreferenced(
+        &self,
+        ws_id: i64,
+        msg_ids: &[i64],
+    ) -> Result<Vec<FileMeta>, AppError>;
+    async fn is_path_used(&self, path: &str) -> Result<bool, AppError>;
+}
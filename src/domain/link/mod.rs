@@ -0,0 +1,184 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+mod repo;
+
+pub use repo::LinkRepo;
+
+/// links unfurled per message
+pub const MAX_LINKS: usize = 5;
+
+/// title, description and image of a url, `ok` is false when the page could not be fetched
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct LinkPreview {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub ok: bool,
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl LinkPreview {
+    pub fn failed(url: &str) -> Self {
+        Self {
+            url: url.to_owned(),
+            fetched_at: Utc::now(),
+            ..Default::default()
+        }
+    }
+
+    /// open graph tags first, then the description meta and the title of the page
+    pub fn from_html(url: &str, html: &str) -> Self {
+        let mut title = None;
+        let mut og_title = None;
+        let mut description = None;
+        let mut og_description = None;
+        let mut image = None;
+
+        let lower = html.to_ascii_lowercase();
+        let mut pos = 0;
+        while let Some(start) = lower[pos..].find('<').map(|i| i + pos) {
+            let end = match lower[start..].find('>') {
+                Some(i) => start + i,
+                None => break,
+            };
+            let tag = &html[start..=end];
+            let tag_lower = &lower[start..=end];
+            pos = end + 1;
+            if tag_lower.starts_with("<meta") {
+                let key = attr(tag, "property").or_else(|| attr(tag, "name"));
+                let content = attr(tag, "content").filter(|v| !v.is_empty());
+                match (key.map(|v| v.to_ascii_lowercase()).as_deref(), content) {
+                    (Some("og:title"), Some(v)) => og_title = og_title.or(Some(v)),
+                    (Some("og:description"), Some(v)) => {
+                        og_description = og_description.or(Some(v))
+                    }
+                    (Some("description"), Some(v)) => description = description.or(Some(v)),
+                    (Some("og:image"), Some(v)) => image = image.or(Some(v)),
+                    _ => {}
+                }
+            } else if title.is_none()
+                && (tag_lower.starts_with("<title>") || tag_lower.starts_with("<title "))
+            {
+                if let Some(close) = lower[pos..].find("</title") {
+                    title = Some(decode_entities(html[pos..pos + close].trim()));
+                }
+            } else if tag_lower.starts_with("</head") || tag_lower.starts_with("<body") {
+                break;
+            }
+        }
+
+        Self {
+            url: url.to_owned(),
+            title: og_title.or(title).and_then(|v| clip(&v, 256)),
+            description: og_description.or(description).and_then(|v| clip(&v, 1024)),
+            image_url: image.and_then(|v| absolute_url(url, &v)),
+            ok: true,
+            fetched_at: Utc::now(),
+        }
+    }
+}
+
+/// a message whose links wait for their previews
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct UnfurlJob {
+    pub msg_id: i64,
+    pub urls: Vec<String>,
+    pub attempts: i32,
+}
+
+/// value of a `name="value"`, `name='value'` or `name=value` attribute
+fn attr(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(i) = lower[from..].find(name).map(|i| i + from) {
+        from = i + name.len();
+        let preceded = lower[..i].ends_with(|c: char| c.is_ascii_whitespace());
+        let rest = lower[from..].trim_start();
+        if !preceded || !rest.starts_with('=') {
+            continue;
+        }
+        let value_at = tag.len() - rest.len() + 1;
+        let value = tag[value_at..].trim_start();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or_default(),
+            _ => value
+                .split(|c: char| c.is_ascii_whitespace() || c == '>')
+                .next()
+                .unwrap_or_default(),
+        };
+        return Some(decode_entities(value.trim()));
+    }
+    None
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let decoded = rest.find(';').filter(|end| *end <= 10).and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ => entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse()))
+                    .and_then(|v| v.ok())
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// collapse whitespace and cut to `max` characters
+fn clip(text: &str, max: usize) -> Option<String> {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.chars().count() {
+        0 => None,
+        n if n > max => Some(text.chars().take(max - 1).chain(['…']).collect()),
+        _ => Some(text),
+    }
+}
+
+/// resolve an image url against the page, only http(s) urls are kept
+fn absolute_url(base: &str, url: &str) -> Option<String> {
+    let url = if url.starts_with("//") {
+        format!("https:{}", url)
+    } else if url.starts_with('/') {
+        let scheme_end = base.find("://")? + 3;
+        let host_end = base[scheme_end..]
+            .find(['/', '?', '#'])
+            .map(|i| i + scheme_end)
+            .unwrap_or(base.len());
+        format!("{}{}", &base[..host_end], url)
+    } else {
+        url.to_owned()
+    };
+    let lower = url.to_ascii_lowercase();
+    ((lower.starts_with("http://") || lower.starts_with("https://")) && url.len() <= 2048)
+        .then_some(url)
+}
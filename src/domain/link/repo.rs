@@ -0,0 +1,21 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::error::AppError;
+
+use super::{LinkPreview, UnfurlJob};
+
+#[async_trait]
+pub trait LinkRepo {
+    /// lease up to `limit` pending jobs, jobs leased by another worker are skipped
+    async fn claim_jobs(&self, limit: i64, lease_secs: i64) -> Result<Vec<UnfurlJob>, AppError>;
+    async fn extract_previews(
+        &self,
+        urls: &[String],
+        fetched_after: DateTime<Utc>,
+    ) -> Result<Vec<LinkPreview>, AppError>;
+    async fn save_preview(&self, preview: &LinkPreview) -> Result<(), AppError>;
+    /// set the previews of the message and drop its job
+    async fn complete_job(&self, msg_id: i64, previews: &[LinkPreview]) -> Result<(), AppError>;
+    async fn delete_job(&self, msg_id: i64) -> Result<(), AppError>;
+}
@@ -1,2 +1,14 @@
+pub mod alert;
+pub mod audit;
+pub mod bot;
 pub mod chat;
+pub mod command;
+pub mod file;
+pub mod link;
+pub mod moderation;
+pub mod poll;
+pub mod rate_limit;
+pub mod retention;
+pub mod schedule;
 pub mod user;
+pub mod webhook;
@@ -0,0 +1,102 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::error::AppError;
+
+use super::chat::Msg;
+
+mod repo;
+
+pub use repo::ModerationRepo;
+
+const MAX_REASON: usize = 500;
+/// mutes are always time-limited
+pub const MAX_MUTE_DAYS: i64 = 30;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "report_status", rename_all = "snake_case")]
+#[serde(rename_all(serialize = "snake_case", deserialize = "snake_case"))]
+pub enum ReportStatus {
+    #[default]
+    Open,
+    /// the moderator acted on the report
+    Resolved,
+    Dismissed,
+}
+
+#[derive(Debug, Clone, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub id: i64,
+    pub ws_id: i64,
+    pub chat_id: i64,
+    /// gone once the message is deleted
+    pub msg_id: Option<i64>,
+    pub sender_id: i64,
+    /// the message when it was reported
+    pub content: String,
+    pub reporter_id: i64,
+    pub reason: String,
+    pub status: ReportStatus,
+    pub resolved_by: Option<i64>,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+impl Report {
+    pub fn new(ws_id: i64, msg: &Msg, reporter_id: i64, reason: String) -> Self {
+        Self {
+            id: -1,
+            ws_id,
+            chat_id: msg.chat_id,
+            msg_id: Some(msg.id),
+            sender_id: msg.sender_id,
+            content: msg.content.clone(),
+            reporter_id,
+            reason: reason.trim().to_owned(),
+            status: ReportStatus::Open,
+            resolved_by: None,
+            note: None,
+            created_at: Utc::now(),
+            resolved_at: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), AppError> {
+        if self.reason.is_empty() {
+            return Err(AppError::InvalidError("reason is empty".to_owned()));
+        }
+        check_reason(&self.reason)
+    }
+}
+
+pub fn check_reason(reason: &str) -> Result<(), AppError> {
+    if reason.chars().count() > MAX_REASON {
+        return Err(AppError::InvalidError(format!(
+            "reason is longer than {} characters",
+            MAX_REASON
+        )));
+    }
+    Ok(())
+}
+
+/// a member silenced by a moderator, not to be mixed up with muting alerts in the chat prefs
+#[derive(Debug, Clone, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct ChatMute {
+    pub chat_id: i64,
+    pub user_id: i64,
+    pub muted_until: DateTime<Utc>,
+    pub muted_by: i64,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct ChatBan {
+    pub chat_id: i64,
+    pub user_id: i64,
+    pub banned_by: i64,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
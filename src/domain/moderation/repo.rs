@@ -0,0 +1,44 @@
+use axum::async_trait;
+
+use crate::{domain::chat::Msg, error::AppError};
+
+use super::{ChatBan, ChatMute, Report, ReportStatus};
+
+#[async_trait]
+pub trait ModerationRepo {
+    /// none if the reporter has an open report on the message
+    async fn save_report(&self, report: &Report) -> Result<Option<Report>, AppError>;
+    async fn extract_reports(
+        &self,
+        ws_id: i64,
+        status: ReportStatus,
+        last_id: i64,
+        limit: i64,
+    ) -> Result<Vec<Report>, AppError>;
+    /// none unless the report is open
+    async fn resolve_report(
+        &self,
+        ws_id: i64,
+        id: i64,
+        status: ReportStatus,
+        resolved_by: i64,
+        note: Option<&str>,
+    ) -> Result<Option<Report>, AppError>;
+    /// delete the message, resolving its open reports, members are notified
+    async fn delete_msg(
+        &self,
+        chat_id: i64,
+        msg_id: i64,
+        moderator_id: i64,
+        note: Option<&str>,
+    ) -> Result<Option<Msg>, AppError>;
+    async fn save_mute(&self, mute: &ChatMute) -> Result<ChatMute, AppError>;
+    async fn delete_mute(&self, chat_id: i64, user_id: i64) -> Result<Option<ChatMute>, AppError>;
+    /// mutes not over yet
+    async fn extract_mutes(&self, chat_id: i64) -> Result<Vec<ChatMute>, AppError>;
+    /// the user leaves the chat as well
+    async fn save_ban(&self, ban: &ChatBan) -> Result<ChatBan, AppError>;
+    async fn delete_ban(&self, chat_id: i64, user_id: i64) -> Result<Option<ChatBan>, AppError>;
+    async fn update_slow_mode(&self, chat_id: i64, secs: i32) -> Result<Option<i32>, AppError>;
+    async fn extract_bans(&self, chat_id: i64) -> Result<Vec<ChatBan>, AppError>;
+}
@@ -0,0 +1,113 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::error::AppError;
+
+mod repo;
+
+pub use repo::PollRepo;
+
+const MAX_OPTIONS: usize = 10;
+
+/// a poll is keyed by the message showing it
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct Poll {
+    pub msg_id: i64,
+    pub chat_id: i64,
+    pub question: String,
+    pub options: Vec<String>,
+    pub multiple: bool,
+    pub anonymous: bool,
+    pub closes_at: Option<DateTime<Utc>>,
+    pub closed: bool,
+    pub created_by: i64,
+    /// votes per option
+    pub counts: Vec<i32>,
+    pub voters: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Poll {
+    pub fn new(chat_id: i64, question: String, options: Vec<String>, created_by: i64) -> Self {
+        Self {
+            msg_id: -1,
+            chat_id,
+            question,
+            counts: vec![0; options.len()],
+            options,
+            multiple: false,
+            anonymous: false,
+            closes_at: None,
+            closed: false,
+            created_by,
+            voters: 0,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        !self.closed && self.closes_at.is_none_or(|at| at > Utc::now())
+    }
+
+    pub fn validate(&mut self) -> Result<(), AppError> {
+        self.question = self.question.trim().to_owned();
+        if self.question.is_empty() || self.question.chars().count() > 300 {
+            return Err(AppError::InvalidError(
+                "poll question should be 1 to 300 characters".to_owned(),
+            ));
+        }
+        for option in self.options.iter_mut() {
+            *option = option.trim().to_owned();
+            if option.is_empty() || option.chars().count() > 100 {
+                return Err(AppError::InvalidError(
+                    "poll option should be 1 to 100 characters".to_owned(),
+                ));
+            }
+        }
+        let mut distinct = self.options.clone();
+        distinct.sort();
+        distinct.dedup();
+        if self.options.len() < 2
+            || self.options.len() > MAX_OPTIONS
+            || distinct.len() != self.options.len()
+        {
+            return Err(AppError::InvalidError(format!(
+                "poll should have 2 to {} distinct options",
+                MAX_OPTIONS
+            )));
+        }
+        if self.closes_at.is_some_and(|at| at <= Utc::now()) {
+            return Err(AppError::InvalidError(
+                "poll should close in the future".to_owned(),
+            ));
+        }
+        self.counts = vec![0; self.options.len()];
+        Ok(())
+    }
+
+    /// sorted and deduplicated choice, empty to retract
+    pub fn check_choice(&self, options: &mut Vec<i16>) -> Result<(), AppError> {
+        options.sort();
+        options.dedup();
+        if options
+            .iter()
+            .any(|v| *v < 0 || *v as usize >= self.options.len())
+        {
+            return Err(AppError::InvalidError("no such poll option".to_owned()));
+        }
+        if !self.multiple && options.len() > 1 {
+            return Err(AppError::InvalidError(
+                "poll allows a single choice".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// who voted for an option, only for polls which are not anonymous
+#[derive(Debug, Clone, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct PollVote {
+    pub user_id: i64,
+    pub option: i16,
+}
@@ -0,0 +1,22 @@
+use axum::async_trait;
+
+use crate::{domain::chat::Msg, error::AppError};
+
+use super::{Poll, PollVote};
+
+#[async_trait]
+pub trait PollRepo {
+    /// store the poll message and the poll together
+    async fn save(&self, msg: &Msg, poll: &Poll) -> Result<(Msg, Poll), AppError>;
+    async fn extract_by_msg(&self, chat_id: i64, msg_id: i64) -> Result<Option<Poll>, AppError>;
+    async fn extract_votes(&self, msg_id: i64) -> Result<Vec<PollVote>, AppError>;
+    /// replace the votes of the user and recount while holding the poll,
+    /// None when the poll closed meanwhile
+    async fn vote(
+        &self,
+        msg_id: i64,
+        user_id: i64,
+        options: &[i16],
+    ) -> Result<Option<Poll>, AppError>;
+    async fn close(&self, msg_id: i64) -> Result<Option<Poll>, AppError>;
+}
@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+
+mod repo;
+
+pub use repo::RateLimitRepo;
+
+/// longest slow mode interval, 6 hours
+pub const MAX_SLOW_MODE_SECS: u32 = 6 * 60 * 60;
+
+/// a bucket of `burst` tokens, refilled with one token per `per_minute`th of a minute
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+impl RateLimit {
+    pub fn validate(&self) -> Result<(), AppError> {
+        if self.burst == 0 || self.per_minute == 0 {
+            return Err(AppError::InvalidError(
+                "rate limits need a positive burst and rate".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn quota(&self) -> Quota {
+        Quota {
+            burst: self.burst,
+            interval: Duration::from_secs(60) / self.per_minute,
+        }
+    }
+}
+
+/// a token every `interval`, at most `burst` of them saved up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quota {
+    pub burst: u32,
+    pub interval: Duration,
+}
+
+impl Quota {
+    /// one message per interval
+    pub fn slow_mode(secs: u32) -> Self {
+        Self {
+            burst: 1,
+            interval: Duration::from_secs(secs as _),
+        }
+    }
+
+    /// how far ahead of now the bucket may be booked
+    pub fn tolerance(&self) -> Duration {
+        self.interval * self.burst
+    }
+}
@@ -0,0 +1,15 @@
+use std::time::Duration;
+
+use axum::async_trait;
+
+use crate::error::AppError;
+
+use super::Quota;
+
+#[async_trait]
+pub trait RateLimitRepo {
+    /// take a token from the bucket, when it is empty the wait until the next token is returned
+    async fn take(&self, key: &str, quota: &Quota) -> Result<Option<Duration>, AppError>;
+    /// forget the full buckets
+    async fn prune(&self) -> Result<u64, AppError>;
+}
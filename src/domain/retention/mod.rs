@@ -0,0 +1,59 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::error::AppError;
+
+mod repo;
+
+pub use repo::RetentionRepo;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "retention_mode", rename_all = "snake_case")]
+#[serde(rename_all(serialize = "snake_case", deserialize = "snake_case"))]
+pub enum RetentionMode {
+    /// the policy of the workspace, for chats only
+    #[default]
+    Inherit,
+    Forever,
+    /// messages older than `retention_days` are deleted
+    Days,
+}
+
+/// retention of a workspace or a chat
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    pub retention: RetentionMode,
+    pub retention_days: Option<i32>,
+    /// keeps every message whatever the policy
+    pub legal_hold: bool,
+}
+
+impl RetentionPolicy {
+    pub fn validate(&mut self, for_workspace: bool) -> Result<(), AppError> {
+        match self.retention {
+            RetentionMode::Inherit if for_workspace => {
+                return Err(AppError::InvalidError(
+                    "workspace retention can not inherit".to_owned(),
+                ))
+            }
+            RetentionMode::Days => {
+                if self.retention_days.is_none_or(|days| days <= 0) {
+                    return Err(AppError::InvalidError(
+                        "retention days should be positive".to_owned(),
+                    ));
+                }
+            }
+            _ => self.retention_days = None,
+        }
+        Ok(())
+    }
+}
+
+/// a chat with messages older than its effective retention
+#[derive(Debug, Clone, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct ExpiringChat {
+    pub chat_id: i64,
+    pub ws_id: i64,
+    pub before: DateTime<Utc>,
+}
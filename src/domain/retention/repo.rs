@@ -0,0 +1,38 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::{domain::chat::Msg, error::AppError};
+
+use super::{ExpiringChat, RetentionPolicy};
+
+#[async_trait]
+pub trait RetentionRepo {
+    async fn extract_ws_policy(&self, ws_id: i64) -> Result<Option<RetentionPolicy>, AppError>;
+    async fn update_ws_policy(
+        &self,
+        ws_id: i64,
+        policy: &RetentionPolicy,
+    ) -> Result<Option<RetentionPolicy>, AppError>;
+    async fn extract_chat_policy(
+        &self,
+        ws_id: i64,
+        chat_id: i64,
+    ) -> Result<Option<RetentionPolicy>, AppError>;
+    async fn update_chat_policy(
+        &self,
+        ws_id: i64,
+        chat_id: i64,
+        policy: &RetentionPolicy,
+    ) -> Result<Option<RetentionPolicy>, AppError>;
+    /// chats not on legal hold with messages older than their retention, the chats whose
+    /// oldest message is the most overdue first
+    async fn extract_expiring_chats(&self, limit: i64) -> Result<Vec<ExpiringChat>, AppError>;
+    /// delete up to `limit` of the oldest messages sent before `before`, unless a legal hold
+    /// was placed meanwhile
+    async fn delete_expired_messages(
+        &self,
+        chat_id: i64,
+        before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<Msg>, AppError>;
+}
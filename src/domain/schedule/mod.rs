@@ -0,0 +1,110 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::error::AppError;
+
+use super::chat::ContentType;
+
+mod repo;
+
+pub use repo::ScheduleRepo;
+
+/// how far ahead an item may be scheduled
+const MAX_AHEAD_DAYS: i64 = 366;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "schedule_kind", rename_all = "snake_case")]
+#[serde(rename_all(serialize = "snake_case", deserialize = "snake_case"))]
+pub enum ScheduleKind {
+    /// sent to the chat as the user
+    #[default]
+    Message,
+    /// alerts the user about a message of the chat, or only with a note
+    Reminder,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "schedule_status", rename_all = "snake_case")]
+#[serde(rename_all(serialize = "snake_case", deserialize = "snake_case"))]
+pub enum ScheduleStatus {
+    #[default]
+    Pending,
+    Done,
+    Failed,
+    Canceled,
+}
+
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledItem {
+    pub id: i64,
+    pub kind: ScheduleKind,
+    pub user_id: i64,
+    pub ws_id: i64,
+    pub chat_id: i64,
+    pub msg_id: Option<i64>,
+    pub content: String,
+    pub content_type: ContentType,
+    pub run_at: DateTime<Utc>,
+    pub status: ScheduleStatus,
+    pub attempts: i32,
+    pub sent_msg_id: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl ScheduledItem {
+    pub fn new(
+        kind: ScheduleKind,
+        user_id: i64,
+        ws_id: i64,
+        chat_id: i64,
+        content: String,
+        run_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            id: -1,
+            kind,
+            user_id,
+            ws_id,
+            chat_id,
+            msg_id: None,
+            content,
+            content_type: ContentType::Plain,
+            run_at,
+            status: ScheduleStatus::Pending,
+            attempts: 0,
+            sent_msg_id: None,
+            error: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), AppError> {
+        let now = Utc::now();
+        if self.run_at <= now || self.run_at > now + chrono::Duration::days(MAX_AHEAD_DAYS) {
+            return Err(AppError::InvalidError(format!(
+                "run_at should be in the next {} days",
+                MAX_AHEAD_DAYS
+            )));
+        }
+        if self.content_type == ContentType::Poll {
+            return Err(AppError::InvalidError(
+                "polls can not be scheduled".to_owned(),
+            ));
+        }
+        match self.kind {
+            ScheduleKind::Message if self.content.trim().is_empty() => Err(AppError::InvalidError(
+                "scheduled message should not be empty".to_owned(),
+            )),
+            ScheduleKind::Reminder if self.msg_id.is_none() && self.content.trim().is_empty() => {
+                Err(AppError::InvalidError(
+                    "reminder should have a message or a note".to_owned(),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use axum::async_trait;
+
+use crate::error::AppError;
+
+use super::{ScheduleStatus, ScheduledItem};
+
+#[async_trait]
+pub trait ScheduleRepo {
+    async fn save(&self, item: &ScheduledItem) -> Result<ScheduledItem, AppError>;
+    async fn extract_by_id(&self, id: i64) -> Result<Option<ScheduledItem>, AppError>;
+    async fn extract_by_user(
+        &self,
+        user_id: i64,
+        ws_id: i64,
+        status: ScheduleStatus,
+    ) -> Result<Vec<ScheduledItem>, AppError>;
+    /// update content and time of a pending item which is not being delivered
+    async fn update_pending(&self, item: &ScheduledItem)
+        -> Result<Option<ScheduledItem>, AppError>;
+    /// cancel a pending item which is not being delivered
+    async fn cancel(&self, id: i64) -> Result<Option<ScheduledItem>, AppError>;
+    /// lease up to `limit` due items, items leased by another scheduler are skipped
+    async fn claim_due(&self, limit: i64, lease_secs: i64) -> Result<Vec<ScheduledItem>, AppError>;
+    /// leave pending for the final status, the status changes only once
+    async fn finish(
+        &self,
+        id: i64,
+        status: ScheduleStatus,
+        sent_msg_id: Option<i64>,
+        error: Option<&str>,
+    ) -> Result<bool, AppError>;
+}
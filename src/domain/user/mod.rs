@@ -6,16 +6,65 @@ mod repo;
 
 pub use repo::UserRepo;
 
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "ws_role", rename_all = "snake_case")]
+#[serde(rename_all(serialize = "snake_case", deserialize = "snake_case"))]
+pub enum WsRole {
+    Admin,
+    #[default]
+    Member,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "user_status", rename_all = "snake_case")]
+#[serde(rename_all(serialize = "snake_case", deserialize = "snake_case"))]
+pub enum UserStatus {
+    #[default]
+    Active,
+    Deactivated,
+}
+
 #[derive(Debug, Clone, Default, FromRow, PartialEq, Serialize, Deserialize)]
 pub struct User {
     pub id: i64,
+    /// default workspace, used when signing in without choosing one
     pub ws_id: i64,
     pub fullname: String,
     pub email: String,
+    #[serde(default, skip_serializing)]
     pub password_hash: String,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub title: Option<String>,
+    pub status_text: Option<String>,
+    pub status_emoji: Option<String>,
+    pub status_expires_at: Option<DateTime<Utc>>,
+    pub timezone: Option<String>,
+    pub status: UserStatus,
+    #[serde(default, skip_serializing)]
+    pub token_version: i32,
+    /// bots sign in with api tokens, never a password
+    #[serde(default)]
+    pub is_bot: bool,
     pub created_at: DateTime<Utc>,
 }
 
+impl User {
+    pub fn is_active(&self) -> bool {
+        self.status == UserStatus::Active
+    }
+
+    pub fn display(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.fullname)
+    }
+
+    /// status is only shown until it expires
+    pub fn has_status(&self) -> bool {
+        self.status_expires_at.is_none_or(|t| t > Utc::now())
+            && (self.status_text.is_some() || self.status_emoji.is_some())
+    }
+}
+
 #[derive(Debug, Clone, Default, FromRow, PartialEq, Serialize, Deserialize)]
 pub struct Workspace {
     pub id: i64,
@@ -23,3 +72,36 @@ pub struct Workspace {
     pub owner_id: i64,
     pub created_at: DateTime<Utc>,
 }
+
+impl Workspace {
+    pub fn is_admin(&self, member: &WsMember) -> bool {
+        member.ws_id == self.id
+            && member.status == UserStatus::Active
+            && (member.user_id == self.owner_id || member.role == WsRole::Admin)
+    }
+}
+
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct WsMember {
+    pub ws_id: i64,
+    pub user_id: i64,
+    pub role: WsRole,
+    pub status: UserStatus,
+    pub created_at: DateTime<Utc>,
+}
+
+impl WsMember {
+    pub fn new(ws_id: i64, user_id: i64, role: WsRole) -> Self {
+        Self {
+            ws_id,
+            user_id,
+            role,
+            status: UserStatus::Active,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.status == UserStatus::Active
+    }
+}
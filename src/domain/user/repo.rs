@@ -2,15 +2,23 @@ use axum::async_trait;
 
 use crate::error::AppError;
 
-use super::{User, Workspace};
+use super::{User, Workspace, WsMember};
 
 #[async_trait]
 pub trait UserRepo {
     async fn extract_all_users(&self, ws_id: u64) -> Result<Vec<User>, AppError>;
+    async fn extract_active_users(&self, ws_id: u64) -> Result<Vec<User>, AppError>;
     async fn find_by_email(&self, email: &str) -> Result<Option<User>, AppError>;
     async fn find_by_id(&self, user_id: i64) -> Result<Option<User>, AppError>;
     async fn save(&self, input: &User) -> Result<User, AppError>;
+    async fn update(&self, input: &User) -> Result<User, AppError>;
     async fn find_ws_by_name(&self, name: &str) -> Result<Option<Workspace>, AppError>;
     async fn find_ws_by_id(&self, ws_id: i64) -> Result<Option<Workspace>, AppError>;
+    async fn extract_user_ws(&self, user_id: i64) -> Result<Vec<Workspace>, AppError>;
     async fn save_ws(&self, ws: &Workspace) -> Result<Workspace, AppError>;
+    async fn update_ws(&self, ws: &Workspace) -> Result<Workspace, AppError>;
+    async fn find_member(&self, ws_id: i64, user_id: i64) -> Result<Option<WsMember>, AppError>;
+    async fn extract_members(&self, ws_id: i64) -> Result<Vec<WsMember>, AppError>;
+    async fn save_member(&self, member: &WsMember) -> Result<WsMember, AppError>;
+    async fn delete_member(&self, ws_id: i64, user_id: i64) -> Result<(), AppError>;
 }
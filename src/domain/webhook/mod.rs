@@ -0,0 +1,143 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, types::Json};
+
+use crate::error::AppError;
+
+mod repo;
+
+pub use repo::WebhookRepo;
+
+/// events a webhook may subscribe to, named as `AppEvent::get_name`
+pub const WEBHOOK_EVENTS: [&str; 9] = [
+    "new_chat",
+    "update_chat",
+    "chat_archived",
+    "chat_restored",
+    "chat_deleted",
+    "new_message",
+    "message_deleted",
+    "member_added",
+    "member_removed",
+];
+
+/// a delivery is dead after this many failed attempts
+pub const MAX_ATTEMPTS: i32 = 8;
+const FIRST_RETRY: Duration = Duration::from_secs(30);
+const MAX_RETRY: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: i64,
+    pub ws_id: i64,
+    pub url: String,
+    /// only shown when the webhook is created
+    #[serde(skip)]
+    pub secret: String,
+    pub events: Vec<String>,
+    pub active: bool,
+    pub created_by: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Webhook {
+    pub fn new(
+        ws_id: i64,
+        created_by: i64,
+        url: String,
+        secret: String,
+        events: Vec<String>,
+    ) -> Self {
+        Self {
+            id: -1,
+            ws_id,
+            url,
+            secret,
+            events,
+            active: true,
+            created_by,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), AppError> {
+        if self.url.len() > 2048 {
+            return Err(AppError::InvalidError("url is too long".to_owned()));
+        }
+        if self.events.is_empty() {
+            return Err(AppError::InvalidError("events are empty".to_owned()));
+        }
+        if let Some(event) = self
+            .events
+            .iter()
+            .find(|v| !WEBHOOK_EVENTS.contains(&v.as_str()))
+        {
+            return Err(AppError::InvalidError(format!("unknown event {}", event)));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "webhook_delivery_status", rename_all = "snake_case")]
+#[serde(rename_all(serialize = "snake_case", deserialize = "snake_case"))]
+pub enum DeliveryStatus {
+    #[default]
+    Pending,
+    Delivered,
+    /// gave up retrying, an admin may redeliver it
+    Dead,
+}
+
+/// an event sent, or to be sent, to a webhook
+#[derive(Debug, Clone, FromRow, PartialEq, Serialize, Deserialize)]
+pub struct Delivery {
+    pub id: i64,
+    pub webhook_id: i64,
+    pub event: String,
+    pub payload: Json<serde_json::Value>,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    /// http status of the last attempt, none if no response came back
+    pub last_status: Option<i16>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// a pending delivery leased by a worker, along with where it goes
+#[derive(Debug, Clone, FromRow, PartialEq)]
+pub struct DeliveryJob {
+    pub id: i64,
+    pub webhook_id: i64,
+    pub url: String,
+    pub secret: String,
+    pub event: String,
+    pub payload: Json<serde_json::Value>,
+    pub attempts: i32,
+}
+
+impl DeliveryJob {
+    /// wait before the next attempt, none once the delivery is dead
+    pub fn retry_delay(&self) -> Option<Duration> {
+        if self.attempts >= MAX_ATTEMPTS {
+            return None;
+        }
+        let factor = 1u32 << (self.attempts.max(1) - 1).min(16);
+        Some((FIRST_RETRY * factor).min(MAX_RETRY))
+    }
+}
+
+/// hex hmac-sha256 of `{timestamp}.{body}`, receivers check it against their copy of the secret
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut signed = timestamp.to_string().into_bytes();
+    signed.push(b'.');
+    signed.extend_from_slice(body);
+    hmac_sha256::HMAC::mac(signed, secret.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
@@ -0,0 +1,48 @@
+use axum::async_trait;
+
+use crate::error::AppError;
+
+use super::{Delivery, DeliveryJob, DeliveryStatus, Webhook};
+
+#[async_trait]
+pub trait WebhookRepo {
+    async fn save(&self, webhook: &Webhook) -> Result<Webhook, AppError>;
+    async fn extract_by_ws(&self, ws_id: i64) -> Result<Vec<Webhook>, AppError>;
+    async fn find_by_id(&self, ws_id: i64, id: i64) -> Result<Option<Webhook>, AppError>;
+    /// none if the webhook is not in the workspace
+    async fn delete(&self, ws_id: i64, id: i64) -> Result<Option<Webhook>, AppError>;
+    /// queue the event for every active webhook of the chat's workspace subscribed to it,
+    /// unless it was queued under the same key, the count of queued deliveries is returned
+    async fn enqueue(
+        &self,
+        chat_id: i64,
+        event: &str,
+        key: &str,
+        payload: &serde_json::Value,
+    ) -> Result<u64, AppError>;
+    /// lease up to `limit` due deliveries of active webhooks, counting the attempt
+    async fn claim_deliveries(
+        &self,
+        limit: i64,
+        lease_secs: i64,
+    ) -> Result<Vec<DeliveryJob>, AppError>;
+    async fn complete_delivery(&self, id: i64, status: i16) -> Result<(), AppError>;
+    /// retried after `retry_secs`, or dead when none
+    async fn fail_delivery(
+        &self,
+        id: i64,
+        status: Option<i16>,
+        error: &str,
+        retry_secs: Option<i64>,
+    ) -> Result<(), AppError>;
+    /// deliveries of the webhook older than `before_id`, newest first
+    async fn extract_deliveries(
+        &self,
+        webhook_id: i64,
+        status: Option<DeliveryStatus>,
+        before_id: i64,
+        limit: i64,
+    ) -> Result<Vec<Delivery>, AppError>;
+    /// queue a dead delivery again with fresh attempts, none unless it is dead
+    async fn redeliver(&self, webhook_id: i64, id: i64) -> Result<Option<Delivery>, AppError>;
+}
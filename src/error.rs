@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -40,6 +40,10 @@ pub enum AppError {
     AnyError(#[from] anyhow::Error),
     #[error("general error: {0}")]
     SerdeError(#[from] serde_json::Error),
+    #[error("http error: {0}")]
+    HttpError(#[from] reqwest::Error),
+    #[error("too many requests, retry in {0}s")]
+    TooManyRequests(u64),
     #[error("general error: {0}")]
     GeneralError(String),
 }
@@ -56,8 +60,17 @@ impl IntoResponse for AppError {
             AppError::IOError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::AnyError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::SerdeError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::HttpError(_) => StatusCode::BAD_GATEWAY,
+            AppError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
             AppError::GeneralError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
-        (status, Json(json!(ErrorOutput::new(self.to_string())))).into_response()
+        let mut response =
+            (status, Json(json!(ErrorOutput::new(self.to_string())))).into_response();
+        if let AppError::TooManyRequests(secs) = self {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
     }
 }
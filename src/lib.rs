@@ -1,12 +1,12 @@
 mod state;
 use adapter::driven::api::{
-    chats,
-    middlewares::{check_msg_perm, verify_token},
-    users,
+    audit, bots, channels, chats, commands,
+    middlewares::{check_channel_perm, check_msg_perm, check_ws_admin, rate_limit, verify_token},
+    moderation, polls, retention, scheduled, users, webhooks, workspace,
 };
 use axum::{
     middleware::from_fn_with_state,
-    routing::{get, post},
+    routing::{delete, get, patch, post},
     Router,
 };
 pub use state::AppState;
@@ -16,6 +16,8 @@ mod domain;
 mod error;
 mod log;
 mod service;
+#[cfg(test)]
+mod test_util;
 pub use adapter::driven::api::auth;
 
 pub async fn index_handler() -> &'static str {
@@ -32,17 +34,141 @@ pub fn build_http_router(state: &AppState) -> Router {
                 .post(chats::send_msg),
         )
         .route("/:id/messages", get(chats::list_messages))
+        .route("/:id/messages/:msg_id", get(chats::get_msg))
+        .route("/:id/messages/:msg_id/forward", post(chats::forward_msg))
+        .route("/:id/messages/:msg_id/report", post(moderation::report))
+        .route("/:id/members", post(chats::add_members))
+        .route("/:id/members/:uid", delete(chats::remove_member))
+        .route("/:id/leave", post(chats::leave))
+        .route("/:id/archive", post(chats::archive))
+        .route("/:id/restore", post(chats::restore))
+        .route("/:id/prefs", patch(chats::update_prefs))
+        .route("/:id/header", patch(chats::update_header))
+        .route("/:id/slow_mode", get(chats::get_slow_mode))
+        .route("/:id/pins", get(chats::list_pins).post(chats::pin))
+        .route("/:id/pins/:msg_id", delete(chats::unpin))
+        .route(
+            "/:id/bookmarks",
+            get(chats::list_bookmarks).post(chats::add_bookmark),
+        )
+        .route("/:id/bookmarks/:bid", delete(chats::remove_bookmark))
+        .route("/:id/polls", post(polls::create))
+        .route("/:id/polls/:msg_id", get(polls::get))
+        .route(
+            "/:id/polls/:msg_id/votes",
+            post(polls::vote).delete(polls::retract),
+        )
+        .route("/:id/polls/:msg_id/close", post(polls::close))
+        .route("/:id/scheduled", post(scheduled::schedule_msg))
+        .route("/:id/reminders", post(scheduled::remind))
         .layer(from_fn_with_state(state.clone(), check_msg_perm))
         .route("/", get(chats::list_all).post(chats::create));
+    let channel = Router::new()
+        .route("/:id", get(chats::get))
+        .route("/:id/messages", get(chats::list_messages))
+        .route("/:id/messages/:msg_id", get(chats::get_msg))
+        .route("/:id/messages/:msg_id/report", post(moderation::report))
+        .route("/:id/join", post(channels::join))
+        .route("/:id/leave", post(chats::leave))
+        .layer(from_fn_with_state(state.clone(), check_channel_perm))
+        .route("/", get(channels::list_all));
+    let admin = Router::new()
+        .route("/", get(workspace::get).patch(workspace::rename))
+        .route("/owner", post(workspace::transfer_owner))
+        .route(
+            "/members",
+            get(workspace::list_members).post(workspace::add_member),
+        )
+        .route(
+            "/members/:uid",
+            patch(workspace::update_member).delete(workspace::remove_member),
+        )
+        .route(
+            "/retention",
+            get(retention::get_ws_policy).patch(retention::update_ws_policy),
+        )
+        .route("/chats/:id", delete(chats::purge))
+        .route(
+            "/chats/:id/retention",
+            get(retention::get_chat_policy).patch(retention::update_chat_policy),
+        )
+        .route("/reports", get(moderation::list_reports))
+        .route("/reports/:id", patch(moderation::resolve_report))
+        .route(
+            "/chats/:id/messages/:msg_id",
+            delete(moderation::delete_msg),
+        )
+        .route(
+            "/chats/:id/mutes",
+            get(moderation::list_mutes).post(moderation::mute),
+        )
+        .route("/chats/:id/mutes/:uid", delete(moderation::unmute))
+        .route(
+            "/chats/:id/bans",
+            get(moderation::list_bans).post(moderation::ban),
+        )
+        .route("/chats/:id/bans/:uid", delete(moderation::unban))
+        .route("/chats/:id/slow_mode", patch(moderation::set_slow_mode))
+        .route("/audit", get(audit::list))
+        .route("/audit/export", get(audit::export))
+        .route("/webhooks", get(webhooks::list_all).post(webhooks::create))
+        .route(
+            "/webhooks/:id",
+            patch(webhooks::update).delete(webhooks::delete),
+        )
+        .route("/webhooks/:id/deliveries", get(webhooks::list_deliveries))
+        .route(
+            "/webhooks/:id/deliveries/:delivery_id/redeliver",
+            post(webhooks::redeliver),
+        )
+        .route("/bots", get(bots::list_all).post(bots::create))
+        .route("/bots/:id", delete(bots::deactivate))
+        .route(
+            "/bots/:id/tokens",
+            get(bots::list_tokens).post(bots::create_token),
+        )
+        .route("/bots/:id/tokens/:token_id", delete(bots::revoke_token))
+        .route(
+            "/bots/:id/incoming",
+            get(bots::list_hooks).post(bots::create_hook),
+        )
+        .route("/bots/:id/incoming/:hook_id", delete(bots::revoke_hook))
+        .route("/commands", get(commands::list_all).post(commands::create))
+        .route("/commands/:id", delete(commands::delete))
+        .layer(from_fn_with_state(state.clone(), check_ws_admin));
     let api = Router::new()
         .route("/users", get(users::list_all_users))
-        .route("/userinfo", get(users::get_user_info))
+        .route(
+            "/userinfo",
+            get(users::get_user_info)
+                .patch(users::update_user_info)
+                .delete(users::erase),
+        )
+        .route("/userinfo/deactivate", post(users::deactivate))
+        .route("/userinfo/export", get(users::export))
+        .route("/userinfo/avatar", post(users::upload_avatar))
         .nest("/chats", chat)
+        .nest("/channels", channel)
+        .route("/mentions", get(chats::list_mentions))
+        .route("/scheduled", get(scheduled::list_all))
+        .route(
+            "/scheduled/:id",
+            patch(scheduled::update).delete(scheduled::cancel),
+        )
+        .nest("/workspace", admin)
+        .route(
+            "/workspaces",
+            get(workspace::list_user_ws).post(workspace::create),
+        )
+        .route("/workspaces/:id/token", post(workspace::switch))
         .route("/upload", post(chats::upload))
         .route("/events", post(chats::notif_handler))
+        // runs after the token is verified
+        .layer(from_fn_with_state(state.clone(), rate_limit))
         .layer(from_fn_with_state(state.clone(), verify_token))
         .route("/signin", post(auth::signin_handler))
-        .route("/signup", post(auth::signup_handler));
+        .route("/signup", post(auth::signup_handler))
+        .route("/hooks/:token", post(bots::incoming));
     Router::new()
         .route("/", get(index_handler))
         .nest("/api", api)
@@ -52,3 +178,14 @@ pub fn build_http_router(state: &AppState) -> Router {
 pub fn init_log(state: &AppState) {
     log::init(state);
 }
+
+pub fn start_jobs(state: &AppState) {
+    adapter::driven::job::spawn_purge_chats(state.clone());
+    adapter::driven::job::spawn_unfurl_links(state.clone());
+    adapter::driven::job::spawn_deliver_scheduled(state.clone());
+    adapter::driven::job::spawn_sweep_retention(state.clone());
+    adapter::driven::job::spawn_prune_rate_limits(state.clone());
+    adapter::driven::job::spawn_prune_alerts(state.clone());
+    adapter::driven::job::spawn_dispatch_webhooks(state.clone());
+    adapter::driven::job::spawn_deliver_webhooks(state.clone());
+}
@@ -1,5 +1,7 @@
+use std::net::SocketAddr;
+
 use anyhow::Result;
-use chat::{build_http_router, init_log, AppState};
+use chat::{build_http_router, init_log, start_jobs, AppState};
 use tokio::net::TcpListener;
 use tracing::info;
 
@@ -7,11 +9,16 @@ use tracing::info;
 async fn main() -> Result<()> {
     let state = AppState::try_new_from_conf().await.unwrap();
     init_log(&state);
+    start_jobs(&state);
 
     let addr = state.config.server.local_addr();
     info!("server listening on {}", addr);
 
     let listener = TcpListener::bind(&addr).await?;
-    axum::serve(listener, build_http_router(&state).into_make_service()).await?;
+    axum::serve(
+        listener,
+        build_http_router(&state).into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
     Ok(())
 }
@@ -0,0 +1,252 @@
+use std::io::{Cursor, Write as _};
+
+use serde::{Deserialize, Serialize};
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+use crate::{
+    common::utils::verify_passwd,
+    domain::{
+        chat::ChatRepo,
+        user::{User, UserRepo, UserStatus},
+    },
+    error::AppError,
+};
+
+use super::{auth::ClaimUser, file::FileService};
+
+const ERASED_NAME: &str = "Deleted User";
+
+pub struct AccountService {
+    users: Box<dyn UserRepo + Send + Sync>,
+    chats: Box<dyn ChatRepo + Send + Sync>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfirmPasswordDto {
+    pub password: String,
+}
+
+impl AccountService {
+    pub fn new(
+        users: Box<dyn UserRepo + Send + Sync>,
+        chats: Box<dyn ChatRepo + Send + Sync>,
+    ) -> Self {
+        Self { users, chats }
+    }
+
+    /// block signin and revoke every token issued so far
+    pub async fn deactivate(
+        &self,
+        user: &ClaimUser,
+        input: ConfirmPasswordDto,
+    ) -> Result<(), AppError> {
+        let mut user = self.confirm(user, &input.password).await?;
+        user.status = UserStatus::Deactivated;
+        user.token_version += 1;
+        self.users.update(&user).await?;
+        Ok(())
+    }
+
+    /// zip archive with the profile, workspaces, chats, messages and files of the user
+    pub async fn export(&self, user: &ClaimUser, files: &FileService) -> Result<Vec<u8>, AppError> {
+        let user = self.find_user(user.id).await?;
+        let mut entries = vec![
+            ("profile.json".to_owned(), serde_json::to_vec_pretty(&user)?),
+            (
+                "workspaces.json".to_owned(),
+                serde_json::to_vec_pretty(&self.users.extract_user_ws(user.id).await?)?,
+            ),
+            (
+                "chats.json".to_owned(),
+                serde_json::to_vec_pretty(&self.chats.extract_user_chats(user.id).await?)?,
+            ),
+            (
+                "messages.json".to_owned(),
+                serde_json::to_vec_pretty(&self.chats.extract_user_messages(user.id).await?)?,
+            ),
+        ];
+        let owned = files.list_owned(user.id as _).await?;
+        entries.push(("files.json".to_owned(), serde_json::to_vec_pretty(&owned)?));
+        for file in owned {
+            // content may be gone already, the metadata is still exported
+            if let Ok(data) = files.read(&file).await {
+                entries.push((format!("files/{}-{}", file.id, file.path), data));
+            }
+        }
+        tokio::task::spawn_blocking(move || build_archive(entries))
+            .await
+            .map_err(|e| AppError::AnyError(e.into()))?
+    }
+
+    /// anonymize the account, its messages and files, other members keep a coherent history
+    pub async fn erase(
+        &self,
+        user: &ClaimUser,
+        input: ConfirmPasswordDto,
+        files: &FileService,
+    ) -> Result<(), AppError> {
+        let user = self.confirm(user, &input.password).await?;
+        for ws in self.users.extract_user_ws(user.id).await? {
+            self.users.delete_member(ws.id, user.id).await?;
+        }
+        self.chats.erase_user_messages(user.id).await?;
+        files.erase_owned(user.id as _).await?;
+        let user = User {
+            fullname: ERASED_NAME.to_owned(),
+            email: format!("deleted-{}@erased.invalid", user.id),
+            password_hash: String::new(),
+            display_name: None,
+            avatar_url: None,
+            title: None,
+            status_text: None,
+            status_emoji: None,
+            status_expires_at: None,
+            timezone: None,
+            status: UserStatus::Deactivated,
+            token_version: user.token_version + 1,
+            ..user
+        };
+        self.users.update(&user).await?;
+        Ok(())
+    }
+
+    async fn confirm(&self, user: &ClaimUser, password: &str) -> Result<User, AppError> {
+        let user = self.find_user(user.id).await?;
+        verify_passwd(password, &user.password_hash)?;
+        for ws in self.users.extract_user_ws(user.id).await? {
+            if ws.owner_id == user.id {
+                return Err(AppError::ConflictError(format!(
+                    "transfer the ownership of workspace {} first",
+                    ws.name
+                )));
+            }
+        }
+        Ok(user)
+    }
+
+    async fn find_user(&self, user_id: u64) -> Result<User, AppError> {
+        self.users
+            .find_by_id(user_id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(user_id.to_string()))
+    }
+}
+
+fn build_archive(entries: Vec<(String, Vec<u8>)>) -> Result<Vec<u8>, AppError> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+    for (name, data) in entries {
+        zip.start_file(name, options)
+            .map_err(|e| AppError::AnyError(e.into()))?;
+        zip.write_all(&data)?;
+    }
+    let cursor = zip.finish().map_err(|e| AppError::AnyError(e.into()))?;
+    Ok(cursor.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read as _;
+
+    use sqlx::PgPool;
+    use zip::ZipArchive;
+
+    use super::*;
+    use crate::{
+        domain::{
+            audit::ClientInfo,
+            chat::{ChatType, ContentType},
+        },
+        service::{
+            auth::SigninUserDto,
+            chat::{CreateChatDto, SendMsgDto},
+        },
+        test_util::{signup, state},
+        AppState,
+    };
+
+    fn password() -> ConfirmPasswordDto {
+        ConfirmPasswordDto {
+            password: "123456".to_owned(),
+        }
+    }
+
+    async fn signin(state: &AppState, email: &str) -> Result<String, AppError> {
+        let input = SigninUserDto {
+            email: email.to_owned(),
+            password: "123456".to_owned(),
+            ws_id: None,
+        };
+        state.auth.signin(input, &ClientInfo::default()).await
+    }
+
+    #[sqlx::test]
+    async fn deactivate_should_revoke_the_tokens_of_the_account(pool: PgPool) {
+        let state = state(pool).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "acme").await;
+        let token = signin(&state, "bob@acme.org").await.unwrap();
+
+        // the owner hands the workspace over first
+        let ret = state.account.deactivate(&alice, password()).await;
+        assert!(matches!(ret, Err(AppError::ConflictError(_))));
+        let input = ConfirmPasswordDto {
+            password: "wrong".to_owned(),
+        };
+        assert!(state.account.deactivate(&bob, input).await.is_err());
+
+        state.account.deactivate(&bob, password()).await.unwrap();
+        assert!(state.auth.verify_token(token).await.is_err());
+        assert!(signin(&state, "bob@acme.org").await.is_err());
+    }
+
+    #[sqlx::test]
+    async fn export_should_hold_the_messages_erase_blanks(pool: PgPool) {
+        let state = state(pool).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "acme").await;
+        let input = CreateChatDto {
+            name: Some("general".to_owned()),
+            chat_type: ChatType::PublicChannel,
+            members: vec![alice.id, bob.id],
+        };
+        let chat = state.chat.create(&alice, input).await.unwrap();
+        let input = SendMsgDto {
+            content: "my phone is 555-0100".to_owned(),
+            content_type: ContentType::Plain,
+            client_id: None,
+            quote_id: None,
+            files: vec![],
+        };
+        let sent = state
+            .chat
+            .send_msg(&bob, chat.id as _, input)
+            .await
+            .unwrap();
+
+        let archive = state.account.export(&bob, &state.file).await.unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(archive)).unwrap();
+        let mut messages = String::new();
+        archive
+            .by_name("messages.json")
+            .unwrap()
+            .read_to_string(&mut messages)
+            .unwrap();
+        assert!(messages.contains("555-0100"));
+
+        state
+            .account
+            .erase(&bob, password(), &state.file)
+            .await
+            .unwrap();
+        let msg = state
+            .chat
+            .get_msg(chat.id as _, sent.id as _)
+            .await
+            .unwrap();
+        assert!(!msg.content.contains("555-0100"));
+        assert!(signin(&state, "bob@acme.org").await.is_err());
+        let members = state.workspace.list_members(alice.ws_id).await.unwrap();
+        assert_eq!(members.len(), 1);
+    }
+}
@@ -0,0 +1,228 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    domain::audit::{AuditAction, AuditEvent, AuditFilter, AuditRepo},
+    error::AppError,
+};
+
+use super::auth::ClaimUser;
+
+const DEFAULT_PAGE_SIZE: u16 = 50;
+const MAX_PAGE_SIZE: u16 = 500;
+const EXPORT_BATCH: i64 = 1000;
+/// an export stops here, narrow the filter to get older events
+const MAX_EXPORT_ROWS: usize = 100_000;
+
+pub struct AuditService {
+    repo: Box<dyn AuditRepo + Send + Sync>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditQueryDto {
+    pub actor_id: Option<u64>,
+    pub action: Option<String>,
+    pub target: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// events older than this one
+    pub before_id: Option<u64>,
+    pub limit: Option<u16>,
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Jsonl,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Jsonl => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+impl AuditQueryDto {
+    fn filter(&self) -> AuditFilter {
+        AuditFilter {
+            actor_id: self.actor_id.map(|id| id as _),
+            action: self.action.clone(),
+            target: self.target.clone(),
+            since: self.since,
+            until: self.until,
+        }
+    }
+
+    fn before_id(&self) -> i64 {
+        self.before_id.map_or(i64::MAX, |id| id as _)
+    }
+}
+
+impl AuditService {
+    pub fn new(repo: Box<dyn AuditRepo + Send + Sync>) -> Self {
+        Self { repo }
+    }
+
+    /// events of the workspace matching the query, newest first
+    pub async fn list(
+        &self,
+        ws_id: u64,
+        input: AuditQueryDto,
+    ) -> Result<Vec<AuditEvent>, AppError> {
+        let limit = input
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        self.repo
+            .extract(ws_id as _, &input.filter(), input.before_id(), limit as _)
+            .await
+    }
+
+    /// every matching event as a csv or json lines document, the export itself is audited
+    pub async fn export(
+        &self,
+        actor: &ClaimUser,
+        input: AuditQueryDto,
+    ) -> Result<Vec<u8>, AppError> {
+        let filter = input.filter();
+        let mut before_id = input.before_id();
+        let mut body = match input.format {
+            ExportFormat::Csv => AuditEvent::CSV_HEADER.to_owned(),
+            ExportFormat::Jsonl => String::new(),
+        };
+        let mut rows = 0;
+        while rows < MAX_EXPORT_ROWS {
+            let batch = EXPORT_BATCH.min((MAX_EXPORT_ROWS - rows) as _);
+            let events = self
+                .repo
+                .extract(actor.ws_id as _, &filter, before_id, batch)
+                .await?;
+            for event in &events {
+                match input.format {
+                    ExportFormat::Csv => body.push_str(&event.to_csv_row()),
+                    ExportFormat::Jsonl => {
+                        body.push_str(&serde_json::to_string(event)?);
+                        body.push('\n');
+                    }
+                }
+            }
+            rows += events.len();
+            match events.last() {
+                Some(last) if events.len() as i64 == batch => before_id = last.id,
+                _ => break,
+            }
+        }
+        let event = AuditEvent::new(
+            actor.ws_id as _,
+            actor.id as _,
+            AuditAction::AuditExport,
+            format!("workspace:{}", actor.ws_id),
+            json!({ "filter": filter, "format": input.format.extension(), "rows": rows }),
+        )
+        .with_client(&actor.client);
+        self.repo.append(&event).await?;
+        Ok(body.into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{
+        domain::{audit::ClientInfo, chat::ChatType},
+        service::{auth::SigninUserDto, chat::CreateChatDto},
+        test_util::{signup, state},
+    };
+
+    fn actions(events: &[AuditEvent]) -> Vec<&str> {
+        events.iter().map(|v| v.action.as_str()).collect()
+    }
+
+    #[sqlx::test]
+    async fn actions_should_be_recorded_with_their_client_and_listed(pool: PgPool) {
+        let state = state(pool).await;
+        let client = ClientInfo {
+            ip: Some("203.0.113.7".to_owned()),
+            user_agent: Some("curl/8.0".to_owned()),
+        };
+        let alice = ClaimUser {
+            client: client.clone(),
+            ..signup(&state, "alice", "acme").await
+        };
+        let bob = signup(&state, "bob", "acme").await;
+        signup(&state, "carol", "globex").await;
+        let input = SigninUserDto {
+            email: "bob@acme.org".to_owned(),
+            password: "wrong".to_owned(),
+            ws_id: None,
+        };
+        assert!(state.auth.signin(input, &client).await.is_err());
+        let input = CreateChatDto {
+            name: Some("general".to_owned()),
+            chat_type: ChatType::PublicChannel,
+            members: vec![alice.id, bob.id],
+        };
+        let chat = state.chat.create(&alice, input).await.unwrap();
+
+        let events = state
+            .audit
+            .list(alice.ws_id, Default::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            actions(&events),
+            vec!["chat_create", "signin_failed", "signup", "signup"]
+        );
+        assert_eq!(events[0].target, format!("chat:{}", chat.id));
+        assert_eq!(events[0].ip.as_deref(), Some("203.0.113.7"));
+        assert_eq!(events[1].user_agent.as_deref(), Some("curl/8.0"));
+
+        let input = AuditQueryDto {
+            actor_id: Some(bob.id),
+            ..Default::default()
+        };
+        let events = state.audit.list(alice.ws_id, input).await.unwrap();
+        assert_eq!(actions(&events), vec!["signin_failed", "signup"]);
+        let input = AuditQueryDto {
+            actor_id: Some(bob.id),
+            before_id: Some(events[0].id as _),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let page = state.audit.list(alice.ws_id, input).await.unwrap();
+        assert_eq!(page, events[1..]);
+
+        let input = AuditQueryDto {
+            action: Some("signup".to_owned()),
+            ..Default::default()
+        };
+        let csv = state.audit.export(&alice, input).await.unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.split_terminator("\r\n").collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(format!("{}\r\n", lines[0]), AuditEvent::CSV_HEADER);
+        let input = AuditQueryDto {
+            limit: Some(1),
+            ..Default::default()
+        };
+        let events = state.audit.list(alice.ws_id, input).await.unwrap();
+        assert_eq!(actions(&events), vec!["audit_export"]);
+    }
+}
@@ -1,35 +1,133 @@
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 
 use crate::{
-    common::utils::{hash_passwd, token::TokenSignVerify, verify_passwd},
-    domain::user::{User, UserRepo, Workspace},
+    common::utils::{hash_passwd, sha256_hex, token::TokenSignVerify, verify_passwd},
+    domain::{
+        audit::{AuditAction, AuditEvent, AuditRepo, ClientInfo},
+        bot::{BotRepo, BOT_TOKEN_PREFIX},
+        user::{User, UserRepo, Workspace, WsMember, WsRole},
+    },
     error::AppError,
 };
 
 pub struct AuthService {
     repo: Box<dyn UserRepo + Send + Sync>,
     tokensv: TokenSignVerify,
+    audit: Box<dyn AuditRepo + Send + Sync>,
+    bots: Box<dyn BotRepo + Send + Sync>,
 }
 
 impl AuthService {
-    pub fn new(repo: Box<dyn UserRepo + Send + Sync>, tokensv: TokenSignVerify) -> Self {
-        Self { repo, tokensv }
+    pub fn new(
+        repo: Box<dyn UserRepo + Send + Sync>,
+        tokensv: TokenSignVerify,
+        audit: Box<dyn AuditRepo + Send + Sync>,
+        bots: Box<dyn BotRepo + Send + Sync>,
+    ) -> Self {
+        Self {
+            repo,
+            tokensv,
+            audit,
+            bots,
+        }
+    }
+
+    /// a token is only valid while the account and the workspace membership are active,
+    /// api tokens of bots until they are revoked as well
+    pub async fn verify_token(&self, token: impl AsRef<str>) -> Result<ClaimUser, AppError> {
+        let token = token.as_ref();
+        let claim: ClaimUser = if token.starts_with(BOT_TOKEN_PREFIX) {
+            self.verify_bot_token(token).await?
+        } else {
+            self.tokensv.verify(token)?
+        };
+        let (user, member) = match (
+            self.repo.find_by_id(claim.id as _).await?,
+            self.repo
+                .find_member(claim.ws_id as _, claim.id as _)
+                .await?,
+        ) {
+            (Some(user), Some(member)) => (user, member),
+            _ => return Err(AppError::PermissionDenyError("token revoked".to_owned())),
+        };
+        if !user.is_active() || !member.is_active() || user.token_version != claim.ver {
+            return Err(AppError::PermissionDenyError("token revoked".to_owned()));
+        }
+        Ok(claim)
     }
 
-    pub fn verify_token(&self, token: impl AsRef<str>) -> Result<ClaimUser, AppError> {
-        self.tokensv.verify(token.as_ref())
+    async fn verify_bot_token(&self, token: &str) -> Result<ClaimUser, AppError> {
+        let token = self
+            .bots
+            .use_token(&sha256_hex(token))
+            .await?
+            .ok_or_else(|| AppError::PermissionDenyError("token revoked".to_owned()))?;
+        match self.repo.find_by_id(token.bot_id).await? {
+            Some(bot) => Ok(bot.into()),
+            None => Err(AppError::PermissionDenyError("token revoked".to_owned())),
+        }
     }
 
-    pub async fn signin(&self, user: SigninUserDto) -> Result<String, AppError> {
+    pub async fn signin(
+        &self,
+        user: SigninUserDto,
+        client: &ClientInfo,
+    ) -> Result<String, AppError> {
         let user_in_db = match self.repo.find_by_email(&user.email).await? {
             Some(user) => user,
             None => return Err(AppError::NotFound(user.email)),
         };
+        let ret = self.try_signin(&user, user_in_db.clone()).await;
+        let (action, detail) = match &ret {
+            Ok(_) => (AuditAction::Signin, json!({})),
+            Err(e) => (AuditAction::SigninFailed, json!({ "error": e.to_string() })),
+        };
+        let ws_id = match &ret {
+            Ok((_, ws_id)) => *ws_id,
+            Err(_) => user_in_db.ws_id,
+        };
+        let event = AuditEvent::new(
+            ws_id,
+            user_in_db.id,
+            action,
+            format!("user:{}", user_in_db.id),
+            detail,
+        )
+        .with_client(client);
+        self.audit.append(&event).await?;
+        ret.map(|(token, _)| token)
+    }
+
+    async fn try_signin(
+        &self,
+        user: &SigninUserDto,
+        user_in_db: User,
+    ) -> Result<(String, i64), AppError> {
+        if user_in_db.is_bot {
+            return Err(AppError::PermissionDenyError(
+                "bots sign in with api tokens".to_owned(),
+            ));
+        }
         verify_passwd(&user.password, &user_in_db.password_hash)?;
-        self.tokensv.sign(ClaimUser::from(user_in_db.clone()))
+        if !user_in_db.is_active() {
+            return Err(AppError::PermissionDenyError(
+                "account is deactivated".to_owned(),
+            ));
+        }
+        let ws_id = match user.ws_id {
+            Some(ws_id) => ws_id as i64,
+            None => self.default_ws(&user_in_db).await?,
+        };
+        let token = self.sign_for_ws(user_in_db, ws_id).await?;
+        Ok((token, ws_id))
     }
 
-    pub async fn signup(&self, input: SignupUserDto) -> Result<String, AppError> {
+    pub async fn signup(
+        &self,
+        input: SignupUserDto,
+        client: &ClientInfo,
+    ) -> Result<String, AppError> {
         match self.repo.find_by_email(&input.email).await {
             Ok(Some(_)) => return Err(AppError::ConflictError("email already exist".to_owned())),
             Ok(None) => {}
@@ -53,10 +151,89 @@ impl AuthService {
             ws_id: ws.id,
             fullname: input.fullname,
             email: input.email,
-            password_hash: password_hash,
+            password_hash,
             ..Default::default()
         };
         let user = self.repo.save(&user).await?;
+        // the first user of a new workspace becomes its owner
+        let role = if ws.owner_id == 0 {
+            self.repo
+                .update_ws(&Workspace {
+                    owner_id: user.id,
+                    ..ws
+                })
+                .await?;
+            WsRole::Admin
+        } else {
+            WsRole::Member
+        };
+        self.repo
+            .save_member(&WsMember::new(user.ws_id, user.id, role.clone()))
+            .await?;
+        let event = AuditEvent::new(
+            user.ws_id,
+            user.id,
+            AuditAction::Signup,
+            format!("user:{}", user.id),
+            json!({ "email": user.email, "role": role }),
+        )
+        .with_client(client);
+        self.audit.append(&event).await?;
+        self.tokensv.sign(ClaimUser::from(user))
+    }
+
+    /// issue a token for another workspace the user is a member of
+    pub async fn switch_ws(&self, user: &ClaimUser, ws_id: u64) -> Result<String, AppError> {
+        let user_in_db = match self.repo.find_by_id(user.id as _).await? {
+            Some(user) => user,
+            None => return Err(AppError::NotFound(user.id.to_string())),
+        };
+        let token = self.sign_for_ws(user_in_db, ws_id as _).await?;
+        let event = AuditEvent::new(
+            ws_id as _,
+            user.id as _,
+            AuditAction::WorkspaceSwitch,
+            format!("workspace:{}", ws_id),
+            json!({ "from": user.ws_id }),
+        )
+        .with_client(&user.client);
+        self.audit.append(&event).await?;
+        Ok(token)
+    }
+
+    async fn default_ws(&self, user: &User) -> Result<i64, AppError> {
+        match self.repo.find_member(user.ws_id, user.id).await? {
+            Some(member) if member.is_active() => Ok(user.ws_id),
+            _ => match self.repo.extract_user_ws(user.id).await?.first() {
+                Some(ws) => Ok(ws.id),
+                None => Err(AppError::PermissionDenyError(
+                    "account is not in any workspace".to_owned(),
+                )),
+            },
+        }
+    }
+
+    async fn sign_for_ws(&self, mut user: User, ws_id: i64) -> Result<String, AppError> {
+        // a bot stays in its workspace and keeps its api token
+        if user.is_bot {
+            return Err(AppError::PermissionDenyError(
+                "bots can not switch workspace".to_owned(),
+            ));
+        }
+        match self.repo.find_member(ws_id, user.id).await? {
+            Some(member) if member.is_active() => {}
+            Some(_) => {
+                return Err(AppError::PermissionDenyError(
+                    "account is deactivated".to_owned(),
+                ))
+            }
+            None => return Err(AppError::NotFound(format!("workspace {}", ws_id))),
+        }
+        // remember the last used workspace for the next signin
+        if user.ws_id != ws_id {
+            user.ws_id = ws_id;
+            user = self.repo.update(&user).await?;
+        }
         self.tokensv.sign(ClaimUser::from(user))
     }
 }
@@ -65,18 +242,26 @@ impl AuthService {
 pub struct SigninUserDto {
     pub email: String,
     pub password: String,
+    pub ws_id: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClaimUser {
     pub id: u64,
     pub ws_id: u64,
+    #[serde(default)]
+    pub ver: i32,
+    /// of the request, it is not part of the token
+    #[serde(skip)]
+    pub client: ClientInfo,
 }
 impl From<User> for ClaimUser {
     fn from(user: User) -> Self {
         Self {
             id: user.id as _,
             ws_id: user.ws_id as _,
+            ver: user.token_version,
+            client: Default::default(),
         }
     }
 }
@@ -88,3 +273,50 @@ pub struct SignupUserDto {
     pub workspace: String,
     pub password: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{
+        service::workspace::AddMemberDto,
+        test_util::{signup, state},
+    };
+
+    #[sqlx::test]
+    async fn member_should_switch_to_its_workspaces_only(pool: PgPool) {
+        let state = state(pool).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "globex").await;
+        let carol = signup(&state, "carol", "initech").await;
+        let input = AddMemberDto {
+            email: "alice@acme.org".to_owned(),
+            role: None,
+        };
+        state.workspace.add_member(&bob, input).await.unwrap();
+        let spaces = state.workspace.list_user_ws(alice.id).await.unwrap();
+        assert_eq!(spaces.len(), 2);
+
+        let token = state.auth.switch_ws(&alice, bob.ws_id).await.unwrap();
+        let claim = state.auth.verify_token(token).await.unwrap();
+        assert_eq!(claim.ws_id, bob.ws_id);
+        assert!(!state.workspace.is_admin(&claim).await.unwrap());
+        let ret = state.auth.switch_ws(&alice, carol.ws_id).await;
+        assert!(matches!(ret, Err(AppError::NotFound(_))));
+
+        // the last workspace used is the one signed in to
+        let input = SigninUserDto {
+            email: "alice@acme.org".to_owned(),
+            password: "123456".to_owned(),
+            ws_id: None,
+        };
+        let token = state
+            .auth
+            .signin(input, &ClientInfo::default())
+            .await
+            .unwrap();
+        let claim = state.auth.verify_token(token).await.unwrap();
+        assert_eq!(claim.ws_id, bob.ws_id);
+    }
+}
@@ -0,0 +1,357 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    common::utils::{random_hex, sha256_hex},
+    domain::{
+        audit::{AuditAction, AuditEvent, AuditRepo},
+        bot::{check_name, BotRepo, BotToken, IncomingWebhook, BOT_TOKEN_PREFIX},
+        user::User,
+    },
+    error::AppError,
+};
+
+use super::{auth::ClaimUser, chat::ChatService, user::ChatUserDto};
+
+const TOKEN_BYTES: usize = 32;
+/// incoming webhooks post to this path followed by their token
+pub const INCOMING_PATH: &str = "/api/hooks/";
+
+pub struct BotService {
+    repo: Box<dyn BotRepo + Send + Sync>,
+    audit: Box<dyn AuditRepo + Send + Sync>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateBotDto {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateBotTokenDto {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateIncomingWebhookDto {
+    pub chat_id: u64,
+}
+
+/// the token is only part of the response when it was just made
+#[derive(Debug, Serialize)]
+pub struct BotTokenDto {
+    #[serde(flatten)]
+    pub info: BotToken,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+/// the url is only part of the response when it was just made
+#[derive(Debug, Serialize)]
+pub struct IncomingWebhookDto {
+    #[serde(flatten)]
+    pub hook: IncomingWebhook,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+impl BotService {
+    pub fn new(
+        repo: Box<dyn BotRepo + Send + Sync>,
+        audit: Box<dyn AuditRepo + Send + Sync>,
+    ) -> Self {
+        Self { repo, audit }
+    }
+
+    pub async fn create(
+        &self,
+        actor: &ClaimUser,
+        input: CreateBotDto,
+    ) -> Result<ChatUserDto, AppError> {
+        let name = input.name.trim();
+        check_name(name)?;
+        // bots have no mailbox, the address only has to be unique
+        let email = format!("bot-{}@bots.invalid", random_hex(8));
+        let bot = self.repo.create_bot(actor.ws_id as _, name, &email).await?;
+        self.record(
+            actor,
+            AuditAction::BotCreate,
+            format!("user:{}", bot.id),
+            json!({ "name": bot.fullname }),
+        )
+        .await?;
+        Ok(bot.into())
+    }
+
+    pub async fn list(&self, ws_id: u64) -> Result<Vec<ChatUserDto>, AppError> {
+        let bots = self.repo.extract_bots(ws_id as _).await?;
+        Ok(bots.into_iter().map(Into::into).collect())
+    }
+
+    /// the bot can not be used anymore, its tokens and incoming webhooks are revoked
+    pub async fn deactivate(&self, actor: &ClaimUser, id: u64) -> Result<ChatUserDto, AppError> {
+        let bot = self.find(actor.ws_id, id).await?;
+        let bot = self.repo.deactivate_bot(bot.id).await?;
+        self.record(
+            actor,
+            AuditAction::BotDeactivate,
+            format!("user:{}", bot.id),
+            json!({ "name": bot.fullname }),
+        )
+        .await?;
+        Ok(bot.into())
+    }
+
+    pub async fn create_token(
+        &self,
+        actor: &ClaimUser,
+        bot_id: u64,
+        input: CreateBotTokenDto,
+    ) -> Result<BotTokenDto, AppError> {
+        let bot = self.find_active(actor.ws_id, bot_id).await?;
+        let token = format!("{}{}", BOT_TOKEN_PREFIX, random_hex(TOKEN_BYTES));
+        let info = BotToken::new(bot.id, actor.id as _, input.name, sha256_hex(&token));
+        info.validate()?;
+        let info = self.repo.save_token(&info).await?;
+        self.record(
+            actor,
+            AuditAction::BotTokenCreate,
+            format!("user:{}", bot.id),
+            json!({ "token_id": info.id, "name": info.name }),
+        )
+        .await?;
+        Ok(BotTokenDto {
+            info,
+            token: Some(token),
+        })
+    }
+
+    pub async fn list_tokens(&self, ws_id: u64, bot_id: u64) -> Result<Vec<BotToken>, AppError> {
+        let bot = self.find(ws_id, bot_id).await?;
+        self.repo.extract_tokens(bot.id).await
+    }
+
+    pub async fn revoke_token(
+        &self,
+        actor: &ClaimUser,
+        bot_id: u64,
+        id: u64,
+    ) -> Result<BotToken, AppError> {
+        let bot = self.find(actor.ws_id, bot_id).await?;
+        let token = self
+            .repo
+            .revoke_token(bot.id, id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("token {}", id)))?;
+        self.record(
+            actor,
+            AuditAction::BotTokenRevoke,
+            format!("user:{}", bot.id),
+            json!({ "token_id": token.id, "name": token.name }),
+        )
+        .await?;
+        Ok(token)
+    }
+
+    /// the bot has to be a member of the chat, it posts with the permissions it has there
+    pub async fn create_hook(
+        &self,
+        chat: &ChatService,
+        actor: &ClaimUser,
+        bot_id: u64,
+        input: CreateIncomingWebhookDto,
+    ) -> Result<IncomingWebhookDto, AppError> {
+        let bot = self.find_active(actor.ws_id, bot_id).await?;
+        if !chat.can_access(&bot.clone().into(), input.chat_id).await? {
+            return Err(AppError::InvalidError(format!(
+                "bot is not a member of chat {}",
+                input.chat_id
+            )));
+        }
+        let token = random_hex(TOKEN_BYTES);
+        let hook = IncomingWebhook::new(
+            actor.ws_id as _,
+            bot.id,
+            input.chat_id as _,
+            actor.id as _,
+            sha256_hex(&token),
+        );
+        let hook = self.repo.save_hook(&hook).await?;
+        self.record(
+            actor,
+            AuditAction::IncomingWebhookCreate,
+            format!("chat:{}", hook.chat_id),
+            json!({ "hook_id": hook.id, "bot_id": bot.id }),
+        )
+        .await?;
+        Ok(IncomingWebhookDto {
+            hook,
+            url: Some(format!("{}{}", INCOMING_PATH, token)),
+        })
+    }
+
+    pub async fn list_hooks(
+        &self,
+        ws_id: u64,
+        bot_id: u64,
+    ) -> Result<Vec<IncomingWebhook>, AppError> {
+        let bot = self.find(ws_id, bot_id).await?;
+        self.repo.extract_hooks(bot.id).await
+    }
+
+    pub async fn revoke_hook(
+        &self,
+        actor: &ClaimUser,
+        bot_id: u64,
+        id: u64,
+    ) -> Result<IncomingWebhook, AppError> {
+        let bot = self.find(actor.ws_id, bot_id).await?;
+        let hook = self
+            .repo
+            .revoke_hook(bot.id, id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("incoming webhook {}", id)))?;
+        self.record(
+            actor,
+            AuditAction::IncomingWebhookRevoke,
+            format!("chat:{}", hook.chat_id),
+            json!({ "hook_id": hook.id, "bot_id": bot.id }),
+        )
+        .await?;
+        Ok(hook)
+    }
+
+    /// the bot posting through the hook and the chat it posts to
+    pub async fn resolve_hook(&self, token: &str) -> Result<(ClaimUser, u64), AppError> {
+        let not_found = || AppError::NotFound("incoming webhook".to_owned());
+        let hook = self
+            .repo
+            .find_hook(&sha256_hex(token))
+            .await?
+            .ok_or_else(not_found)?;
+        let bot = self
+            .repo
+            .find_bot(hook.ws_id, hook.bot_id)
+            .await?
+            .filter(User::is_active)
+            .ok_or_else(not_found)?;
+        let mut claim = ClaimUser::from(bot);
+        claim.ws_id = hook.ws_id as _;
+        Ok((claim, hook.chat_id as _))
+    }
+
+    async fn find(&self, ws_id: u64, id: u64) -> Result<User, AppError> {
+        self.repo
+            .find_bot(ws_id as _, id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("bot {}", id)))
+    }
+
+    async fn find_active(&self, ws_id: u64, id: u64) -> Result<User, AppError> {
+        let bot = self.find(ws_id, id).await?;
+        if !bot.is_active() {
+            return Err(AppError::InvalidError(format!("bot {} is deactivated", id)));
+        }
+        Ok(bot)
+    }
+
+    async fn record(
+        &self,
+        actor: &ClaimUser,
+        action: AuditAction,
+        target: String,
+        detail: serde_json::Value,
+    ) -> Result<(), AppError> {
+        let event = AuditEvent::new(actor.ws_id as _, actor.id as _, action, target, detail)
+            .with_client(&actor.client);
+        self.audit.append(&event).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{
+        domain::chat::{ChatType, ContentType},
+        service::chat::{AddMembersDto, CreateChatDto, SendMsgDto},
+        test_util::{signup, state},
+    };
+
+    fn msg(content: &str) -> SendMsgDto {
+        SendMsgDto {
+            content: content.to_owned(),
+            content_type: ContentType::Plain,
+            client_id: None,
+            quote_id: None,
+            files: vec![],
+        }
+    }
+
+    #[sqlx::test]
+    async fn bot_should_post_with_its_token_or_hook_until_revoked(pool: PgPool) {
+        let state = state(pool).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let input = CreateChatDto {
+            name: Some("general".to_owned()),
+            chat_type: ChatType::PublicChannel,
+            members: vec![alice.id],
+        };
+        let chat_id = state.chat.create(&alice, input).await.unwrap().id as u64;
+        let input = CreateBotDto {
+            name: "deploy".to_owned(),
+        };
+        let bot = state.bot.create(&alice, input).await.unwrap();
+        let bot_id = bot.id as u64;
+        let input = CreateBotTokenDto {
+            name: "ci".to_owned(),
+        };
+        let token = state.bot.create_token(&alice, bot_id, input).await.unwrap();
+        let secret = token.token.unwrap();
+        let claim = state.auth.verify_token(&secret).await.unwrap();
+        assert_eq!((claim.id, claim.ws_id), (bot_id, alice.ws_id));
+
+        // a bot posts where it is a member only
+        assert!(!state.chat.can_access(&claim, chat_id).await.unwrap());
+        let input = CreateIncomingWebhookDto { chat_id };
+        let ret = state
+            .bot
+            .create_hook(&state.chat, &alice, bot_id, input)
+            .await;
+        assert!(matches!(ret, Err(AppError::InvalidError(_))));
+        let input = AddMembersDto {
+            members: vec![bot_id],
+        };
+        state
+            .chat
+            .add_members(&alice, chat_id, input)
+            .await
+            .unwrap();
+        let sent = state.chat.send_msg(&claim, chat_id, msg("deployed")).await;
+        assert!(sent.unwrap().bot);
+
+        let input = CreateIncomingWebhookDto { chat_id };
+        let hook = state
+            .bot
+            .create_hook(&state.chat, &alice, bot_id, input)
+            .await
+            .unwrap();
+        let url = hook.url.unwrap();
+        let hook_token = url.strip_prefix(INCOMING_PATH).unwrap();
+        let (poster, to) = state.bot.resolve_hook(hook_token).await.unwrap();
+        assert_eq!((poster.id, to), (bot_id, chat_id));
+
+        state
+            .bot
+            .revoke_token(&alice, bot_id, token.info.id as _)
+            .await
+            .unwrap();
+        let ret = state.auth.verify_token(&secret).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenyError(_))));
+        state.bot.deactivate(&alice, bot_id).await.unwrap();
+        let ret = state.bot.resolve_hook(hook_token).await;
+        assert!(matches!(ret, Err(AppError::NotFound(_))));
+    }
+}
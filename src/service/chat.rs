@@ -1,14 +1,41 @@
-use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::json;
+use tracing::warn;
 
 use crate::{
-    domain::chat::{Chat, ChatRepo, ChatType, Msg},
+    domain::{
+        audit::{AuditAction, AuditEvent, AuditRepo},
+        chat::{
+            Bookmark, ChannelSummary, Chat, ChatPrefs, ChatRepo, ChatStatus, ChatType, ContentType,
+            Msg, NotifLevel, Pin, PinnedMsg, UserChat,
+        },
+        command::{EphemeralMsg, SlashCommand},
+    },
     error::AppError,
 };
 
-use super::auth::ClaimUser;
+use super::{
+    auth::ClaimUser, command::CommandService, file::FileService, rate_limit::RateLimitService,
+    user::apply, workspace::WorkspaceService,
+};
+
+/// deleted chats purged per run of the purge job
+const PURGE_BATCH: i64 = 100;
 
 pub struct ChatService {
     repo: Box<dyn ChatRepo + Send + Sync>,
+    audit: Box<dyn AuditRepo + Send + Sync>,
+    /// holds members to the slow mode of the chat they post to
+    limits: Arc<RateLimitService>,
+    /// runs the commands posted to a chat
+    commands: Arc<CommandService>,
+    /// workspace admins manage every chat
+    workspace: Arc<WorkspaceService>,
+    /// how long a deleted chat can be restored before it is purged
+    restore_window: Duration,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -34,9 +61,90 @@ pub struct UpdateChatDto {
     pub members: Vec<u64>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddMembersDto {
+    pub members: Vec<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateHeaderDto {
+    pub topic: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PinMsgDto {
+    pub msg_id: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddBookmarkDto {
+    pub title: String,
+    pub url: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SendMsgDto {
     pub content: String,
+    #[serde(default)]
+    pub content_type: ContentType,
+    /// the `Idempotency-Key` header is used when omitted
+    #[serde(default)]
+    pub client_id: Option<String>,
+    /// a message of the same chat to quote
+    #[serde(default)]
+    pub quote_id: Option<u64>,
+    /// urls of files the sender uploaded, attached to the message
+    #[serde(default)]
+    pub files: Vec<String>,
+}
+
+/// a message posted to the chat, or the reply to a command only its sender sees
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", content = "msg", rename_all = "snake_case")]
+pub enum PostOutput {
+    Posted(Msg),
+    Ephemeral(EphemeralMsg),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SlowModeDto {
+    /// between two messages of a member, 0 turns slow mode off
+    pub seconds: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ForwardMsgDto {
+    /// the chat to forward to
+    pub chat_id: u64,
+}
+
+/// omitted fields keep their value, `"muted_until": null` unmutes
+#[derive(Debug, Deserialize)]
+pub struct UpdateChatPrefsDto {
+    #[serde(default, deserialize_with = "double_option")]
+    pub muted_until: Option<Option<DateTime<Utc>>>,
+    pub favorite: Option<bool>,
+    pub hidden: Option<bool>,
+    pub notif_level: Option<NotifLevel>,
+}
+
+fn double_option<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListChatsDto {
+    pub status: Option<ChatStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchChannelsDto {
+    pub q: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -45,73 +153,1456 @@ pub struct ListOptionsDto {
     pub limit: Option<u8>,
 }
 
+/// at most one cursor, the latest messages are listed without one
+#[derive(Debug, Default, Deserialize)]
+pub struct ListMessagesDto {
+    #[serde(alias = "last_id")]
+    pub before: Option<u64>,
+    pub after: Option<u64>,
+    /// the page is centered on this message, which is included
+    pub around: Option<u64>,
+    pub limit: Option<u8>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MessagePage {
+    /// newest first whatever the cursor
+    pub messages: Vec<Msg>,
+    pub has_more_before: bool,
+    pub has_more_after: bool,
+}
+
+const DEFAULT_PAGE_SIZE: u8 = 10;
+/// files attached to one message
+const MAX_FILES: usize = 10;
+
 impl ChatService {
-    pub fn new(repo: Box<dyn ChatRepo + Send + Sync>) -> Self {
-        Self { repo }
+    pub fn new(
+        repo: Box<dyn ChatRepo + Send + Sync>,
+        audit: Box<dyn AuditRepo + Send + Sync>,
+        limits: Arc<RateLimitService>,
+        commands: Arc<CommandService>,
+        workspace: Arc<WorkspaceService>,
+        purge_after_days: u32,
+    ) -> Self {
+        Self {
+            repo,
+            audit,
+            limits,
+            commands,
+            workspace,
+            restore_window: Duration::days(purge_after_days as _),
+        }
     }
 
     pub async fn get_by_id(&self, chat_id: u64) -> Result<Option<Chat>, AppError> {
-        self.repo.extract_by_id(chat_id as i64).await
+        Ok(self
+            .repo
+            .extract_by_id(chat_id as i64)
+            .await?
+            .filter(Chat::is_readable))
+    }
+
+    pub async fn can_access(&self, user: &ClaimUser, chat_id: u64) -> Result<bool, AppError> {
+        self.repo
+            .is_member(chat_id as _, user.ws_id as _, user.id as _)
+            .await
+    }
+
+    /// members of the workspace may read public channels without joining
+    pub async fn can_preview(&self, user: &ClaimUser, chat_id: u64) -> Result<bool, AppError> {
+        self.repo
+            .is_public_channel(chat_id as _, user.ws_id as _)
+            .await
+    }
+
+    pub async fn list_channels(
+        &self,
+        user: &ClaimUser,
+        input: SearchChannelsDto,
+    ) -> Result<Vec<ChannelSummary>, AppError> {
+        let query = input.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+        self.repo
+            .extract_public_channels(user.ws_id as _, user.id as _, query)
+            .await
+    }
+
+    pub async fn join(&self, user: &ClaimUser, chat_id: u64) -> Result<Chat, AppError> {
+        let chat = self.find_active_chat(chat_id).await?;
+        if chat.chat_type != ChatType::PublicChannel {
+            return Err(AppError::InvalidError(
+                "only public channels can be joined".to_owned(),
+            ));
+        }
+        if chat.members.contains(&(user.id as i64)) {
+            return Ok(chat);
+        }
+        self.check_not_banned(chat.id, &[user.id as _]).await?;
+        let chat = self.repo.add_members(chat.id, vec![user.id as _]).await?;
+        self.record(user, AuditAction::ChatJoin, chat.id, json!({}))
+            .await?;
+        Ok(chat)
+    }
+
+    pub async fn archive(&self, user: &ClaimUser, chat_id: u64) -> Result<Chat, AppError> {
+        let chat = self.find_chat(chat_id).await?;
+        self.check_can_manage(user, &chat).await?;
+        let chat = self.transit(chat, ChatStatus::Archived).await?;
+        self.record(user, AuditAction::ChatArchive, chat.id, json!({}))
+            .await?;
+        Ok(chat)
     }
 
-    pub async fn delete(&self, chat_id: u64) -> Result<Chat, AppError> {
-        self.repo.delete_by_id(chat_id as i64).await
+    /// unarchive, or undelete within the restore window
+    pub async fn restore(&self, user: &ClaimUser, chat_id: u64) -> Result<Chat, AppError> {
+        let chat = self.find_chat(chat_id).await?;
+        self.check_can_manage(user, &chat).await?;
+        if chat.status == ChatStatus::Deleted
+            && chat.status_changed_at + self.restore_window < Utc::now()
+        {
+            return Err(AppError::InvalidError(format!(
+                "chat {} can no longer be restored",
+                chat_id
+            )));
+        }
+        if let (true, Some(name)) = (chat.chat_type.is_channel(), &chat.name) {
+            if self
+                .repo
+                .is_channel_name_taken(chat.ws_id, name, chat.id)
+                .await?
+            {
+                return Err(AppError::ConflictError(format!("channel {} exist", name)));
+            }
+        }
+        let from = chat.status;
+        let chat = self.transit(chat, ChatStatus::Active).await?;
+        self.record(
+            user,
+            AuditAction::ChatRestore,
+            chat.id,
+            json!({ "from": from }),
+        )
+        .await?;
+        Ok(chat)
     }
 
-    pub async fn update(&self, chat_id: u64, input: UpdateChatDto) -> Result<Chat, AppError> {
-        let mut chat = self.repo.extract_by_id(chat_id as i64).await?.unwrap();
+    pub async fn delete(&self, user: &ClaimUser, chat_id: u64) -> Result<Chat, AppError> {
+        let chat = self.find_chat(chat_id).await?;
+        self.check_can_manage(user, &chat).await?;
+        let chat = self.transit(chat, ChatStatus::Deleted).await?;
+        self.record(user, AuditAction::ChatDelete, chat.id, json!({}))
+            .await?;
+        Ok(chat)
+    }
+
+    /// hard delete by a workspace admin, skipping the restore window
+    pub async fn purge(
+        &self,
+        files: &FileService,
+        user: &ClaimUser,
+        chat_id: u64,
+    ) -> Result<Chat, AppError> {
+        let chat = self.find_chat(chat_id).await?;
+        if chat.ws_id != user.ws_id as i64 || chat.status == ChatStatus::Purged {
+            return Err(AppError::NotFound(format!("chat {}", chat_id)));
+        }
+        let chat = self
+            .purge_chat(files, &chat)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("chat {}", chat_id)))?;
+        self.record(
+            user,
+            AuditAction::ChatPurge,
+            chat.id,
+            json!({ "name": chat.name }),
+        )
+        .await?;
+        Ok(chat)
+    }
+
+    /// purge chats deleted longer than the restore window ago, returns how many were purged
+    pub async fn purge_expired(&self, files: &FileService) -> Result<usize, AppError> {
+        let before = Utc::now() - self.restore_window;
+        let chats = self
+            .repo
+            .extract_expired(ChatStatus::Deleted, before, PURGE_BATCH)
+            .await?;
+        let mut purged = 0;
+        for chat in chats {
+            match self.purge_chat(files, &chat).await {
+                Ok(Some(_)) => purged += 1,
+                Ok(None) => {}
+                Err(e) => warn!("Failed to purge chat {}, error: {}", chat.id, e),
+            }
+        }
+        Ok(purged)
+    }
+
+    async fn purge_chat(&self, files: &FileService, chat: &Chat) -> Result<Option<Chat>, AppError> {
+        // files are found through the messages, so they go first
+        files
+            .erase_chat_files(chat.id as _, chat.ws_id as _)
+            .await?;
+        self.repo.purge(chat.id).await
+    }
+
+    async fn transit(&self, chat: Chat, to: ChatStatus) -> Result<Chat, AppError> {
+        if !chat.status.can_transit_to(to) {
+            return Err(AppError::InvalidError(format!(
+                "chat {} can not change from {:?} to {:?}",
+                chat.id, chat.status, to
+            )));
+        }
+        self.repo.update_status(chat.id, to).await
+    }
+
+    pub async fn update(
+        &self,
+        user: &ClaimUser,
+        chat_id: u64,
+        input: UpdateChatDto,
+    ) -> Result<Chat, AppError> {
+        let mut chat = self.find_active_chat(chat_id).await?;
+        self.check_can_manage(user, &chat).await?;
+        if !chat.chat_type.can_convert_to(&input.chat_type) {
+            return Err(AppError::InvalidError(format!(
+                "chat type {:?} can not change to {:?}",
+                chat.chat_type, input.chat_type
+            )));
+        }
+        let mut members: Vec<i64> = input.members.into_iter().map(|v| v as i64).collect();
+        members.sort();
+        members.dedup();
+        if chat.chat_type == ChatType::Single && members != chat.members {
+            return Err(AppError::InvalidError(
+                "members of a single chat can not change".to_owned(),
+            ));
+        }
+        if !self
+            .repo
+            .is_members_exist(chat.ws_id, members.clone())
+            .await?
+        {
+            return Err(AppError::InvalidError("members should exist".to_owned()));
+        }
+        let added: Vec<i64> = members
+            .iter()
+            .filter(|v| !chat.members.contains(v))
+            .cloned()
+            .collect();
+        self.check_not_banned(chat.id, &added).await?;
         chat.name = input.name;
         chat.chat_type = input.chat_type;
-        chat.members = input.members.into_iter().map(|v| v as i64).collect();
-        self.repo.save(&chat).await
+        chat.members = members;
+        self.check_rules(&mut chat).await?;
+        let chat = self.repo.save(&chat).await?;
+        self.record(
+            user,
+            AuditAction::ChatUpdate,
+            chat.id,
+            json!({ "name": chat.name, "chat_type": chat.chat_type, "members": chat.members }),
+        )
+        .await?;
+        Ok(chat)
+    }
+
+    pub async fn add_members(
+        &self,
+        user: &ClaimUser,
+        chat_id: u64,
+        input: AddMembersDto,
+    ) -> Result<Chat, AppError> {
+        let chat = self.find_active_chat(chat_id).await?;
+        Self::check_members_mutable(&chat)?;
+        let mut members: Vec<i64> = input.members.into_iter().map(|v| v as i64).collect();
+        members.sort();
+        members.dedup();
+        if members.is_empty() {
+            return Err(AppError::InvalidError("members is empty".to_owned()));
+        }
+        if !self
+            .repo
+            .is_members_exist(chat.ws_id, members.clone())
+            .await?
+        {
+            return Err(AppError::InvalidError("members should exist".to_owned()));
+        }
+        self.check_not_banned(chat.id, &members).await?;
+        let chat = self.repo.add_members(chat.id, members.clone()).await?;
+        self.record(
+            user,
+            AuditAction::ChatMemberAdd,
+            chat.id,
+            json!({ "members": members }),
+        )
+        .await?;
+        Ok(chat)
     }
-    pub async fn create(
+
+    pub async fn remove_member(
         &self,
+        user: &ClaimUser,
+        chat_id: u64,
         user_id: u64,
-        ws_id: u64,
-        input: CreateChatDto,
     ) -> Result<Chat, AppError> {
+        let chat = self.find_active_chat(chat_id).await?;
+        // the others leave the chat on their own
+        self.check_can_manage(user, &chat).await?;
+        let chat = self.remove(chat, user_id).await?;
+        self.record(
+            user,
+            AuditAction::ChatMemberRemove,
+            chat.id,
+            json!({ "user_id": user_id }),
+        )
+        .await?;
+        Ok(chat)
+    }
+
+    pub async fn leave(&self, user: &ClaimUser, chat_id: u64) -> Result<Chat, AppError> {
+        let chat = self.find_active_chat(chat_id).await?;
+        let chat = self.remove(chat, user.id).await?;
+        self.record(user, AuditAction::ChatLeave, chat.id, json!({}))
+            .await?;
+        Ok(chat)
+    }
+
+    async fn remove(&self, chat: Chat, user_id: u64) -> Result<Chat, AppError> {
+        Self::check_members_mutable(&chat)?;
+        if !chat.members.contains(&(user_id as i64)) {
+            return Err(AppError::NotFound(format!("member {}", user_id)));
+        }
+        self.repo.remove_member(chat.id, user_id as _).await
+    }
+
+    pub async fn update_prefs(
+        &self,
+        user: &ClaimUser,
+        chat_id: u64,
+        input: UpdateChatPrefsDto,
+    ) -> Result<ChatPrefs, AppError> {
+        self.find_readable_chat(chat_id).await?;
+        let mut prefs = self
+            .repo
+            .extract_prefs(chat_id as _, user.id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("member {}", user.id)))?;
+        if let Some(muted_until) = input.muted_until {
+            prefs.muted_until = muted_until.filter(|until| *until > Utc::now());
+        }
+        if let Some(favorite) = input.favorite {
+            prefs.favorite = favorite;
+        }
+        if let Some(hidden) = input.hidden {
+            prefs.hidden = hidden;
+        }
+        if let Some(notif_level) = input.notif_level {
+            prefs.notif_level = notif_level;
+        }
+        self.repo
+            .update_prefs(chat_id as _, user.id as _, &prefs)
+            .await
+    }
+
+    pub async fn update_header(
+        &self,
+        chat_id: u64,
+        input: UpdateHeaderDto,
+    ) -> Result<Chat, AppError> {
+        let mut chat = self.find_active_chat(chat_id).await?;
+        apply(&mut chat.topic, input.topic, 256, "topic")?;
+        apply(
+            &mut chat.description,
+            input.description,
+            1024,
+            "description",
+        )?;
+        self.repo
+            .update_header(chat.id, chat.topic, chat.description)
+            .await
+    }
+
+    pub async fn pin(
+        &self,
+        user: &ClaimUser,
+        chat_id: u64,
+        input: PinMsgDto,
+    ) -> Result<Pin, AppError> {
+        self.find_active_chat(chat_id).await?;
+        if self
+            .repo
+            .extract_msg(chat_id as _, input.msg_id as _)
+            .await?
+            .is_none()
+        {
+            return Err(AppError::NotFound(format!("message {}", input.msg_id)));
+        }
+        let pin = Pin::new(chat_id as _, input.msg_id as _, user.id as _);
+        self.repo.save_pin(&pin).await
+    }
+
+    pub async fn unpin(&self, chat_id: u64, msg_id: u64) -> Result<Pin, AppError> {
+        self.find_active_chat(chat_id).await?;
+        self.repo
+            .delete_pin(chat_id as _, msg_id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("pin {}", msg_id)))
+    }
+
+    pub async fn list_pins(&self, chat_id: u64) -> Result<Vec<PinnedMsg>, AppError> {
+        self.find_readable_chat(chat_id).await?;
+        self.repo.extract_pins(chat_id as _).await
+    }
+
+    pub async fn add_bookmark(
+        &self,
+        user: &ClaimUser,
+        chat_id: u64,
+        input: AddBookmarkDto,
+    ) -> Result<Bookmark, AppError> {
+        self.find_active_chat(chat_id).await?;
+        let mut bookmark = Bookmark::new(chat_id as _, input.title, input.url, user.id as _);
+        bookmark.validate()?;
+        self.repo.save_bookmark(&bookmark).await
+    }
+
+    pub async fn remove_bookmark(&self, chat_id: u64, id: u64) -> Result<Bookmark, AppError> {
+        self.find_active_chat(chat_id).await?;
+        self.repo
+            .delete_bookmark(chat_id as _, id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("bookmark {}", id)))
+    }
+
+    pub async fn list_bookmarks(&self, chat_id: u64) -> Result<Vec<Bookmark>, AppError> {
+        self.find_readable_chat(chat_id).await?;
+        self.repo.extract_bookmarks(chat_id as _).await
+    }
+
+    async fn find_chat(&self, chat_id: u64) -> Result<Chat, AppError> {
+        self.repo
+            .extract_by_id(chat_id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("chat {}", chat_id)))
+    }
+
+    async fn find_readable_chat(&self, chat_id: u64) -> Result<Chat, AppError> {
+        let chat = self.find_chat(chat_id).await?;
+        if !chat.is_readable() {
+            return Err(AppError::NotFound(format!("chat {}", chat_id)));
+        }
+        Ok(chat)
+    }
+
+    /// archived chats are read-only
+    pub async fn find_active_chat(&self, chat_id: u64) -> Result<Chat, AppError> {
+        let chat = self.find_readable_chat(chat_id).await?;
+        if chat.status == ChatStatus::Archived {
+            return Err(AppError::InvalidError(format!(
+                "chat {} is archived",
+                chat_id
+            )));
+        }
+        Ok(chat)
+    }
+
+    /// the chat must be active, the user not muted by a moderator and not posting faster
+    /// than the slow mode of the chat, every way of posting to a chat goes through it
+    pub async fn check_can_post(&self, user: &ClaimUser, chat_id: u64) -> Result<Chat, AppError> {
+        let chat = self.find_active_chat(chat_id).await?;
+        if let Some(muted_until) = self.repo.extract_muted_until(chat.id, user.id as _).await? {
+            return Err(AppError::PermissionDenyError(format!(
+                "muted in chat {} until {}",
+                chat_id, muted_until
+            )));
+        }
+        let secs = self.slow_mode(chat_id).await?;
+        if secs > 0 {
+            self.limits.slow_mode(chat_id, user.id, secs).await?;
+        }
+        Ok(chat)
+    }
+
+    /// seconds a member waits between two messages, 0 when slow mode is off
+    pub async fn slow_mode(&self, chat_id: u64) -> Result<u32, AppError> {
+        let secs = self
+            .repo
+            .extract_slow_mode(chat_id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("chat {}", chat_id)))?;
+        Ok(secs as _)
+    }
+
+    /// active members of the workspace
+    pub async fn check_members_exist(&self, ws_id: u64, members: &[i64]) -> Result<(), AppError> {
+        if !self
+            .repo
+            .is_members_exist(ws_id as _, members.to_vec())
+            .await?
+        {
+            return Err(AppError::InvalidError("members should exist".to_owned()));
+        }
+        Ok(())
+    }
+
+    /// ids of the active members of the workspace written as `@handle`
+    pub async fn resolve_handles(
+        &self,
+        ws_id: u64,
+        handles: &[String],
+    ) -> Result<Vec<u64>, AppError> {
+        let users = self.repo.resolve_handles(ws_id as _, handles).await?;
+        if let Some(handle) = handles
+            .iter()
+            .find(|handle| !users.iter().any(|(_, v)| v == *handle))
+        {
+            return Err(AppError::NotFound(format!("member @{}", handle)));
+        }
+        Ok(users.into_iter().map(|(id, _)| id as _).collect())
+    }
+
+    async fn check_not_banned(&self, chat_id: i64, user_ids: &[i64]) -> Result<(), AppError> {
+        if user_ids.is_empty() {
+            return Ok(());
+        }
+        let banned = self.repo.extract_banned(chat_id, user_ids).await?;
+        if !banned.is_empty() {
+            return Err(AppError::PermissionDenyError(format!(
+                "users {:?} are banned from chat {}",
+                banned, chat_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// the creator of the chat or a workspace admin, both members own a direct message
+    async fn check_can_manage(&self, user: &ClaimUser, chat: &Chat) -> Result<(), AppError> {
+        let user_id = user.id as i64;
+        if chat.created_by == Some(user_id)
+            || (chat.chat_type == ChatType::Single && chat.members.contains(&user_id))
+            || self.workspace.is_admin(user).await?
+        {
+            return Ok(());
+        }
+        Err(AppError::PermissionDenyError(format!(
+            "only the creator of chat {} or a workspace admin can change it",
+            chat.id
+        )))
+    }
+
+    fn check_members_mutable(chat: &Chat) -> Result<(), AppError> {
+        if chat.chat_type == ChatType::Single {
+            return Err(AppError::InvalidError(
+                "members of a single chat can not change".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// type rules of the chat plus channel names unique within the workspace
+    async fn check_rules(&self, chat: &mut Chat) -> Result<(), AppError> {
+        chat.validate()?;
+        if let (true, Some(name)) = (chat.chat_type.is_channel(), &chat.name) {
+            if self
+                .repo
+                .is_channel_name_taken(chat.ws_id, name, chat.id)
+                .await?
+            {
+                return Err(AppError::ConflictError(format!("channel {} exist", name)));
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn create(&self, user: &ClaimUser, input: CreateChatDto) -> Result<Chat, AppError> {
         // validate
-        if !input.members.contains(&user_id) {
+        if !input.members.contains(&user.id) {
             return Err(AppError::InvalidError(
                 "current user should in members".to_owned(),
             ));
         }
+        let mut input = Chat::new(
+            user.ws_id as i64,
+            user.id as i64,
+            input.name,
+            input.chat_type,
+            input.members,
+        );
+        self.check_rules(&mut input).await?;
         if !self
             .repo
-            .is_members_exist(input.members.iter().map(|v| *v as i64).collect())
+            .is_members_exist(input.ws_id, input.members.clone())
             .await?
         {
             return Err(AppError::InvalidError("members should exist".to_owned()));
         }
-        let input = Chat::new(ws_id as i64, input.name, input.chat_type, input.members);
-        self.repo.save(&input).await
+        if input.chat_type == ChatType::Single {
+            // opening a direct message again returns the existing one
+            return self.repo.find_or_create_single(&input).await;
+        }
+        let chat = self.repo.save(&input).await?;
+        self.record(
+            user,
+            AuditAction::ChatCreate,
+            chat.id,
+            json!({ "name": chat.name, "chat_type": chat.chat_type, "members": chat.members }),
+        )
+        .await?;
+        Ok(chat)
     }
 
-    pub async fn list_all(&self, user_id: u64, ws_id: u64) -> Result<Vec<Chat>, AppError> {
+    async fn record(
+        &self,
+        actor: &ClaimUser,
+        action: AuditAction,
+        chat_id: i64,
+        detail: serde_json::Value,
+    ) -> Result<(), AppError> {
+        let event = AuditEvent::new(
+            actor.ws_id as _,
+            actor.id as _,
+            action,
+            format!("chat:{}", chat_id),
+            detail,
+        )
+        .with_client(&actor.client);
+        self.audit.append(&event).await?;
+        Ok(())
+    }
+
+    pub async fn list_all(
+        &self,
+        user_id: u64,
+        ws_id: u64,
+        input: ListChatsDto,
+    ) -> Result<Vec<UserChat>, AppError> {
+        let statuses = match input.status {
+            Some(status) => vec![status],
+            None => vec![ChatStatus::Active, ChatStatus::Archived],
+        };
         self.repo
-            .extract_all_chat(user_id as i64, ws_id as i64)
+            .extract_all_chat(user_id as i64, ws_id as i64, &statuses)
             .await
     }
 
+    /// post a message a member sends, a message starting with a command runs the command
+    /// instead
+    pub async fn post_msg(
+        &self,
+        user: &ClaimUser,
+        chat_id: u64,
+        input: SendMsgDto,
+    ) -> Result<PostOutput, AppError> {
+        if input.content_type != ContentType::Poll {
+            if let Some(cmd) = SlashCommand::parse(&input.content) {
+                check_client_id(input.client_id.as_deref())?;
+                return self
+                    .commands
+                    .run(self, user, chat_id, cmd, input.client_id)
+                    .await;
+            }
+        }
+        let msg = self.send_msg(user, chat_id, input).await?;
+        Ok(PostOutput::Posted(msg))
+    }
+
+    /// store the message as it is, for command replies, copies, scheduled messages and
+    /// incoming webhooks which never run commands
     pub async fn send_msg(
         &self,
         user: &ClaimUser,
         chat_id: u64,
         input: SendMsgDto,
     ) -> Result<Msg, AppError> {
-        let input = Msg::new(chat_id as _, user.id as _, input.content);
-        self.repo.store_msg(&input).await
+        let SendMsgDto {
+            content,
+            content_type,
+            client_id,
+            quote_id,
+            files,
+        } = input;
+        check_client_id(client_id.as_deref())?;
+        // a retry gets the message it already sent, whatever slow mode says by now
+        if let Some(client_id) = &client_id {
+            let sent = self
+                .repo
+                .extract_by_client_id(chat_id as _, user.id as _, client_id)
+                .await?;
+            if let Some(sent) = sent {
+                return Ok(sent);
+            }
+        }
+        self.check_can_post(user, chat_id).await?;
+        if content_type == ContentType::Poll {
+            return Err(AppError::InvalidError(
+                "polls are created with their options".to_owned(),
+            ));
+        }
+        let mut input = Msg::new(chat_id as _, user.id as _, content);
+        input.content_type = content_type;
+        input.client_id = client_id;
+        if let Some(quote_id) = quote_id {
+            let quoted = self.repo.extract_msg(chat_id as _, quote_id as _).await?;
+            input.quote_id = Some(
+                quoted
+                    .ok_or_else(|| {
+                        AppError::InvalidError(format!("message {} is not in this chat", quote_id))
+                    })?
+                    .id,
+            );
+        }
+        input.render();
+        // unknown handles stay plain text
+        let (handles, broadcast) = input.parse_mentions();
+        if !handles.is_empty() {
+            input.mentions = self.repo.resolve_mentions(input.chat_id, &handles).await?;
+        }
+        input.mention_broadcast = broadcast;
+        let files = self.resolve_files(user, &files).await?;
+        // previews are added later by the unfurl worker
+        let links = input.extract_links();
+        self.repo.store_msg(&input, &links, &files).await
     }
 
-    pub async fn list_messages(
+    /// ids of the files of the urls, each must have been uploaded by the user
+    async fn resolve_files(&self, user: &ClaimUser, urls: &[String]) -> Result<Vec<i64>, AppError> {
+        if urls.is_empty() {
+            return Ok(vec![]);
+        }
+        if urls.len() > MAX_FILES {
+            return Err(AppError::InvalidError(format!(
+                "at most {} files can be attached",
+                MAX_FILES
+            )));
+        }
+        let files = self
+            .repo
+            .resolve_files(user.ws_id as _, user.id as _, urls)
+            .await?;
+        if let Some(url) = urls
+            .iter()
+            .find(|url| !files.iter().any(|(v, _)| v == *url))
+        {
+            return Err(AppError::InvalidError(format!(
+                "file {} was not uploaded by you",
+                url
+            )));
+        }
+        Ok(files.into_iter().map(|(_, id)| id).collect())
+    }
+
+    /// forward a message of a chat the user can read to a chat the user belongs to
+    pub async fn forward_msg(
         &self,
+        user: &ClaimUser,
         chat_id: u64,
+        msg_id: u64,
+        input: ForwardMsgDto,
+    ) -> Result<Msg, AppError> {
+        let source = self.get_msg(chat_id, msg_id).await?;
+        if !self.can_access(user, input.chat_id).await? {
+            return Err(AppError::NotFound(format!("chat {}", input.chat_id)));
+        }
+        self.check_can_post(user, input.chat_id).await?;
+        let msg = source.forward(input.chat_id as _, user.id as _);
+        // the copy shares the files of the original
+        let files = self.repo.extract_msg_files(source.id).await?;
+        self.repo.store_msg(&msg, &[], &files).await
+    }
+
+    /// messages of the workspace mentioning the user, newest first
+    pub async fn list_mentions(
+        &self,
+        user: &ClaimUser,
         last_id: u64,
         limit: u64,
     ) -> Result<Vec<Msg>, AppError> {
         self.repo
-            .extract_messages(chat_id as i64, last_id as i64, limit as i64)
+            .extract_mentions(user.id as _, user.ws_id as _, last_id as _, limit as _)
+            .await
+    }
+
+    pub async fn get_msg(&self, chat_id: u64, msg_id: u64) -> Result<Msg, AppError> {
+        self.find_readable_chat(chat_id).await?;
+        self.repo
+            .extract_msg(chat_id as _, msg_id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("message {}", msg_id)))
+    }
+
+    pub async fn list_messages(
+        &self,
+        chat_id: u64,
+        input: ListMessagesDto,
+    ) -> Result<MessagePage, AppError> {
+        self.find_readable_chat(chat_id).await?;
+        let chat_id = chat_id as i64;
+        let limit = input.limit.unwrap_or(DEFAULT_PAGE_SIZE).max(1) as i64;
+        let (older, newer, has_more_before, has_more_after) =
+            match (input.before, input.after, input.around) {
+                (None, None, None) => {
+                    let (older, more) = self.older_than(chat_id, i64::MAX, limit).await?;
+                    (older, vec![], more, false)
+                }
+                (Some(before), None, None) => {
+                    let before = cursor(before)?;
+                    let (older, more) = self.older_than(chat_id, before, limit).await?;
+                    let (_, newer) = self.newer_than(chat_id, before - 1, 0).await?;
+                    (older, vec![], more, newer)
+                }
+                (None, Some(after), None) => {
+                    let after = cursor(after)?;
+                    let (newer, more) = self.newer_than(chat_id, after, limit).await?;
+                    let (_, older) = self.older_than(chat_id, after.saturating_add(1), 0).await?;
+                    (vec![], newer, older, more)
+                }
+                (None, None, Some(around)) => {
+                    let around = cursor(around)?;
+                    if self.repo.extract_msg(chat_id, around).await?.is_none() {
+                        return Err(AppError::NotFound(format!("message {}", around)));
+                    }
+                    let (older, more_before) = self.older_than(chat_id, around, limit / 2).await?;
+                    let (newer, more_after) = self
+                        .newer_than(chat_id, around - 1, limit - limit / 2)
+                        .await?;
+                    (older, newer, more_before, more_after)
+                }
+                _ => {
+                    return Err(AppError::InvalidError(
+                        "only one of before, after and around is allowed".to_owned(),
+                    ))
+                }
+            };
+        let mut messages = newer;
+        messages.reverse();
+        messages.extend(older);
+        Ok(MessagePage {
+            messages,
+            has_more_before,
+            has_more_after,
+        })
+    }
+
+    /// up to `limit` messages older than `before_id` and whether there are more
+    async fn older_than(
+        &self,
+        chat_id: i64,
+        before_id: i64,
+        limit: i64,
+    ) -> Result<(Vec<Msg>, bool), AppError> {
+        let mut msgs = self
+            .repo
+            .extract_messages(chat_id, before_id, limit + 1)
+            .await?;
+        let more = msgs.len() as i64 > limit;
+        msgs.truncate(limit as _);
+        Ok((msgs, more))
+    }
+
+    /// up to `limit` messages newer than `after_id` and whether there are more
+    async fn newer_than(
+        &self,
+        chat_id: i64,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<(Vec<Msg>, bool), AppError> {
+        let mut msgs = self
+            .repo
+            .extract_messages_after(chat_id, after_id, limit + 1)
+            .await?;
+        let more = msgs.len() as i64 > limit;
+        msgs.truncate(limit as _);
+        Ok((msgs, more))
+    }
+}
+
+/// message ids are positive i64 in the database
+fn cursor(id: u64) -> Result<i64, AppError> {
+    i64::try_from(id).map_err(|_| AppError::InvalidError(format!("invalid message id {}", id)))
+}
+
+/// printable ascii, at most 64 characters
+fn check_client_id(client_id: Option<&str>) -> Result<(), AppError> {
+    match client_id {
+        Some(client_id)
+            if client_id.is_empty()
+                || client_id.len() > 64
+                || !client_id.chars().all(|c| c.is_ascii_graphic()) =>
+        {
+            Err(AppError::InvalidError(format!(
+                "invalid client message id {}",
+                client_id
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{
+        service::moderation::ModerateDto,
+        test_util::{signup, state},
+        AppState,
+    };
+
+    fn msg(content: &str, client_id: &str) -> SendMsgDto {
+        SendMsgDto {
+            content: content.to_owned(),
+            content_type: ContentType::Plain,
+            client_id: Some(client_id.to_owned()),
+            quote_id: None,
+            files: vec![],
+        }
+    }
+
+    async fn channel(state: &AppState, user: &ClaimUser, members: &[&ClaimUser]) -> u64 {
+        let input = CreateChatDto {
+            name: Some("general".to_owned()),
+            chat_type: ChatType::PublicChannel,
+            members: members.iter().map(|v| v.id).collect(),
+        };
+        state.chat.create(user, input).await.unwrap().id as _
+    }
+
+    /// a file uploaded by the user, as the upload would record it
+    async fn upload(pool: &PgPool, user: &ClaimUser, name: &str) -> String {
+        let url = format!("/{}/{}", user.ws_id, name);
+        sqlx::query("INSERT INTO files (ws_id, owner_id, url, path) VALUES ($1, $2, $3, $4)")
+            .bind(user.ws_id as i64)
+            .bind(user.id as i64)
+            .bind(&url)
+            .bind(name)
+            .execute(pool)
+            .await
+            .unwrap();
+        url
+    }
+
+    async fn file_urls(pool: &PgPool) -> Vec<String> {
+        sqlx::query_scalar("SELECT url FROM files ORDER BY url")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn send_msg_retry_should_get_the_sent_message_under_slow_mode(pool: PgPool) {
+        let state = state(pool).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let chat_id = channel(&state, &alice, &[&alice]).await;
+        state
+            .moderation
+            .set_slow_mode(&state.chat, &alice, chat_id, SlowModeDto { seconds: 60 })
+            .await
+            .unwrap();
+
+        let sent = state.chat.send_msg(&alice, chat_id, msg("hi", "c1")).await;
+        let retried = state.chat.send_msg(&alice, chat_id, msg("hi", "c1")).await;
+        assert_eq!(sent.unwrap().id, retried.unwrap().id);
+
+        let next = state
+            .chat
+            .send_msg(&alice, chat_id, msg("again", "c2"))
+            .await;
+        assert!(matches!(next, Err(AppError::TooManyRequests(_))));
+    }
+
+    #[sqlx::test]
+    async fn send_msg_should_store_one_message_per_client_id(pool: PgPool) {
+        let state = state(pool.clone()).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "acme").await;
+        let chat_id = channel(&state, &alice, &[&alice, &bob]).await;
+
+        let (first, second) = tokio::join!(
+            state.chat.send_msg(&alice, chat_id, msg("hi", "c1")),
+            state.chat.send_msg(&alice, chat_id, msg("hi", "c1")),
+        );
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert_eq!(first.id, second.id);
+        assert_eq!(first.client_id.as_deref(), Some("c1"));
+        // a retry keeps the content it first sent
+        let retried = state.chat.send_msg(&alice, chat_id, msg("edited", "c1"));
+        assert_eq!(retried.await.unwrap().content, "hi");
+        // the key is per sender
+        let other = state.chat.send_msg(&bob, chat_id, msg("hi", "c1")).await;
+        assert_ne!(other.unwrap().id, first.id);
+        let count: i64 = sqlx::query_scalar("SELECT count(*) FROM messages")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+
+        for client_id in ["", "with space", &"x".repeat(65)] {
+            let ret = state.chat.send_msg(&alice, chat_id, msg("hi", client_id));
+            assert!(matches!(ret.await, Err(AppError::InvalidError(_))));
+        }
+    }
+
+    #[sqlx::test]
+    async fn send_msg_should_attach_only_listed_files_of_the_sender(pool: PgPool) {
+        let state = state(pool.clone()).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "acme").await;
+        let chat_id = channel(&state, &alice, &[&alice, &bob]).await;
+        let plan = upload(&pool, &alice, "plan.png").await;
+        upload(&pool, &alice, "plan.png.bak").await;
+        let avatar = upload(&pool, &bob, "avatar.png").await;
+
+        // someone else's file can not be attached
+        let mut input = msg("look", "c1");
+        input.files = vec![avatar.clone()];
+        let ret = state.chat.send_msg(&alice, chat_id, input).await;
+        assert!(matches!(ret, Err(AppError::InvalidError(_))));
+
+        // urls in the content are not attachments
+        let content = format!("{} {}.bak {}", plan, plan, avatar);
+        let mut input = msg(&content, "c2");
+        input.files = vec![plan.clone()];
+        let sent = state.chat.send_msg(&alice, chat_id, input).await.unwrap();
+
+        let input = ModerateDto {
+            reason: "cleanup".to_owned(),
+        };
+        state
+            .moderation
+            .delete_msg(
+                &state.chat,
+                &state.file,
+                &alice,
+                chat_id,
+                sent.id as _,
+                input,
+            )
+            .await
+            .unwrap();
+        let left = file_urls(&pool).await;
+        assert_eq!(left, vec![avatar, format!("{}.bak", plan)]);
+    }
+
+    #[sqlx::test]
+    async fn forward_msg_should_refer_to_the_original_and_share_its_files(pool: PgPool) {
+        let state = state(pool.clone()).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "acme").await;
+        let general = channel(&state, &alice, &[&alice, &bob]).await;
+        let input = CreateChatDto {
+            name: Some("random".to_owned()),
+            chat_type: ChatType::PublicChannel,
+            members: vec![alice.id, bob.id],
+        };
+        let random = state.chat.create(&alice, input).await.unwrap().id as u64;
+        let input = CreateChatDto {
+            name: Some("private".to_owned()),
+            chat_type: ChatType::PrivateChannel,
+            members: vec![alice.id],
+        };
+        let private = state.chat.create(&alice, input).await.unwrap().id as u64;
+        let plan = upload(&pool, &alice, "plan.png").await;
+        let mut input = msg("the plan", "c1");
+        input.files = vec![plan];
+        let source = state.chat.send_msg(&alice, general, input).await.unwrap();
+
+        let forward = |chat_id| ForwardMsgDto { chat_id };
+        let ret = state
+            .chat
+            .forward_msg(&bob, general, source.id as _, forward(private))
+            .await;
+        assert!(matches!(ret, Err(AppError::NotFound(_))));
+        let copy = state
+            .chat
+            .forward_msg(&bob, general, source.id as _, forward(random))
+            .await
+            .unwrap();
+        assert_eq!(
+            (copy.chat_id, copy.sender_id),
+            (random as i64, bob.id as i64)
+        );
+        assert_eq!(copy.content, "the plan");
+        // a forward of a forward refers to the first message
+        let again = state
+            .chat
+            .forward_msg(&alice, random, copy.id as _, forward(private))
+            .await
+            .unwrap();
+        for msg in [&copy, &again] {
+            let forwarded = &msg.forwarded.as_ref().unwrap().0;
+            assert_eq!(forwarded.msg_id, source.id);
+            assert_eq!(forwarded.chat_id, general as i64);
+            assert_eq!(forwarded.sender_id, alice.id as i64);
+            assert_eq!(forwarded.created_at, source.created_at);
+        }
+        let files: Vec<i64> =
+            sqlx::query_scalar("SELECT DISTINCT file_id FROM message_files WHERE msg_id = ANY($1)")
+                .bind(vec![source.id, copy.id, again.id])
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(file_urls(&pool).await.len(), 1);
+
+        // a quote stays in its chat
+        let mut input = msg("agreed", "c2");
+        input.quote_id = Some(copy.id as _);
+        let ret = state.chat.send_msg(&bob, general, input).await;
+        assert!(matches!(ret, Err(AppError::InvalidError(_))));
+        let mut input = msg("agreed", "c3");
+        input.quote_id = Some(source.id as _);
+        let reply = state.chat.send_msg(&bob, general, input).await.unwrap();
+        assert_eq!(reply.quote_id, Some(source.id));
+    }
+
+    #[sqlx::test]
+    async fn only_the_creator_or_an_admin_should_change_members(pool: PgPool) {
+        let state = state(pool).await;
+        let admin = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "acme").await;
+        let carol = signup(&state, "carol", "acme").await;
+        let dave = signup(&state, "dave", "acme").await;
+        let chat_id = channel(&state, &bob, &[&bob, &carol, &dave]).await;
+        let update = |members: &[&ClaimUser]| UpdateChatDto {
+            name: Some("general".to_owned()),
+            chat_type: ChatType::PublicChannel,
+            members: members.iter().map(|v| v.id).collect(),
+        };
+
+        let ret = state
+            .chat
+            .update(&carol, chat_id, update(&[&bob, &carol]))
+            .await;
+        assert!(matches!(ret, Err(AppError::PermissionDenyError(_))));
+        let ret = state.chat.remove_member(&carol, chat_id, dave.id).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenyError(_))));
+        let ret = state.chat.remove_member(&carol, chat_id, carol.id).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenyError(_))));
+
+        let chat = state.chat.leave(&carol, chat_id).await.unwrap();
+        assert_eq!(chat.members, vec![bob.id as i64, dave.id as i64]);
+        let chat = state
+            .chat
+            .remove_member(&bob, chat_id, dave.id)
+            .await
+            .unwrap();
+        assert_eq!(chat.members, vec![bob.id as i64]);
+        let chat = state
+            .chat
+            .update(&admin, chat_id, update(&[&bob, &dave]))
+            .await
+            .unwrap();
+        assert_eq!(chat.members, vec![bob.id as i64, dave.id as i64]);
+    }
+
+    #[sqlx::test]
+    async fn only_the_creator_or_an_admin_should_archive_delete_or_restore(pool: PgPool) {
+        let state = state(pool).await;
+        let admin = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "acme").await;
+        let carol = signup(&state, "carol", "acme").await;
+        let chat_id = channel(&state, &bob, &[&bob, &carol]).await;
+
+        let ret = state.chat.archive(&carol, chat_id).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenyError(_))));
+        let ret = state.chat.delete(&carol, chat_id).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenyError(_))));
+
+        let chat = state.chat.archive(&bob, chat_id).await.unwrap();
+        assert_eq!(chat.status, ChatStatus::Archived);
+        let ret = state.chat.restore(&carol, chat_id).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenyError(_))));
+        let chat = state.chat.restore(&admin, chat_id).await.unwrap();
+        assert_eq!(chat.status, ChatStatus::Active);
+        let chat = state.chat.delete(&admin, chat_id).await.unwrap();
+        assert_eq!(chat.status, ChatStatus::Deleted);
+    }
+
+    #[sqlx::test]
+    async fn list_messages_should_page_around_cursors(pool: PgPool) {
+        let state = state(pool).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let chat_id = channel(&state, &alice, &[&alice]).await;
+        let mut ids = vec![];
+        for i in 0..5 {
+            let sent = state
+                .chat
+                .send_msg(&alice, chat_id, msg("hi", &format!("c{}", i)))
+                .await
+                .unwrap();
+            ids.push(sent.id);
+        }
+        let list = |before, after, around| ListMessagesDto {
+            before,
+            after,
+            around,
+            limit: Some(2),
+        };
+        let page_ids = |page: &MessagePage| page.messages.iter().map(|v| v.id).collect::<Vec<_>>();
+
+        let page = state
+            .chat
+            .list_messages(chat_id, list(Some(ids[3] as _), None, None))
+            .await
+            .unwrap();
+        assert_eq!(page_ids(&page), vec![ids[2], ids[1]]);
+        assert!(page.has_more_before && page.has_more_after);
+
+        let page = state
+            .chat
+            .list_messages(chat_id, list(None, Some(ids[2] as _), None))
+            .await
+            .unwrap();
+        assert_eq!(page_ids(&page), vec![ids[4], ids[3]]);
+        assert!(page.has_more_before && !page.has_more_after);
+
+        let page = state
+            .chat
+            .list_messages(chat_id, list(None, None, Some(ids[2] as _)))
+            .await
+            .unwrap();
+        assert_eq!(page_ids(&page), vec![ids[2], ids[1]]);
+        assert!(page.has_more_before && page.has_more_after);
+
+        let page = state
+            .chat
+            .list_messages(chat_id, list(None, None, None))
+            .await
+            .unwrap();
+        assert_eq!(page_ids(&page), vec![ids[4], ids[3]]);
+        assert!(page.has_more_before && !page.has_more_after);
+
+        // ids past i64 would wrap to negative cursors
+        let ret = state
+            .chat
+            .list_messages(chat_id, list(Some(u64::MAX), None, None))
+            .await;
+        assert!(matches!(ret, Err(AppError::InvalidError(_))));
+    }
+
+    #[sqlx::test]
+    async fn add_members_should_add_members_of_the_workspace_only(pool: PgPool) {
+        let state = state(pool).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "acme").await;
+        let mallory = signup(&state, "mallory", "globex").await;
+        let chat_id = channel(&state, &alice, &[&alice]).await;
+        let add = |members: &[&ClaimUser]| AddMembersDto {
+            members: members.iter().map(|v| v.id).collect(),
+        };
+
+        let ret = state.chat.add_members(&alice, chat_id, add(&[])).await;
+        assert!(matches!(ret, Err(AppError::InvalidError(_))));
+        let ret = state
+            .chat
+            .add_members(&alice, chat_id, add(&[&mallory]))
+            .await;
+        assert!(matches!(ret, Err(AppError::InvalidError(_))));
+        let chat = state
+            .chat
+            .add_members(&alice, chat_id, add(&[&bob, &bob]))
+            .await
+            .unwrap();
+        assert_eq!(chat.members, vec![alice.id as i64, bob.id as i64]);
+
+        let ret = state.chat.remove_member(&alice, chat_id, mallory.id).await;
+        assert!(matches!(ret, Err(AppError::NotFound(_))));
+    }
+
+    #[sqlx::test]
+    async fn create_should_keep_one_direct_message_per_pair(pool: PgPool) {
+        let state = state(pool).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "acme").await;
+        let carol = signup(&state, "carol", "acme").await;
+        let single = |members: &[&ClaimUser]| CreateChatDto {
+            name: Some("ignored".to_owned()),
+            chat_type: ChatType::Single,
+            members: members.iter().map(|v| v.id).collect(),
+        };
+
+        let opened = state
+            .chat
+            .create(&alice, single(&[&alice, &bob]))
+            .await
+            .unwrap();
+        assert_eq!(opened.name, None);
+        let again = state
+            .chat
+            .create(&bob, single(&[&bob, &alice]))
+            .await
+            .unwrap();
+        assert_eq!(again.id, opened.id);
+        let ret = state
+            .chat
+            .create(&alice, single(&[&alice, &bob, &carol]))
+            .await;
+        assert!(matches!(ret, Err(AppError::InvalidError(_))));
+
+        let input = UpdateChatDto {
+            name: Some("trio".to_owned()),
+            chat_type: ChatType::Group,
+            members: vec![alice.id, bob.id, carol.id],
+        };
+        let ret = state.chat.update(&alice, opened.id as _, input).await;
+        assert!(matches!(ret, Err(AppError::InvalidError(_))));
+    }
+
+    #[sqlx::test]
+    async fn create_should_keep_channel_names_unique(pool: PgPool) {
+        let state = state(pool).await;
+        let alice = signup(&state, "alice", "acme").await;
+        channel(&state, &alice, &[&alice]).await;
+
+        let input = CreateChatDto {
+            name: Some(" general ".to_owned()),
+            chat_type: ChatType::PrivateChannel,
+            members: vec![alice.id],
+        };
+        let ret = state.chat.create(&alice, input).await;
+        assert!(matches!(ret, Err(AppError::ConflictError(_))));
+        let input = CreateChatDto {
+            name: None,
+            chat_type: ChatType::Group,
+            members: vec![alice.id],
+        };
+        let ret = state.chat.create(&alice, input).await;
+        assert!(matches!(ret, Err(AppError::InvalidError(_))));
+    }
+
+    #[sqlx::test]
+    async fn members_should_find_and_join_public_channels_only(pool: PgPool) {
+        let state = state(pool).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "acme").await;
+        let public = channel(&state, &alice, &[&alice]).await;
+        let input = CreateChatDto {
+            name: Some("secret".to_owned()),
+            chat_type: ChatType::PrivateChannel,
+            members: vec![alice.id],
+        };
+        let private = state.chat.create(&alice, input).await.unwrap().id as u64;
+        let search = |q: &str| SearchChannelsDto {
+            q: Some(q.to_owned()),
+        };
+
+        let found = state.chat.list_channels(&bob, search("GEN")).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].id, found[0].joined), (public as i64, false));
+        let found = state
+            .chat
+            .list_channels(&bob, search("secret"))
+            .await
+            .unwrap();
+        assert!(found.is_empty());
+        assert!(state.chat.can_preview(&bob, public).await.unwrap());
+        assert!(!state.chat.can_preview(&bob, private).await.unwrap());
+
+        let ret = state.chat.join(&bob, private).await;
+        assert!(matches!(ret, Err(AppError::InvalidError(_))));
+        let chat = state.chat.join(&bob, public).await.unwrap();
+        assert!(chat.members.contains(&(bob.id as i64)));
+        // joining again is a no-op
+        state.chat.join(&bob, public).await.unwrap();
+        let found = state.chat.list_channels(&bob, search("")).await.unwrap();
+        assert_eq!((found[0].member_count, found[0].joined), (2, true));
+    }
+
+    #[sqlx::test]
+    async fn header_should_hold_the_topic_pins_and_bookmarks(pool: PgPool) {
+        let state = state(pool).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let chat_id = channel(&state, &alice, &[&alice]).await;
+
+        let input = UpdateHeaderDto {
+            topic: Some(" release week ".to_owned()),
+            description: Some(String::new()),
+        };
+        let chat = state.chat.update_header(chat_id, input).await.unwrap();
+        assert_eq!(chat.topic.as_deref(), Some("release week"));
+        assert_eq!(chat.description, None);
+
+        let sent = state
+            .chat
+            .send_msg(&alice, chat_id, msg("ship it", "c1"))
+            .await;
+        let msg_id = sent.unwrap().id as u64;
+        let ret = state
+            .chat
+            .pin(&alice, chat_id, PinMsgDto { msg_id: 0 })
+            .await;
+        assert!(matches!(ret, Err(AppError::NotFound(_))));
+        state
+            .chat
+            .pin(&alice, chat_id, PinMsgDto { msg_id })
+            .await
+            .unwrap();
+        let pins = state.chat.list_pins(chat_id).await.unwrap();
+        assert_eq!(pins.len(), 1);
+        assert_eq!(pins[0].message.content, "ship it");
+        state.chat.unpin(chat_id, msg_id).await.unwrap();
+        let ret = state.chat.unpin(chat_id, msg_id).await;
+        assert!(matches!(ret, Err(AppError::NotFound(_))));
+
+        let bookmark = |url: &str| AddBookmarkDto {
+            title: "runbook".to_owned(),
+            url: url.to_owned(),
+        };
+        let ret = state
+            .chat
+            .add_bookmark(&alice, chat_id, bookmark("javascript:alert(1)"))
+            .await;
+        assert!(matches!(ret, Err(AppError::InvalidError(_))));
+        let saved = state
+            .chat
+            .add_bookmark(&alice, chat_id, bookmark("https://example.com/runbook"))
+            .await
+            .unwrap();
+        assert_eq!(state.chat.list_bookmarks(chat_id).await.unwrap().len(), 1);
+        state
+            .chat
+            .remove_bookmark(chat_id, saved.id as _)
+            .await
+            .unwrap();
+        assert!(state.chat.list_bookmarks(chat_id).await.unwrap().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn deleted_chat_should_be_purged_after_the_restore_window(pool: PgPool) {
+        let state = state(pool.clone()).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let chat_id = channel(&state, &alice, &[&alice]).await;
+        state
+            .chat
+            .send_msg(&alice, chat_id, msg("hi", "c1"))
+            .await
+            .unwrap();
+
+        // archived chats are read-only
+        state.chat.archive(&alice, chat_id).await.unwrap();
+        let ret = state.chat.send_msg(&alice, chat_id, msg("hi", "c2")).await;
+        assert!(matches!(ret, Err(AppError::InvalidError(_))));
+        state.chat.restore(&alice, chat_id).await.unwrap();
+
+        state.chat.delete(&alice, chat_id).await.unwrap();
+        assert_eq!(state.chat.purge_expired(&state.file).await.unwrap(), 0);
+        sqlx::query("UPDATE chats SET status_changed_at = now() - interval '1 year' WHERE id = $1")
+            .bind(chat_id as i64)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let ret = state.chat.restore(&alice, chat_id).await;
+        assert!(matches!(ret, Err(AppError::InvalidError(_))));
+        assert_eq!(state.chat.purge_expired(&state.file).await.unwrap(), 1);
+
+        let chat = state.chat.find_chat(chat_id).await.unwrap();
+        assert_eq!(chat.status, ChatStatus::Purged);
+        let left: i64 = sqlx::query_scalar("SELECT count(*) FROM messages WHERE chat_id = $1")
+            .bind(chat_id as i64)
+            .fetch_one(&pool)
             .await
+            .unwrap();
+        assert_eq!(left, 0);
     }
 }
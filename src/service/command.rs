@@ -0,0 +1,591 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use reqwest::{header::CONTENT_TYPE, redirect::Policy, Client};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::warn;
+
+use crate::{
+    common::utils::random_hex,
+    domain::{
+        audit::{AuditAction, AuditEvent, AuditRepo},
+        chat::ContentType,
+        command::{
+            parse_duration, Command, CommandReply, CommandRepo, EphemeralMsg, ResponseType,
+            SlashCommand, BUILTIN_COMMANDS,
+        },
+        webhook::sign,
+    },
+    error::AppError,
+};
+
+use super::{
+    auth::ClaimUser,
+    chat::{
+        AddMembersDto, ChatService, PostOutput, SendMsgDto, UpdateChatPrefsDto, UpdateHeaderDto,
+    },
+    moderation::{ModerationService, MuteDto},
+    schedule::{RemindDto, ScheduleService},
+    unfurl::PublicResolver,
+    webhook::check_endpoint_url,
+    workspace::WorkspaceService,
+};
+
+/// the member waits for the answer, so it has to come quickly
+const CALL_TIMEOUT: Duration = Duration::from_secs(5);
+const SECRET_BYTES: usize = 32;
+/// `/mute` without a duration
+const DEFAULT_MUTE_HOURS: i64 = 8;
+/// a run not finished by then was cut short, a retry runs the command again
+const RUN_LEASE_SECS: i64 = 60;
+
+pub const TIMESTAMP_HEADER: &str = "x-command-timestamp";
+/// `sha256=<hex hmac of "{timestamp}.{body}">`
+pub const SIGNATURE_HEADER: &str = "x-command-signature";
+
+pub struct CommandService {
+    repo: Box<dyn CommandRepo + Send + Sync>,
+    audit: Box<dyn AuditRepo + Send + Sync>,
+    // the services built-in commands act through, besides the chat they are run in
+    schedule: Arc<ScheduleService>,
+    moderation: Arc<ModerationService>,
+    workspace: Arc<WorkspaceService>,
+    client: Client,
+    // plain http and private hosts are allowed, to test against a local stub
+    insecure: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCommandDto {
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// the secret is only part of the response when it was just made
+#[derive(Debug, Serialize)]
+pub struct CommandDto {
+    #[serde(flatten)]
+    pub command: Command,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+impl From<Command> for CommandDto {
+    fn from(command: Command) -> Self {
+        Self {
+            command,
+            secret: None,
+        }
+    }
+}
+
+/// posted to the url of a workspace command
+#[derive(Debug, Serialize)]
+struct CommandCall<'a> {
+    command: &'a str,
+    text: &'a str,
+    ws_id: u64,
+    chat_id: u64,
+    user_id: u64,
+}
+
+impl CommandService {
+    pub fn try_new(
+        repo: Box<dyn CommandRepo + Send + Sync>,
+        audit: Box<dyn AuditRepo + Send + Sync>,
+        schedule: Arc<ScheduleService>,
+        moderation: Arc<ModerationService>,
+        workspace: Arc<WorkspaceService>,
+        insecure: bool,
+    ) -> Result<Self, AppError> {
+        let mut builder = Client::builder()
+            .timeout(CALL_TIMEOUT)
+            .redirect(Policy::none())
+            .user_agent(concat!("chat-command/", env!("CARGO_PKG_VERSION")));
+        if !insecure {
+            builder = builder.dns_resolver(Arc::new(PublicResolver));
+        }
+        let client = builder.build()?;
+        Ok(Self {
+            repo,
+            audit,
+            schedule,
+            moderation,
+            workspace,
+            client,
+            insecure,
+        })
+    }
+
+    pub async fn create(
+        &self,
+        actor: &ClaimUser,
+        input: CreateCommandDto,
+    ) -> Result<CommandDto, AppError> {
+        let secret = random_hex(SECRET_BYTES);
+        let command = Command::new(
+            actor.ws_id as _,
+            actor.id as _,
+            input.name,
+            input.url,
+            input.description,
+            secret.clone(),
+        );
+        command.validate()?;
+        check_endpoint_url(&command.url, self.insecure).await?;
+        let command = self.repo.save(&command).await?;
+        self.record(actor, AuditAction::CommandCreate, &command)
+            .await?;
+        Ok(CommandDto {
+            command,
+            secret: Some(secret),
+        })
+    }
+
+    pub async fn list(&self, ws_id: u64) -> Result<Vec<CommandDto>, AppError> {
+        let commands = self.repo.extract_by_ws(ws_id as _).await?;
+        Ok(commands.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn delete(&self, actor: &ClaimUser, id: u64) -> Result<CommandDto, AppError> {
+        let command = self
+            .repo
+            .delete(actor.ws_id as _, id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("command {}", id)))?;
+        self.record(actor, AuditAction::CommandDelete, &command)
+            .await?;
+        Ok(command.into())
+    }
+
+    /// run the command sent to the chat, sent again with the same client message id
+    /// it is not run twice and the output of the first run is returned
+    pub async fn run(
+        &self,
+        chat: &ChatService,
+        user: &ClaimUser,
+        chat_id: u64,
+        cmd: SlashCommand,
+        client_id: Option<String>,
+    ) -> Result<PostOutput, AppError> {
+        let Some(client_id) = client_id else {
+            return self.execute(chat, user, chat_id, cmd, None).await;
+        };
+        let (chat_key, user_key) = (chat_id as i64, user.id as i64);
+        let earlier = self
+            .repo
+            .claim_run(chat_key, user_key, &client_id, RUN_LEASE_SECS)
+            .await?;
+        if let Some(earlier) = earlier {
+            return match earlier.output {
+                Some(output) => Ok(serde_json::from_value(output.0)?),
+                None => Err(AppError::ConflictError(format!(
+                    "command of client message id {} still running",
+                    client_id
+                ))),
+            };
+        }
+        match self
+            .execute(chat, user, chat_id, cmd, Some(client_id.clone()))
+            .await
+        {
+            Ok(output) => {
+                let value = serde_json::to_value(&output)?;
+                self.repo
+                    .finish_run(chat_key, user_key, &client_id, &value)
+                    .await?;
+                Ok(output)
+            }
+            Err(e) => {
+                self.repo
+                    .release_run(chat_key, user_key, &client_id)
+                    .await?;
+                Err(e)
+            }
+        }
+    }
+
+    async fn execute(
+        &self,
+        chat: &ChatService,
+        user: &ClaimUser,
+        chat_id: u64,
+        cmd: SlashCommand,
+        client_id: Option<String>,
+    ) -> Result<PostOutput, AppError> {
+        let reply = match cmd.name.as_str() {
+            "help" => self.help(user).await?,
+            "topic" => Self::topic(chat, chat_id, &cmd.args).await?,
+            "invite" => Self::invite(chat, user, chat_id, &cmd.args).await?,
+            "leave" => {
+                chat.leave(user, chat_id).await?;
+                CommandReply::ephemeral("You left the chat")
+            }
+            "remind" => self.remind(chat, user, chat_id, &cmd.args).await?,
+            "mute" => self.mute(chat, user, chat_id, &cmd.args).await?,
+            _ => self.call(user, chat_id, &cmd).await?,
+        };
+        if reply.response_type == ResponseType::InChannel && !reply.text.trim().is_empty() {
+            // the reply is posted once however often the command is retried
+            let input = SendMsgDto {
+                content: reply.text,
+                content_type: ContentType::Plain,
+                client_id,
+                quote_id: None,
+                files: vec![],
+            };
+            let msg = chat.send_msg(user, chat_id, input).await?;
+            return Ok(PostOutput::Posted(msg));
+        }
+        let msg = EphemeralMsg::new(chat_id as _, user.id as _, cmd.name, reply.text);
+        if !msg.text.is_empty() {
+            self.repo.notify_ephemeral(&msg).await?;
+        }
+        Ok(PostOutput::Ephemeral(msg))
+    }
+
+    async fn help(&self, user: &ClaimUser) -> Result<CommandReply, AppError> {
+        let mut lines: Vec<String> = BUILTIN_COMMANDS
+            .iter()
+            .map(|(_, usage)| usage.to_string())
+            .collect();
+        for command in self.repo.extract_by_ws(user.ws_id as _).await? {
+            match command.description.is_empty() {
+                true => lines.push(format!("/{}", command.name)),
+                false => lines.push(format!("/{} - {}", command.name, command.description)),
+            }
+        }
+        Ok(CommandReply::ephemeral(lines.join("\n")))
+    }
+
+    /// the current topic without arguments
+    async fn topic(chat: &ChatService, chat_id: u64, args: &str) -> Result<CommandReply, AppError> {
+        if args.is_empty() {
+            let topic = chat.get_by_id(chat_id).await?.and_then(|chat| chat.topic);
+            return Ok(CommandReply::ephemeral(match topic {
+                Some(topic) => format!("The topic is: {}", topic),
+                None => "No topic is set".to_owned(),
+            }));
+        }
+        let input = UpdateHeaderDto {
+            topic: Some(args.to_owned()),
+            description: None,
+        };
+        let updated = chat.update_header(chat_id, input).await?;
+        Ok(CommandReply::ephemeral(format!(
+            "Topic set to: {}",
+            updated.topic.unwrap_or_default()
+        )))
+    }
+
+    async fn invite(
+        chat: &ChatService,
+        user: &ClaimUser,
+        chat_id: u64,
+        args: &str,
+    ) -> Result<CommandReply, AppError> {
+        let mut handles: Vec<String> = vec![];
+        for handle in args.split_whitespace().filter_map(parse_handle) {
+            if !handles.contains(&handle) {
+                handles.push(handle);
+            }
+        }
+        if handles.is_empty() {
+            return Err(usage("invite"));
+        }
+        let members = chat.resolve_handles(user.ws_id, &handles).await?;
+        chat.add_members(user, chat_id, AddMembersDto { members })
+            .await?;
+        let handles: Vec<String> = handles.iter().map(|v| format!("@{}", v)).collect();
+        Ok(CommandReply::ephemeral(format!(
+            "Added {}",
+            handles.join(", ")
+        )))
+    }
+
+    /// `/remind 30m note`, `/remind in 2h note` or `/remind 2030-01-01T09:00:00Z note`
+    async fn remind(
+        &self,
+        chat: &ChatService,
+        user: &ClaimUser,
+        chat_id: u64,
+        args: &str,
+    ) -> Result<CommandReply, AppError> {
+        let args = args.strip_prefix("in ").map_or(args, str::trim_start);
+        let (when, note) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+        let run_at = match parse_duration(when) {
+            Some(duration) => Utc::now() + duration,
+            None => when.parse::<DateTime<Utc>>().map_err(|_| usage("remind"))?,
+        };
+        let input = RemindDto {
+            msg_id: None,
+            run_at,
+            note: note.trim().to_owned(),
+        };
+        let item = self.schedule.remind(chat, user, chat_id, input).await?;
+        Ok(CommandReply::ephemeral(format!(
+            "I will remind you at {}",
+            item.run_at.to_rfc3339()
+        )))
+    }
+
+    /// mutes the chat for the member, or keeps another member from posting for admins
+    async fn mute(
+        &self,
+        chat: &ChatService,
+        user: &ClaimUser,
+        chat_id: u64,
+        args: &str,
+    ) -> Result<CommandReply, AppError> {
+        if args.starts_with('@') {
+            return self.mute_member(chat, user, chat_id, args).await;
+        }
+        let muted_until = match args {
+            "off" => None,
+            "" => Some(Utc::now() + chrono::Duration::hours(DEFAULT_MUTE_HOURS)),
+            _ => Some(Utc::now() + parse_duration(args).ok_or_else(|| usage("mute"))?),
+        };
+        let input = UpdateChatPrefsDto {
+            muted_until: Some(muted_until),
+            favorite: None,
+            hidden: None,
+            notif_level: None,
+        };
+        let prefs = chat.update_prefs(user, chat_id, input).await?;
+        Ok(CommandReply::ephemeral(match prefs.muted_until {
+            Some(until) => format!("The chat is muted until {}", until.to_rfc3339()),
+            None => "The chat is unmuted".to_owned(),
+        }))
+    }
+
+    async fn mute_member(
+        &self,
+        chat: &ChatService,
+        user: &ClaimUser,
+        chat_id: u64,
+        args: &str,
+    ) -> Result<CommandReply, AppError> {
+        if !self.workspace.is_admin(user).await? {
+            return Err(AppError::PermissionDenyError(
+                "workspace admin required".to_owned(),
+            ));
+        }
+        let mut words = args.splitn(3, char::is_whitespace);
+        let handle = words.next().and_then(parse_handle);
+        let duration = words.next().and_then(parse_duration);
+        let (handle, duration) = match (handle, duration) {
+            (Some(handle), Some(duration)) => (handle, duration),
+            _ => return Err(usage("mute")),
+        };
+        let user_id = chat
+            .resolve_handles(user.ws_id, std::slice::from_ref(&handle))
+            .await?[0];
+        let input = MuteDto {
+            user_id,
+            minutes: duration.num_minutes().try_into().unwrap_or(u32::MAX),
+            reason: words.next().unwrap_or_default().trim().to_owned(),
+        };
+        let mute = self.moderation.mute(chat, user, chat_id, input).await?;
+        Ok(CommandReply::ephemeral(format!(
+            "@{} can not post until {}",
+            handle,
+            mute.muted_until.to_rfc3339()
+        )))
+    }
+
+    /// ask the endpoint of a workspace command, a failure is only told to the member
+    async fn call(
+        &self,
+        user: &ClaimUser,
+        chat_id: u64,
+        cmd: &SlashCommand,
+    ) -> Result<CommandReply, AppError> {
+        let command = match self.repo.find_by_name(user.ws_id as _, &cmd.name).await? {
+            Some(command) => command,
+            None => {
+                return Ok(CommandReply::ephemeral(format!(
+                    "Unknown command /{}, /help lists the commands",
+                    cmd.name
+                )))
+            }
+        };
+        let call = CommandCall {
+            command: &command.name,
+            text: &cmd.args,
+            ws_id: user.ws_id,
+            chat_id,
+            user_id: user.id,
+        };
+        match self.send(&command, &call).await {
+            Ok(reply) => Ok(reply),
+            Err(e) => {
+                warn!("Failed to call command {}, error: {}", command.id, e);
+                Ok(CommandReply::ephemeral(format!(
+                    "/{} failed, try again later",
+                    command.name
+                )))
+            }
+        }
+    }
+
+    async fn send(
+        &self,
+        command: &Command,
+        call: &CommandCall<'_>,
+    ) -> Result<CommandReply, AppError> {
+        let url = check_endpoint_url(&command.url, self.insecure).await?;
+        let body = serde_json::to_vec(call)?;
+        let timestamp = Utc::now().timestamp();
+        let signature = sign(&command.secret, timestamp, &body);
+        let response = self
+            .client
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(SIGNATURE_HEADER, format!("sha256={}", signature))
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
+        let body = response.bytes().await?;
+        // an empty answer only acknowledges the call
+        if body.iter().all(u8::is_ascii_whitespace) {
+            return Ok(CommandReply::default());
+        }
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    async fn record(
+        &self,
+        actor: &ClaimUser,
+        action: AuditAction,
+        command: &Command,
+    ) -> Result<(), AppError> {
+        let event = AuditEvent::new(
+            actor.ws_id as _,
+            actor.id as _,
+            action,
+            format!("command:{}", command.id),
+            json!({ "name": command.name, "url": command.url }),
+        )
+        .with_client(&actor.client);
+        self.audit.append(&event).await?;
+        Ok(())
+    }
+}
+
+/// `@handle` or `handle`, lowercased as mentions are
+fn parse_handle(word: &str) -> Option<String> {
+    let handle = word.trim_start_matches('@').trim_end_matches(',');
+    (!handle.is_empty()).then(|| handle.to_lowercase())
+}
+
+fn usage(name: &str) -> AppError {
+    let usage = BUILTIN_COMMANDS
+        .iter()
+        .find(|(v, _)| *v == name)
+        .map_or("", |(_, usage)| usage);
+    AppError::InvalidError(format!("usage: {}", usage))
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+    use tokio::sync::broadcast::Receiver;
+
+    use super::*;
+    use crate::{
+        domain::chat::ChatType,
+        service::{chat::CreateChatDto, notif::AppEvent},
+        test_util::{signup, state},
+    };
+
+    fn post(content: &str, client_id: Option<&str>) -> SendMsgDto {
+        SendMsgDto {
+            content: content.to_owned(),
+            content_type: ContentType::Plain,
+            client_id: client_id.map(str::to_owned),
+            quote_id: None,
+            files: vec![],
+        }
+    }
+
+    fn ephemeral_text(output: PostOutput) -> String {
+        match output {
+            PostOutput::Ephemeral(msg) => msg.text,
+            PostOutput::Posted(msg) => panic!("message {} was posted", msg.id),
+        }
+    }
+
+    async fn next(rx: &mut Receiver<Arc<AppEvent>>) -> Arc<AppEvent> {
+        let recv = tokio::time::timeout(Duration::from_secs(5), rx.recv());
+        recv.await.unwrap().unwrap()
+    }
+
+    #[sqlx::test]
+    async fn commands_should_reply_to_the_invoker_and_run_once(pool: PgPool) {
+        let state = state(pool.clone()).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "acme").await;
+        signup(&state, "carol", "acme").await;
+        let input = CreateChatDto {
+            name: Some("general".to_owned()),
+            chat_type: ChatType::PublicChannel,
+            members: vec![alice.id, bob.id],
+        };
+        let chat_id = state.chat.create(&alice, input).await.unwrap().id as u64;
+        let mut alice_rx = state.notif.register(&alice);
+        let mut bob_rx = state.notif.register(&bob);
+
+        let help = state.chat.post_msg(&alice, chat_id, post("/help", None));
+        assert!(ephemeral_text(help.await.unwrap()).contains("/topic"));
+        let event = next(&mut alice_rx).await;
+        assert_eq!(event.get_name(), "ephemeral_message");
+        // the reply is not sent to the other members
+        state
+            .chat
+            .post_msg(&bob, chat_id, post("hi", None))
+            .await
+            .unwrap();
+        assert_eq!(next(&mut bob_rx).await.get_name(), "new_message");
+
+        let unknown = state.chat.post_msg(&alice, chat_id, post("/deploy", None));
+        assert!(ephemeral_text(unknown.await.unwrap()).starts_with("Unknown command /deploy"));
+
+        // a retry returns the first output and runs nothing
+        let topic = state
+            .chat
+            .post_msg(&alice, chat_id, post("/topic Launch", Some("c1")))
+            .await
+            .unwrap();
+        assert_eq!(ephemeral_text(topic), "Topic set to: Launch");
+        let retried = state
+            .chat
+            .post_msg(&alice, chat_id, post("/topic Other", Some("c1")))
+            .await
+            .unwrap();
+        assert_eq!(ephemeral_text(retried), "Topic set to: Launch");
+        let chat = state.chat.get_by_id(chat_id).await.unwrap().unwrap();
+        assert_eq!(chat.topic.as_deref(), Some("Launch"));
+
+        let invite = state
+            .chat
+            .post_msg(&alice, chat_id, post("/invite @carol @carol", None))
+            .await
+            .unwrap();
+        assert_eq!(ephemeral_text(invite), "Added @carol");
+        let chat = state.chat.get_by_id(chat_id).await.unwrap().unwrap();
+        assert_eq!(chat.members.len(), 3);
+
+        // a path is not a command
+        let posted = state
+            .chat
+            .post_msg(&alice, chat_id, post("/usr/bin is gone", None))
+            .await
+            .unwrap();
+        assert!(matches!(posted, PostOutput::Posted(msg) if msg.content == "/usr/bin is gone"));
+    }
+}
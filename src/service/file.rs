@@ -1,25 +1,48 @@
 use std::path::{Path, PathBuf};
 
 use axum::extract::multipart::Field;
+use serde_json::json;
 use tokio::{
     fs::{self, File},
     io::AsyncWriteExt,
 };
+use tracing::warn;
 
-use crate::error::AppError;
+use crate::{
+    domain::{
+        audit::{AuditAction, AuditEvent, AuditRepo},
+        chat::Msg,
+        file::{FileMeta, FileRepo},
+    },
+    error::AppError,
+};
+
+use super::auth::ClaimUser;
 
 pub struct FileService {
     base_dir: PathBuf,
+    repo: Box<dyn FileRepo + Send + Sync>,
+    audit: Box<dyn AuditRepo + Send + Sync>,
 }
 
 impl FileService {
-    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+    pub fn new<P: AsRef<Path>>(
+        dir: P,
+        repo: Box<dyn FileRepo + Send + Sync>,
+        audit: Box<dyn AuditRepo + Send + Sync>,
+    ) -> Self {
         Self {
             base_dir: dir.as_ref().to_owned(),
+            repo,
+            audit,
         }
     }
 
-    pub async fn save(&self, ws_id: u64, mut field: Field<'_>) -> Result<Option<String>, AppError> {
+    pub async fn save(
+        &self,
+        user: &ClaimUser,
+        mut field: Field<'_>,
+    ) -> Result<Option<String>, AppError> {
         let filename = match field.file_name() {
             Some(filename) => filename.to_owned(),
             None => return Ok(None),
@@ -36,6 +59,60 @@ impl FileService {
                 f.write_all(&chunk).await?;
             }
         }
-        Ok(Some(format!("/{}/{}", ws_id, filename)))
+        let url = format!("/{}/{}", user.ws_id, filename);
+        let meta = FileMeta::new(user.ws_id as _, user.id as _, url.clone(), filename);
+        let meta = self.repo.save(&meta).await?;
+        let event = AuditEvent::new(
+            user.ws_id as _,
+            user.id as _,
+            AuditAction::FileUpload,
+            format!("file:{}", meta.id),
+            json!({ "url": url }),
+        )
+        .with_client(&user.client);
+        self.audit.append(&event).await?;
+        Ok(Some(url))
+    }
+
+    pub async fn list_owned(&self, user_id: u64) -> Result<Vec<FileMeta>, AppError> {
+        self.repo.extract_by_owner(user_id as _).await
+    }
+
+    pub async fn read(&self, file: &FileMeta) -> Result<Vec<u8>, AppError> {
+        Ok(fs::read(self.base_dir.join(&file.path)).await?)
+    }
+
+    /// forget every file uploaded by the user, removing the content once nobody else uploaded it
+    pub async fn erase_owned(&self, user_id: u64) -> Result<(), AppError> {
+        let files = self.repo.delete_by_owner(user_id as _).await?;
+        self.remove_unused(files).await
+    }
+
+    /// forget the files shared only in the chat, called before its messages are purged
+    pub async fn erase_chat_files(&self, chat_id: u64, ws_id: u64) -> Result<(), AppError> {
+        let files = self.repo.delete_by_chat(chat_id as _, ws_id as _).await?;
+        self.remove_unused(files).await
+    }
+
+    /// forget the files shared in messages already deleted, unless another message links them
+    pub async fn erase_msg_files(&self, ws_id: u64, msgs: &[Msg]) -> Result<(), AppError> {
+        if msgs.is_empty() {
+            return Ok(());
+        }
+        let msg_ids: Vec<i64> = msgs.iter().map(|msg| msg.id).collect();
+        let files = self.repo.delete_unreferenced(ws_id as _, &msg_ids).await?;
+        self.remove_unused(files).await
+    }
+
+    async fn remove_unused(&self, files: Vec<FileMeta>) -> Result<(), AppError> {
+        for file in files {
+            if self.repo.is_path_used(&file.path).await? {
+                continue;
+            }
+            if let Err(e) = fs::remove_file(self.base_dir.join(&file.path)).await {
+                warn!("Failed to remove file {}, error: {}", file.path, e);
+            }
+        }
+        Ok(())
     }
 }
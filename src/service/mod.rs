@@ -1,6 +1,18 @@
+pub mod account;
+pub mod audit;
 pub mod auth;
+pub mod bot;
 pub mod chat;
+pub mod command;
 pub mod file;
+pub mod moderation;
 pub mod notif;
+pub mod poll;
+pub mod rate_limit;
+pub mod retention;
+pub mod schedule;
+pub mod unfurl;
 pub mod user;
+pub mod webhook;
+pub mod workspace;
 pub use auth::AuthService;
@@ -0,0 +1,567 @@
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    domain::{
+        audit::{AuditAction, AuditEvent, AuditRepo},
+        chat::{Chat, Msg},
+        moderation::{
+            check_reason, ChatBan, ChatMute, ModerationRepo, Report, ReportStatus, MAX_MUTE_DAYS,
+        },
+        rate_limit::MAX_SLOW_MODE_SECS,
+    },
+    error::AppError,
+};
+
+use super::{
+    auth::ClaimUser,
+    chat::{ChatService, SlowModeDto},
+    file::FileService,
+};
+
+const DEFAULT_QUEUE_SIZE: u8 = 20;
+
+pub struct ModerationService {
+    repo: Box<dyn ModerationRepo + Send + Sync>,
+    audit: Box<dyn AuditRepo + Send + Sync>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReportMsgDto {
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListReportsDto {
+    #[serde(default)]
+    pub status: ReportStatus,
+    pub last_id: Option<u64>,
+    pub limit: Option<u8>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResolveReportDto {
+    /// resolved or dismissed
+    pub status: ReportStatus,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ModerateDto {
+    #[serde(default)]
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MuteDto {
+    pub user_id: u64,
+    pub minutes: u32,
+    #[serde(default)]
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BanDto {
+    pub user_id: u64,
+    #[serde(default)]
+    pub reason: String,
+}
+
+impl ModerationService {
+    pub fn new(
+        repo: Box<dyn ModerationRepo + Send + Sync>,
+        audit: Box<dyn AuditRepo + Send + Sync>,
+    ) -> Self {
+        Self { repo, audit }
+    }
+
+    /// any member who can read the message may report it
+    pub async fn report(
+        &self,
+        chat: &ChatService,
+        user: &ClaimUser,
+        chat_id: u64,
+        msg_id: u64,
+        input: ReportMsgDto,
+    ) -> Result<Report, AppError> {
+        let msg = chat.get_msg(chat_id, msg_id).await?;
+        if msg.sender_id == user.id as i64 {
+            return Err(AppError::InvalidError(
+                "can not report your own message".to_owned(),
+            ));
+        }
+        let report = Report::new(user.ws_id as _, &msg, user.id as _, input.reason);
+        report.validate()?;
+        let report = self.repo.save_report(&report).await?.ok_or_else(|| {
+            AppError::ConflictError(format!("message {} is already reported", msg_id))
+        })?;
+        self.record(
+            user,
+            AuditAction::MessageReport,
+            format!("message:{}", msg_id),
+            json!({ "chat_id": chat_id, "report_id": report.id, "reason": report.reason }),
+        )
+        .await?;
+        Ok(report)
+    }
+
+    /// the moderation queue of the workspace, oldest first
+    pub async fn list_reports(
+        &self,
+        ws_id: u64,
+        input: ListReportsDto,
+    ) -> Result<Vec<Report>, AppError> {
+        let limit = input.limit.unwrap_or(DEFAULT_QUEUE_SIZE);
+        self.repo
+            .extract_reports(
+                ws_id as _,
+                input.status,
+                input.last_id.unwrap_or(0) as _,
+                limit as _,
+            )
+            .await
+    }
+
+    pub async fn resolve_report(
+        &self,
+        actor: &ClaimUser,
+        id: u64,
+        input: ResolveReportDto,
+    ) -> Result<Report, AppError> {
+        if input.status == ReportStatus::Open {
+            return Err(AppError::InvalidError(
+                "a report is resolved or dismissed".to_owned(),
+            ));
+        }
+        let note = input
+            .note
+            .as_deref()
+            .map(str::trim)
+            .filter(|v| !v.is_empty());
+        if let Some(note) = note {
+            check_reason(note)?;
+        }
+        let report = self
+            .repo
+            .resolve_report(actor.ws_id as _, id as _, input.status, actor.id as _, note)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("open report {}", id)))?;
+        self.record(
+            actor,
+            AuditAction::ReportResolve,
+            format!("report:{}", id),
+            json!({ "status": report.status, "note": report.note }),
+        )
+        .await?;
+        Ok(report)
+    }
+
+    /// delete any message of the workspace, its open reports are resolved
+    pub async fn delete_msg(
+        &self,
+        chat: &ChatService,
+        files: &FileService,
+        actor: &ClaimUser,
+        chat_id: u64,
+        msg_id: u64,
+        input: ModerateDto,
+    ) -> Result<Msg, AppError> {
+        let reason = Self::check_reason(&input.reason)?;
+        self.find_chat(chat, actor, chat_id).await?;
+        let msg = self
+            .repo
+            .delete_msg(chat_id as _, msg_id as _, actor.id as _, reason)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("message {}", msg_id)))?;
+        files
+            .erase_msg_files(actor.ws_id, std::slice::from_ref(&msg))
+            .await?;
+        self.record(
+            actor,
+            AuditAction::MessageDelete,
+            format!("message:{}", msg_id),
+            json!({
+                "chat_id": chat_id,
+                "sender_id": msg.sender_id,
+                "content": msg.content,
+                "reason": reason,
+            }),
+        )
+        .await?;
+        Ok(msg)
+    }
+
+    /// the member can read the chat but not post until the mute is over
+    pub async fn mute(
+        &self,
+        chat: &ChatService,
+        actor: &ClaimUser,
+        chat_id: u64,
+        input: MuteDto,
+    ) -> Result<ChatMute, AppError> {
+        let reason = Self::check_reason(&input.reason)?;
+        let target = self.find_chat(chat, actor, chat_id).await?;
+        Self::check_target(actor, input.user_id)?;
+        if !target.members.contains(&(input.user_id as i64)) {
+            return Err(AppError::NotFound(format!("member {}", input.user_id)));
+        }
+        if input.minutes == 0 || input.minutes as i64 > MAX_MUTE_DAYS * 24 * 60 {
+            return Err(AppError::InvalidError(format!(
+                "a mute lasts a minute to {} days",
+                MAX_MUTE_DAYS
+            )));
+        }
+        let mute = ChatMute {
+            chat_id: chat_id as _,
+            user_id: input.user_id as _,
+            muted_until: Utc::now() + Duration::minutes(input.minutes as _),
+            muted_by: actor.id as _,
+            reason: reason.unwrap_or_default().to_owned(),
+            created_at: Utc::now(),
+        };
+        let mute = self.repo.save_mute(&mute).await?;
+        self.record(
+            actor,
+            AuditAction::MemberMute,
+            format!("user:{}", input.user_id),
+            json!({ "chat_id": chat_id, "muted_until": mute.muted_until, "reason": mute.reason }),
+        )
+        .await?;
+        Ok(mute)
+    }
+
+    pub async fn unmute(
+        &self,
+        chat: &ChatService,
+        actor: &ClaimUser,
+        chat_id: u64,
+        user_id: u64,
+    ) -> Result<ChatMute, AppError> {
+        self.find_chat(chat, actor, chat_id).await?;
+        let mute = self
+            .repo
+            .delete_mute(chat_id as _, user_id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("mute of user {}", user_id)))?;
+        self.record(
+            actor,
+            AuditAction::MemberUnmute,
+            format!("user:{}", user_id),
+            json!({ "chat_id": chat_id }),
+        )
+        .await?;
+        Ok(mute)
+    }
+
+    pub async fn list_mutes(
+        &self,
+        chat: &ChatService,
+        actor: &ClaimUser,
+        chat_id: u64,
+    ) -> Result<Vec<ChatMute>, AppError> {
+        self.find_chat(chat, actor, chat_id).await?;
+        self.repo.extract_mutes(chat_id as _).await
+    }
+
+    /// remove the user from a channel for good, until unbanned
+    pub async fn ban(
+        &self,
+        chat: &ChatService,
+        actor: &ClaimUser,
+        chat_id: u64,
+        input: BanDto,
+    ) -> Result<ChatBan, AppError> {
+        let reason = Self::check_reason(&input.reason)?;
+        let target = self.find_chat(chat, actor, chat_id).await?;
+        if !target.chat_type.is_channel() {
+            return Err(AppError::InvalidError("only channels have bans".to_owned()));
+        }
+        Self::check_target(actor, input.user_id)?;
+        chat.check_members_exist(actor.ws_id, &[input.user_id as _])
+            .await?;
+        let ban = ChatBan {
+            chat_id: chat_id as _,
+            user_id: input.user_id as _,
+            banned_by: actor.id as _,
+            reason: reason.unwrap_or_default().to_owned(),
+            created_at: Utc::now(),
+        };
+        let ban = self.repo.save_ban(&ban).await?;
+        self.record(
+            actor,
+            AuditAction::MemberBan,
+            format!("user:{}", input.user_id),
+            json!({ "chat_id": chat_id, "reason": ban.reason }),
+        )
+        .await?;
+        Ok(ban)
+    }
+
+    /// the user may join again, they are not added back
+    pub async fn unban(
+        &self,
+        chat: &ChatService,
+        actor: &ClaimUser,
+        chat_id: u64,
+        user_id: u64,
+    ) -> Result<ChatBan, AppError> {
+        self.find_chat(chat, actor, chat_id).await?;
+        let ban = self
+            .repo
+            .delete_ban(chat_id as _, user_id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("ban of user {}", user_id)))?;
+        self.record(
+            actor,
+            AuditAction::MemberUnban,
+            format!("user:{}", user_id),
+            json!({ "chat_id": chat_id }),
+        )
+        .await?;
+        Ok(ban)
+    }
+
+    pub async fn list_bans(
+        &self,
+        chat: &ChatService,
+        actor: &ClaimUser,
+        chat_id: u64,
+    ) -> Result<Vec<ChatBan>, AppError> {
+        self.find_chat(chat, actor, chat_id).await?;
+        self.repo.extract_bans(chat_id as _).await
+    }
+
+    /// members of the channel post once per interval
+    pub async fn set_slow_mode(
+        &self,
+        chat: &ChatService,
+        actor: &ClaimUser,
+        chat_id: u64,
+        input: SlowModeDto,
+    ) -> Result<SlowModeDto, AppError> {
+        let target = self.find_chat(chat, actor, chat_id).await?;
+        if !target.chat_type.is_channel() {
+            return Err(AppError::InvalidError(
+                "only channels have a slow mode".to_owned(),
+            ));
+        }
+        if input.seconds > MAX_SLOW_MODE_SECS {
+            return Err(AppError::InvalidError(format!(
+                "slow mode is at most {} seconds",
+                MAX_SLOW_MODE_SECS
+            )));
+        }
+        let old = chat.slow_mode(chat_id).await?;
+        let seconds = self
+            .repo
+            .update_slow_mode(chat_id as _, input.seconds as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("chat {}", chat_id)))?;
+        self.record(
+            actor,
+            AuditAction::SlowModeUpdate,
+            format!("chat:{}", chat_id),
+            json!({ "from": old, "to": seconds }),
+        )
+        .await?;
+        Ok(SlowModeDto {
+            seconds: seconds as _,
+        })
+    }
+
+    /// a readable chat of the moderator's workspace
+    async fn find_chat(
+        &self,
+        chat: &ChatService,
+        actor: &ClaimUser,
+        chat_id: u64,
+    ) -> Result<Chat, AppError> {
+        chat.get_by_id(chat_id)
+            .await?
+            .filter(|c| c.ws_id == actor.ws_id as i64)
+            .ok_or_else(|| AppError::NotFound(format!("chat {}", chat_id)))
+    }
+
+    fn check_target(actor: &ClaimUser, user_id: u64) -> Result<(), AppError> {
+        if actor.id == user_id {
+            return Err(AppError::InvalidError(
+                "moderators can not moderate themselves".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_reason(reason: &str) -> Result<Option<&str>, AppError> {
+        let reason = reason.trim();
+        check_reason(reason)?;
+        Ok(Some(reason).filter(|v| !v.is_empty()))
+    }
+
+    async fn record(
+        &self,
+        actor: &ClaimUser,
+        action: AuditAction,
+        target: String,
+        detail: serde_json::Value,
+    ) -> Result<(), AppError> {
+        let event = AuditEvent::new(actor.ws_id as _, actor.id as _, action, target, detail)
+            .with_client(&actor.client);
+        self.audit.append(&event).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{
+        domain::chat::{ChatType, ContentType},
+        service::chat::{CreateChatDto, SendMsgDto},
+        test_util::{signup, state},
+    };
+
+    fn msg(content: &str) -> SendMsgDto {
+        SendMsgDto {
+            content: content.to_owned(),
+            content_type: ContentType::Plain,
+            client_id: None,
+            quote_id: None,
+            files: vec![],
+        }
+    }
+
+    #[sqlx::test]
+    async fn reported_message_should_be_resolved_by_its_deletion(pool: PgPool) {
+        let state = state(pool.clone()).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "acme").await;
+        let input = CreateChatDto {
+            name: Some("general".to_owned()),
+            chat_type: ChatType::PublicChannel,
+            members: vec![alice.id, bob.id],
+        };
+        let chat_id = state.chat.create(&alice, input).await.unwrap().id as u64;
+        let sent = state
+            .chat
+            .send_msg(&bob, chat_id, msg("spam"))
+            .await
+            .unwrap();
+        let msg_id = sent.id as u64;
+        let report = |user| {
+            let input = ReportMsgDto {
+                reason: "spam".to_owned(),
+            };
+            state
+                .moderation
+                .report(&state.chat, user, chat_id, msg_id, input)
+        };
+
+        assert!(matches!(report(&bob).await, Err(AppError::InvalidError(_))));
+        let reported = report(&alice).await.unwrap();
+        assert!(matches!(
+            report(&alice).await,
+            Err(AppError::ConflictError(_))
+        ));
+        let list = |status| ListReportsDto {
+            status,
+            last_id: None,
+            limit: None,
+        };
+        let queue = state
+            .moderation
+            .list_reports(alice.ws_id, list(ReportStatus::Open))
+            .await
+            .unwrap();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].content, "spam");
+
+        let input = ModerateDto {
+            reason: "spam".to_owned(),
+        };
+        state
+            .moderation
+            .delete_msg(&state.chat, &state.file, &alice, chat_id, msg_id, input)
+            .await
+            .unwrap();
+        let resolved = state
+            .moderation
+            .list_reports(alice.ws_id, list(ReportStatus::Resolved))
+            .await
+            .unwrap();
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(
+            (resolved[0].id, resolved[0].resolved_by, resolved[0].msg_id),
+            (reported.id, Some(alice.id as i64), None)
+        );
+        let actions: Vec<String> = sqlx::query_scalar(
+            "SELECT action::text FROM audit_events WHERE target = $1 ORDER BY id",
+        )
+        .bind(format!("message:{}", msg_id))
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(actions, vec!["message_report", "message_delete"]);
+    }
+
+    #[sqlx::test]
+    async fn muted_member_should_not_post_and_banned_user_should_not_join(pool: PgPool) {
+        let state = state(pool).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "acme").await;
+        let input = CreateChatDto {
+            name: Some("general".to_owned()),
+            chat_type: ChatType::PublicChannel,
+            members: vec![alice.id, bob.id],
+        };
+        let chat_id = state.chat.create(&alice, input).await.unwrap().id as u64;
+
+        let mute = |user_id, minutes| MuteDto {
+            user_id,
+            minutes,
+            reason: String::new(),
+        };
+        let ret = state
+            .moderation
+            .mute(&state.chat, &alice, chat_id, mute(alice.id, 5));
+        assert!(matches!(ret.await, Err(AppError::InvalidError(_))));
+        let ret = state
+            .moderation
+            .mute(&state.chat, &alice, chat_id, mute(bob.id, 0));
+        assert!(matches!(ret.await, Err(AppError::InvalidError(_))));
+        state
+            .moderation
+            .mute(&state.chat, &alice, chat_id, mute(bob.id, 5))
+            .await
+            .unwrap();
+        let ret = state.chat.send_msg(&bob, chat_id, msg("hi")).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenyError(_))));
+        state
+            .moderation
+            .unmute(&state.chat, &alice, chat_id, bob.id)
+            .await
+            .unwrap();
+        state.chat.send_msg(&bob, chat_id, msg("hi")).await.unwrap();
+
+        let input = BanDto {
+            user_id: bob.id,
+            reason: "trolling".to_owned(),
+        };
+        state
+            .moderation
+            .ban(&state.chat, &alice, chat_id, input)
+            .await
+            .unwrap();
+        assert!(!state.chat.can_access(&bob, chat_id).await.unwrap());
+        let ret = state.chat.join(&bob, chat_id).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenyError(_))));
+        state
+            .moderation
+            .unban(&state.chat, &alice, chat_id, bob.id)
+            .await
+            .unwrap();
+        state.chat.join(&bob, chat_id).await.unwrap();
+    }
+}
@@ -1,18 +1,35 @@
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
 
 use dashmap::DashMap;
 use futures::StreamExt as _;
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgListener;
-use tokio::sync::broadcast::{self, Receiver};
+use tokio::sync::{
+    broadcast::{self, Receiver},
+    mpsc, oneshot,
+};
 use tracing::{info, warn};
 
 use crate::{
-    domain::chat::{Chat, Msg},
+    common::utils::sha256_hex,
+    domain::{
+        alert::AlertRepo,
+        chat::{Bookmark, Chat, ChatStatus, MentionBroadcast, Msg, Pin},
+        command::EphemeralMsg,
+        poll::Poll,
+        schedule::ScheduledItem,
+        user::User,
+    },
     error::AppError,
 };
 
+use super::{auth::ClaimUser, user::ChatUserDto};
+
 type OnlineUserMap = DashMap<u64, broadcast::Sender<Arc<AppEvent>>>;
+type WsUserMap = DashMap<u64, HashSet<u64>>;
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "event")]
@@ -20,7 +37,26 @@ pub enum AppEvent {
     NewChat(Chat),
     AddToChat(Chat),
     RemoveFromChat(Chat),
+    UpdateChat(Chat),
+    UpdateChatHeader(Chat),
+    ChatArchived(Chat),
+    ChatRestored(Chat),
+    ChatDeleted(Chat),
+    ChatPurged(Chat),
+    MessagePinned(Pin),
+    MessageUnpinned(Pin),
+    BookmarkAdded(Bookmark),
+    BookmarkRemoved(Bookmark),
+    MemberAdded(ChatMembers),
+    MemberRemoved(ChatMembers),
     NewMessage(Msg),
+    MessageUnfurled(Msg),
+    MessageDeleted(DeletedMsg),
+    Mentioned(Msg),
+    Reminder(Reminder),
+    PollUpdated(Poll),
+    ProfileUpdated(ChatUserDto),
+    EphemeralMessage(EphemeralMsg),
 }
 
 impl AppEvent {
@@ -29,16 +65,128 @@ impl AppEvent {
             AppEvent::NewChat(_) => "new_chat",
             AppEvent::AddToChat(_) => "add_to_chat",
             AppEvent::RemoveFromChat(_) => "remove_from_chat",
+            AppEvent::UpdateChat(_) => "update_chat",
+            AppEvent::UpdateChatHeader(_) => "update_chat_header",
+            AppEvent::ChatArchived(_) => "chat_archived",
+            AppEvent::ChatRestored(_) => "chat_restored",
+            AppEvent::ChatDeleted(_) => "chat_deleted",
+            AppEvent::ChatPurged(_) => "chat_purged",
+            AppEvent::MessagePinned(_) => "message_pinned",
+            AppEvent::MessageUnpinned(_) => "message_unpinned",
+            AppEvent::BookmarkAdded(_) => "bookmark_added",
+            AppEvent::BookmarkRemoved(_) => "bookmark_removed",
+            AppEvent::MemberAdded(_) => "member_added",
+            AppEvent::MemberRemoved(_) => "member_removed",
             AppEvent::NewMessage(_) => "new_message",
+            AppEvent::MessageUnfurled(_) => "message_unfurled",
+            AppEvent::MessageDeleted(_) => "message_deleted",
+            AppEvent::Mentioned(_) => "mentioned",
+            AppEvent::Reminder(_) => "reminder",
+            AppEvent::PollUpdated(_) => "poll_updated",
+            AppEvent::ProfileUpdated(_) => "profile_updated",
+            AppEvent::EphemeralMessage(_) => "ephemeral_message",
         }
     }
+
+    /// the chat the event happened in, none for events about a user
+    pub fn chat_id(&self) -> Option<i64> {
+        match self {
+            AppEvent::NewChat(chat)
+            | AppEvent::AddToChat(chat)
+            | AppEvent::RemoveFromChat(chat)
+            | AppEvent::UpdateChat(chat)
+            | AppEvent::UpdateChatHeader(chat)
+            | AppEvent::ChatArchived(chat)
+            | AppEvent::ChatRestored(chat)
+            | AppEvent::ChatDeleted(chat)
+            | AppEvent::ChatPurged(chat) => Some(chat.id),
+            AppEvent::MessagePinned(pin) | AppEvent::MessageUnpinned(pin) => Some(pin.chat_id),
+            AppEvent::BookmarkAdded(bookmark) | AppEvent::BookmarkRemoved(bookmark) => {
+                Some(bookmark.chat_id)
+            }
+            AppEvent::MemberAdded(members) | AppEvent::MemberRemoved(members) => {
+                Some(members.chat_id)
+            }
+            AppEvent::NewMessage(msg)
+            | AppEvent::MessageUnfurled(msg)
+            | AppEvent::Mentioned(msg) => Some(msg.chat_id),
+            AppEvent::MessageDeleted(msg) => Some(msg.chat_id),
+            AppEvent::Reminder(reminder) => Some(reminder.reminder.chat_id),
+            AppEvent::PollUpdated(poll) => Some(poll.chat_id),
+            AppEvent::EphemeralMessage(msg) => Some(msg.chat_id),
+            AppEvent::ProfileUpdated(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatMembers {
+    pub chat_id: i64,
+    pub user_ids: Vec<i64>,
+}
+
+impl ChatMembers {
+    fn new(chat_id: i64, user_ids: &HashSet<u64>) -> Self {
+        let mut user_ids: Vec<i64> = user_ids.iter().map(|v| *v as _).collect();
+        user_ids.sort();
+        Self { chat_id, user_ids }
+    }
+}
+
+/// a message removed by a moderator
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeletedMsg {
+    pub chat_id: i64,
+    pub msg_id: i64,
+}
+
+// pg_notify('reminder_due', json_build_object('reminder', NEW, 'message', message_json(m))::text);
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Reminder {
+    pub reminder: ScheduledItem,
+    /// gone if the message was deleted meanwhile
+    pub message: Option<Msg>,
+}
+
+/// push and email delivery, only called for users whose chat settings allow an alert
+pub trait Alerter {
+    fn alert(&self, user_id: u64, event: &AppEvent);
+}
+
+/// stands in until a push or email provider is configured
+pub struct LogAlerter;
+
+impl Alerter for LogAlerter {
+    fn alert(&self, user_id: u64, event: &AppEvent) {
+        info!("Alerting user {} of {}", user_id, event.get_name());
+    }
+}
+
+type SharedAlerter = Arc<dyn Alerter + Send + Sync>;
+type SharedAlertRepo = Arc<dyn AlertRepo + Send + Sync>;
+type SharedTap = Arc<Mutex<Option<mpsc::Sender<TappedEvent>>>>;
+
+/// events the listener may get ahead of the consumer of the tap before it waits for it
+const TAP_CAPACITY: usize = 1024;
+
+/// an event seen by the tap, every replica gets the same key for the same event
+#[derive(Debug, Clone)]
+pub struct TappedEvent {
+    pub key: String,
+    pub event: Arc<AppEvent>,
 }
 
 pub struct NotifService {
     online_users: Arc<OnlineUserMap>,
+    ws_users: Arc<WsUserMap>,
+    // every event once, whoever it is sent to
+    tap: SharedTap,
+    alerts: SharedAlertRepo,
+    // dropped with the service, which stops the listener
+    _stop: oneshot::Sender<()>,
 }
 
-// pg_notify('chat_updated', json_build_object('op', TG_OP, 'old', OLD, 'new', NEW)::text);
+// pg_notify('chat_updated', json_build_object('op', TG_OP, 'old', chat_json(OLD), 'new', chat_json(NEW))::text);
 #[derive(Debug, Serialize, Deserialize)]
 struct ChatUpdated {
     op: String,
@@ -47,75 +195,366 @@ struct ChatUpdated {
 }
 
 impl ChatUpdated {
-    fn get_affected_chat_user_ids(&self) -> HashSet<u64> {
-        let old_users: HashSet<u64> = self
-            .old
-            .as_ref()
-            .map(|chat| chat.members.iter().map(|v| *v as _).collect())
-            .unwrap_or_default();
-        let new_users: HashSet<u64> = self
-            .new
-            .as_ref()
-            .map(|chat| chat.members.iter().map(|v| *v as _).collect())
-            .unwrap_or_default();
-        old_users.union(&new_users).cloned().collect()
-    }
-}
-
-// pg_notify('chat_message_created', row_to_json(NEW)::text);
+    /// membership changes come through chat_members_changed, here only the chat itself changed
+    fn into_notifications(self) -> Result<Vec<Notification>, AppError> {
+        let (old, new) = match (self.op.as_str(), self.old, self.new) {
+            ("UPDATE", Some(old), Some(new)) => (old, new),
+            _ => return Err(AppError::InvalidError(self.op)),
+        };
+        let user_ids: HashSet<u64> = new.members.iter().map(|v| *v as _).collect();
+        if old.status != new.status {
+            let event = match new.status {
+                ChatStatus::Active => AppEvent::ChatRestored(new),
+                ChatStatus::Archived => AppEvent::ChatArchived(new),
+                ChatStatus::Deleted => AppEvent::ChatDeleted(new),
+                ChatStatus::Purged => AppEvent::ChatPurged(new),
+            };
+            return Ok(vec![Notification::new(user_ids, event)]);
+        }
+        let mut notifications = vec![];
+        if old.topic != new.topic || old.description != new.description {
+            notifications.push(Notification::new(
+                user_ids.clone(),
+                AppEvent::UpdateChatHeader(new.clone()),
+            ));
+        }
+        if old.name != new.name || old.chat_type != new.chat_type {
+            notifications.push(Notification::new(user_ids, AppEvent::UpdateChat(new)));
+        }
+        Ok(notifications)
+    }
+}
+
+// pg_notify('chat_members_changed', json_build_object('op', TG_OP, 'chat', chat_json(REC.c), 'user_ids', REC.user_ids)::text);
+#[derive(Debug, Serialize, Deserialize)]
+struct ChatMembersChanged {
+    op: String,
+    chat: Chat,
+    user_ids: Vec<i64>,
+}
+
+impl ChatMembersChanged {
+    /// members inserted together with the chat make a new chat
+    fn into_notifications(self) -> Result<Vec<Notification>, AppError> {
+        // members of a purged chat already got chat_purged
+        if self.chat.status == ChatStatus::Purged {
+            return Ok(vec![]);
+        }
+        let changed: HashSet<u64> = self.user_ids.iter().map(|v| *v as _).collect();
+        let members: HashSet<u64> = self.chat.members.iter().map(|v| *v as _).collect();
+        let others: HashSet<u64> = members.difference(&changed).cloned().collect();
+        match self.op.as_str() {
+            "INSERT" if others.is_empty() => Ok(vec![Notification::new(
+                changed,
+                AppEvent::NewChat(self.chat),
+            )]),
+            "INSERT" => Ok(vec![
+                Notification::new(
+                    others,
+                    AppEvent::MemberAdded(ChatMembers::new(self.chat.id, &changed)),
+                ),
+                Notification::new(changed, AppEvent::AddToChat(self.chat)),
+            ]),
+            "DELETE" => Ok(vec![
+                Notification::new(
+                    others,
+                    AppEvent::MemberRemoved(ChatMembers::new(self.chat.id, &changed)),
+                ),
+                Notification::new(changed, AppEvent::RemoveFromChat(self.chat)),
+            ]),
+            _ => Err(AppError::InvalidError(self.op)),
+        }
+    }
+}
+
+// pg_notify('chat_message_created', json_build_object('message', message_json(NEW), 'members', USERS, 'alerts', ALERTS)::text);
 #[derive(Debug, Serialize, Deserialize)]
 struct MessageCreated {
     message: Msg,
     members: Vec<i64>,
+    // members not muting the chat
+    #[serde(default)]
+    alerts: Vec<i64>,
+}
+
+impl MessageCreated {
+    /// every member syncs the message, mentioned members are told and alerted even when muted
+    fn into_notifications(mut self) -> Vec<Notification> {
+        self.message.render();
+        let members: HashSet<u64> = self.members.iter().map(|v| *v as _).collect();
+        let sender = self.message.sender_id as u64;
+        let mut mentioned: HashSet<u64> = self
+            .message
+            .mentions
+            .iter()
+            .map(|v| *v as u64)
+            .filter(|v| members.contains(v))
+            .collect();
+        let mut here: HashSet<u64> = HashSet::new();
+        match self.message.mention_broadcast {
+            Some(MentionBroadcast::Channel) => mentioned.extend(members.iter()),
+            Some(MentionBroadcast::Here) => {
+                here = members.difference(&mentioned).cloned().collect()
+            }
+            None => {}
+        }
+        mentioned.remove(&sender);
+        here.remove(&sender);
+        let alerts: HashSet<u64> = self
+            .alerts
+            .iter()
+            .map(|v| *v as u64)
+            .filter(|v| !mentioned.contains(v) && !here.contains(v))
+            .collect();
+
+        let mut notifications = vec![];
+        for (user_ids, online_only) in [(mentioned, false), (here, true)] {
+            if user_ids.is_empty() {
+                continue;
+            }
+            let mut notification =
+                Notification::new(user_ids.clone(), AppEvent::Mentioned(self.message.clone()));
+            notification.alert_ids = user_ids;
+            notification.online_only = online_only;
+            notifications.push(notification);
+        }
+        let mut notification = Notification::new(members, AppEvent::NewMessage(self.message));
+        notification.alert_ids = alerts;
+        notifications.push(notification);
+        notifications
+    }
+}
+
+// pg_notify('chat_message_unfurled', json_build_object('message', message_json(NEW), 'members', USERS)::text);
+#[derive(Debug, Serialize, Deserialize)]
+struct MessageUnfurled {
+    message: Msg,
+    members: Vec<i64>,
+}
+
+// pg_notify('chat_message_deleted', json_build_object('chat_id', CHAT_ID, 'msg_id', MSG_ID, 'members', MEMBERS)::text);
+#[derive(Debug, Serialize, Deserialize)]
+struct MessageDeleted {
+    #[serde(flatten)]
+    message: DeletedMsg,
+    members: Vec<i64>,
+}
+
+// pg_notify('poll_updated', json_build_object('poll', NEW, 'members', USERS)::text);
+#[derive(Debug, Serialize, Deserialize)]
+struct PollUpdated {
+    poll: Poll,
+    members: Vec<i64>,
+}
+
+// pg_notify('chat_pin_changed', json_build_object('op', TG_OP, 'pin', REC, 'members', MEMBERS)::text);
+#[derive(Debug, Serialize, Deserialize)]
+struct PinChanged {
+    op: String,
+    pin: Pin,
+    members: Vec<i64>,
+}
+
+// pg_notify('chat_bookmark_changed', json_build_object('op', TG_OP, 'bookmark', REC, 'members', MEMBERS)::text);
+#[derive(Debug, Serialize, Deserialize)]
+struct BookmarkChanged {
+    op: String,
+    bookmark: Bookmark,
+    members: Vec<i64>,
+}
+
+// pg_notify('ws_member_removed', json_build_object('ws_id', OLD.ws_id, 'user_id', OLD.user_id)::text);
+#[derive(Debug, Serialize, Deserialize)]
+struct WsMemberRemoved {
+    ws_id: u64,
+    user_id: u64,
+}
+
+// pg_notify('user_updated', json_build_object('user', to_jsonb(NEW) - 'password_hash', 'workspaces', WORKSPACES)::text);
+#[derive(Debug, Serialize, Deserialize)]
+struct UserUpdated {
+    user: User,
+    workspaces: Option<Vec<i64>>,
 }
 
 impl NotifService {
-    pub async fn try_new(db_url: &str) -> Result<Self, AppError> {
+    pub async fn try_new(
+        db_url: &str,
+        alerter: SharedAlerter,
+        alerts: SharedAlertRepo,
+    ) -> Result<Self, AppError> {
         let online_users: Arc<OnlineUserMap> = Arc::new(Default::default());
-        Self::listen(db_url, online_users.clone()).await?;
-        Ok(Self { online_users })
+        let ws_users: Arc<WsUserMap> = Arc::new(Default::default());
+        let tap: SharedTap = Arc::new(Mutex::new(None));
+        let (stop, stopped) = oneshot::channel();
+        Self::listen(
+            db_url,
+            online_users.clone(),
+            ws_users.clone(),
+            alerter,
+            alerts.clone(),
+            tap.clone(),
+            stopped,
+        )
+        .await?;
+        Ok(Self {
+            online_users,
+            ws_users,
+            tap,
+            alerts,
+            _stop: stop,
+        })
     }
 
-    pub fn register(&self, user_id: u64) -> Receiver<Arc<AppEvent>> {
-        match self.online_users.get(&user_id) {
+    /// forget the claims of alerts that were sent
+    pub async fn prune_alerts(&self) -> Result<u64, AppError> {
+        self.alerts.prune().await
+    }
+
+    /// every event of every workspace, for a consumer other than the connected users;
+    /// the listener waits for the consumer instead of dropping events, tapping again
+    /// replaces the consumer
+    pub fn tap(&self) -> mpsc::Receiver<TappedEvent> {
+        let (tx, rx) = mpsc::channel(TAP_CAPACITY);
+        *self.tap.lock().expect("tap lock") = Some(tx);
+        rx
+    }
+
+    pub fn register(&self, user: &ClaimUser) -> Receiver<Arc<AppEvent>> {
+        self.ws_users.entry(user.ws_id).or_default().insert(user.id);
+        match self.online_users.get(&user.id) {
             Some(tx) => tx.subscribe(),
             None => {
                 let (tx, rx) = broadcast::channel(1024);
-                self.online_users.insert(user_id, tx);
+                self.online_users.insert(user.id, tx);
                 rx
             }
         }
     }
 
-    async fn listen(db_url: &str, online_users: Arc<OnlineUserMap>) -> Result<(), AppError> {
+    /// stop sending the events of a workspace the user switched away from or left
+    pub fn unregister(&self, ws_id: u64, user_id: u64) {
+        Self::remove_ws_user(&self.ws_users, ws_id, user_id);
+    }
+
+    fn remove_ws_user(ws_users: &WsUserMap, ws_id: u64, user_id: u64) {
+        ws_users.remove_if_mut(&ws_id, |_, users| {
+            users.remove(&user_id);
+            users.is_empty()
+        });
+    }
+
+    async fn listen(
+        db_url: &str,
+        online_users: Arc<OnlineUserMap>,
+        ws_users: Arc<WsUserMap>,
+        alerter: SharedAlerter,
+        alerts: SharedAlertRepo,
+        tap: SharedTap,
+        mut stopped: oneshot::Receiver<()>,
+    ) -> Result<(), AppError> {
         let mut listener = PgListener::connect(db_url).await?;
         listener.listen("chat_updated").await?;
+        listener.listen("chat_members_changed").await?;
         listener.listen("chat_message_created").await?;
+        listener.listen("chat_message_unfurled").await?;
+        listener.listen("chat_message_deleted").await?;
+        listener.listen("chat_pin_changed").await?;
+        listener.listen("chat_bookmark_changed").await?;
+        listener.listen("reminder_due").await?;
+        listener.listen("poll_updated").await?;
+        listener.listen("user_updated").await?;
+        listener.listen("ephemeral_message").await?;
+        listener.listen("ws_member_removed").await?;
 
         // { process_id: 2801, channel: "chat_message_created", payload: "{\"message\" : {\"id\":7,\"chat_id\":1,\"sender_id\":1,\"content\":\"this is a test message\",\"created_at\":\"2024-11-17T00:57:45.398913+00:00\"}, \"members\" : [1,2]}" }
         // { process_id: 2801, channel: "chat_updated", payload: "{\"op\" : \"INSERT\", \"old\" : null, \"new\" : {\"id\":8,\"ws_id\":1,\"name\":\"test chat new b\",\"chat_type\":\"public_channel\",\"members\":[1,4],\"status\":1,\"created_at\":\"2024-11-17T01:01:32.372249+00:00\"}}" }
         let mut stream = listener.into_stream();
         tokio::spawn(async move {
-            while let Some(Ok(notif)) = stream.next().await {
+            loop {
+                let notif = tokio::select! {
+                    notif = stream.next() => notif,
+                    _ = &mut stopped => break,
+                };
+                let Some(Ok(notif)) = notif else {
+                    break;
+                };
                 println!(
                     "Received notification: {:?}, current listen user: {:?}",
                     notif,
                     online_users.len()
                 );
-                let notification = Notification::try_load(notif.channel(), notif.payload())?;
-                for user_id in notification.user_ids {
-                    if let Some(tx) = online_users.get(&user_id) {
-                        info!("Sending notification to user {}", user_id);
-                        if let Err(e) = tx.send(notification.event.clone()) {
-                            warn!(
-                                "Failed to send notification to user {}, error: {}",
-                                user_id, e
-                            );
+                // members removed on any replica are forgotten on every replica
+                if notif.channel() == "ws_member_removed" {
+                    match serde_json::from_str::<WsMemberRemoved>(notif.payload()) {
+                        Ok(removed) => {
+                            Self::remove_ws_user(&ws_users, removed.ws_id, removed.user_id)
+                        }
+                        Err(e) => warn!("Failed to load removed member, error: {}", e),
+                    }
+                    continue;
+                }
+                // a payload that can not be read must not stop the fan-out of the others
+                let notifications = match Notification::try_load(notif.channel(), notif.payload()) {
+                    Ok(notifications) => notifications,
+                    Err(e) => {
+                        warn!(
+                            "Failed to load notification of {}, error: {}",
+                            notif.channel(),
+                            e
+                        );
+                        continue;
+                    }
+                };
+                for (i, mut notification) in notifications.into_iter().enumerate() {
+                    for ws_id in &notification.ws_ids {
+                        if let Some(users) = ws_users.get(ws_id) {
+                            notification.user_ids.extend(users.iter());
+                        }
+                    }
+                    if notification.online_only {
+                        notification
+                            .alert_ids
+                            .retain(|user_id| online_users.contains_key(user_id));
+                    }
+                    // the payload is what every replica was notified of
+                    let key = sha256_hex(&format!("{}:{}:{}", notif.channel(), i, notif.payload()));
+                    if !notification.alert_ids.is_empty() {
+                        // every replica is notified, only the one that claims an alert sends it
+                        let user_ids: Vec<u64> = notification.alert_ids.iter().copied().collect();
+                        match alerts.claim(&key, &user_ids).await {
+                            Ok(claimed) => {
+                                for user_id in claimed {
+                                    alerter.alert(user_id, &notification.event);
+                                }
+                            }
+                            Err(e) => warn!("Failed to claim alerts, error: {}", e),
+                        }
+                    }
+                    // the notifications wait in the listener while the consumer catches up
+                    let consumer = tap.lock().expect("tap lock").clone();
+                    if let Some(consumer) = consumer {
+                        let tapped = TappedEvent {
+                            key,
+                            event: notification.event.clone(),
+                        };
+                        if consumer.send(tapped).await.is_err() {
+                            warn!("The tap of {} is gone", notif.channel());
+                        }
+                    }
+                    for user_id in notification.user_ids {
+                        if let Some(tx) = online_users.get(&user_id) {
+                            info!("Sending notification to user {}", user_id);
+                            if let Err(e) = tx.send(notification.event.clone()) {
+                                warn!(
+                                    "Failed to send notification to user {}, error: {}",
+                                    user_id, e
+                                );
+                            }
                         }
                     }
                 }
             }
-            Ok::<_, AppError>(())
         });
         Ok(())
     }
@@ -124,35 +563,342 @@ impl NotifService {
 #[derive(Debug)]
 struct Notification {
     user_ids: HashSet<u64>,
+    // online users of these workspaces are notified as well
+    ws_ids: Vec<u64>,
+    // every user gets the event to sync state, these are alerted as well
+    alert_ids: HashSet<u64>,
+    // alert only users online at fan-out, for @here
+    online_only: bool,
     event: Arc<AppEvent>,
 }
 
 impl Notification {
-    fn try_load(rtype: &str, payload: &str) -> Result<Self, AppError> {
+    fn new(user_ids: HashSet<u64>, event: AppEvent) -> Self {
+        Self {
+            user_ids,
+            ws_ids: vec![],
+            alert_ids: HashSet::new(),
+            online_only: false,
+            event: Arc::new(event),
+        }
+    }
+
+    fn try_load(rtype: &str, payload: &str) -> Result<Vec<Self>, AppError> {
         match rtype {
             "chat_updated" => {
                 let payload: ChatUpdated = serde_json::from_str(payload)?;
-                let user_ids = payload.get_affected_chat_user_ids();
+                payload.into_notifications()
+            }
+            "chat_members_changed" => {
+                let payload: ChatMembersChanged = serde_json::from_str(payload)?;
+                payload.into_notifications()
+            }
+            "chat_message_created" => {
+                let payload: MessageCreated = serde_json::from_str(payload)?;
+                Ok(payload.into_notifications())
+            }
+            "chat_message_unfurled" => {
+                let mut payload: MessageUnfurled = serde_json::from_str(payload)?;
+                payload.message.render();
+                let user_ids = payload.members.iter().map(|v| *v as u64).collect();
+                Ok(vec![Self::new(
+                    user_ids,
+                    AppEvent::MessageUnfurled(payload.message),
+                )])
+            }
+            "chat_message_deleted" => {
+                let payload: MessageDeleted = serde_json::from_str(payload)?;
+                let user_ids = payload.members.iter().map(|v| *v as u64).collect();
+                Ok(vec![Self::new(
+                    user_ids,
+                    AppEvent::MessageDeleted(payload.message),
+                )])
+            }
+            "chat_pin_changed" => {
+                let payload: PinChanged = serde_json::from_str(payload)?;
+                let user_ids = payload.members.iter().map(|v| *v as u64).collect();
                 let event = match payload.op.as_str() {
-                    "INSERT" => AppEvent::NewChat(payload.new.unwrap()),
-                    "UPDATE" => AppEvent::AddToChat(payload.new.unwrap()),
-                    "DELETE" => AppEvent::RemoveFromChat(payload.old.unwrap()),
+                    "INSERT" => AppEvent::MessagePinned(payload.pin),
+                    "DELETE" => AppEvent::MessageUnpinned(payload.pin),
                     _ => return Err(AppError::InvalidError(payload.op)),
                 };
-                Ok(Self {
-                    user_ids,
-                    event: Arc::new(event),
-                })
+                Ok(vec![Self::new(user_ids, event)])
             }
-            "chat_message_created" => {
-                let payload: MessageCreated = serde_json::from_str(payload)?;
+            "chat_bookmark_changed" => {
+                let payload: BookmarkChanged = serde_json::from_str(payload)?;
+                let user_ids = payload.members.iter().map(|v| *v as u64).collect();
+                let event = match payload.op.as_str() {
+                    "INSERT" => AppEvent::BookmarkAdded(payload.bookmark),
+                    "DELETE" => AppEvent::BookmarkRemoved(payload.bookmark),
+                    _ => return Err(AppError::InvalidError(payload.op)),
+                };
+                Ok(vec![Self::new(user_ids, event)])
+            }
+            "poll_updated" => {
+                let payload: PollUpdated = serde_json::from_str(payload)?;
                 let user_ids = payload.members.iter().map(|v| *v as u64).collect();
-                Ok(Self {
+                Ok(vec![Self::new(
                     user_ids,
-                    event: Arc::new(AppEvent::NewMessage(payload.message)),
-                })
+                    AppEvent::PollUpdated(payload.poll),
+                )])
+            }
+            "reminder_due" => {
+                let mut payload: Reminder = serde_json::from_str(payload)?;
+                if let Some(message) = payload.message.as_mut() {
+                    message.render();
+                }
+                let user_ids = HashSet::from([payload.reminder.user_id as u64]);
+                let mut notification = Self::new(user_ids.clone(), AppEvent::Reminder(payload));
+                notification.alert_ids = user_ids;
+                Ok(vec![notification])
+            }
+            "user_updated" => {
+                let payload: UserUpdated = serde_json::from_str(payload)?;
+                let mut notification = Self::new(
+                    HashSet::from([payload.user.id as u64]),
+                    AppEvent::ProfileUpdated(payload.user.into()),
+                );
+                notification.ws_ids = payload
+                    .workspaces
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|v| v as u64)
+                    .collect();
+                Ok(vec![notification])
+            }
+            // pg_notify('ephemeral_message', EPHEMERAL_MSG) from the command that was run
+            "ephemeral_message" => {
+                let payload: EphemeralMsg = serde_json::from_str(payload)?;
+                let user_ids = HashSet::from([payload.user_id as u64]);
+                Ok(vec![Self::new(
+                    user_ids,
+                    AppEvent::EphemeralMessage(payload),
+                )])
+            }
+            _ => Err(AppError::InvalidError(rtype.to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{
+        domain::chat::{ChatType, ContentType, NotifLevel},
+        service::chat::{
+            AddMembersDto, CreateChatDto, ListChatsDto, SendMsgDto, UpdateChatPrefsDto,
+        },
+        test_util::{signup, state},
+    };
+
+    fn is_registered(notif: &NotifService, ws_id: u64, user_id: u64) -> bool {
+        notif
+            .ws_users
+            .get(&ws_id)
+            .is_some_and(|users| users.contains(&user_id))
+    }
+
+    /// the next event of this kind, the others are skipped
+    async fn wait_for(rx: &mut Receiver<Arc<AppEvent>>, name: &str) -> Arc<AppEvent> {
+        let wait = async {
+            loop {
+                let event = rx.recv().await.unwrap();
+                if event.get_name() == name {
+                    return event;
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), wait)
+            .await
+            .unwrap_or_else(|_| panic!("no {} event", name))
+    }
+
+    #[sqlx::test]
+    async fn members_should_be_told_who_joined_and_left(pool: PgPool) {
+        let state = state(pool).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "acme").await;
+        let carol = signup(&state, "carol", "acme").await;
+        let mut alice_rx = state.notif.register(&alice);
+        let mut bob_rx = state.notif.register(&bob);
+        let mut carol_rx = state.notif.register(&carol);
+
+        let input = CreateChatDto {
+            name: Some("general".to_owned()),
+            chat_type: ChatType::PublicChannel,
+            members: vec![alice.id, bob.id],
+        };
+        let chat = state.chat.create(&alice, input).await.unwrap();
+        wait_for(&mut alice_rx, "new_chat").await;
+        wait_for(&mut bob_rx, "new_chat").await;
+
+        let input = AddMembersDto {
+            members: vec![carol.id],
+        };
+        state
+            .chat
+            .add_members(&alice, chat.id as _, input)
+            .await
+            .unwrap();
+        match wait_for(&mut carol_rx, "add_to_chat").await.as_ref() {
+            AppEvent::AddToChat(added) => assert_eq!(added.id, chat.id),
+            event => panic!("unexpected {:?}", event),
+        }
+        for rx in [&mut alice_rx, &mut bob_rx] {
+            match wait_for(rx, "member_added").await.as_ref() {
+                AppEvent::MemberAdded(diff) => assert_eq!(diff.user_ids, vec![carol.id as i64]),
+                event => panic!("unexpected {:?}", event),
+            }
+        }
+
+        state.chat.leave(&bob, chat.id as _).await.unwrap();
+        wait_for(&mut bob_rx, "remove_from_chat").await;
+        for rx in [&mut alice_rx, &mut carol_rx] {
+            match wait_for(rx, "member_removed").await.as_ref() {
+                AppEvent::MemberRemoved(diff) => assert_eq!(diff.user_ids, vec![bob.id as i64]),
+                event => panic!("unexpected {:?}", event),
+            }
+        }
+    }
+
+    /// the users alerted of the next alert claimed by the replica
+    async fn next_alerted(pool: &PgPool) -> Vec<i64> {
+        for _ in 0..50 {
+            let alerted: Vec<i64> =
+                sqlx::query_scalar("DELETE FROM alert_claims RETURNING user_id")
+                    .fetch_all(pool)
+                    .await
+                    .unwrap();
+            if !alerted.is_empty() {
+                let mut alerted = alerted;
+                alerted.sort();
+                return alerted;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("nobody was alerted");
+    }
+
+    #[sqlx::test]
+    async fn members_should_be_alerted_as_their_chat_prefs_say(pool: PgPool) {
+        let state = state(pool.clone()).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "acme").await;
+        let carol = signup(&state, "carol", "acme").await;
+        let dave = signup(&state, "dave", "acme").await;
+        let input = CreateChatDto {
+            name: Some("general".to_owned()),
+            chat_type: ChatType::PublicChannel,
+            members: vec![alice.id, bob.id, carol.id, dave.id],
+        };
+        let chat_id = state.chat.create(&alice, input).await.unwrap().id as u64;
+        let prefs = |muted_until, notif_level| UpdateChatPrefsDto {
+            muted_until,
+            favorite: Some(true),
+            hidden: None,
+            notif_level,
+        };
+        let muted = Some(Some(chrono::Utc::now() + chrono::Duration::hours(1)));
+        state
+            .chat
+            .update_prefs(&bob, chat_id, prefs(muted, None))
+            .await
+            .unwrap();
+        state
+            .chat
+            .update_prefs(&carol, chat_id, prefs(None, Some(NotifLevel::Mentions)))
+            .await
+            .unwrap();
+        let chats = state
+            .chat
+            .list_all(bob.id, bob.ws_id, ListChatsDto { status: None })
+            .await
+            .unwrap();
+        assert!(chats[0].prefs.favorite && chats[0].prefs.muted_until.is_some());
+
+        let send = |content: &str| SendMsgDto {
+            content: content.to_owned(),
+            content_type: ContentType::Plain,
+            client_id: None,
+            quote_id: None,
+            files: vec![],
+        };
+        state
+            .chat
+            .send_msg(&alice, chat_id, send("hi"))
+            .await
+            .unwrap();
+        assert_eq!(next_alerted(&pool).await, vec![dave.id as i64]);
+    }
+
+    #[sqlx::test]
+    async fn mentioned_member_should_be_alerted_even_when_muted(pool: PgPool) {
+        let state = state(pool.clone()).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "acme").await;
+        let carol = signup(&state, "carol", "acme").await;
+        let input = CreateChatDto {
+            name: Some("general".to_owned()),
+            chat_type: ChatType::PublicChannel,
+            members: vec![alice.id, bob.id],
+        };
+        let chat_id = state.chat.create(&alice, input).await.unwrap().id as u64;
+        let input = UpdateChatPrefsDto {
+            muted_until: Some(Some(chrono::Utc::now() + chrono::Duration::hours(1))),
+            favorite: None,
+            hidden: None,
+            notif_level: None,
+        };
+        state.chat.update_prefs(&bob, chat_id, input).await.unwrap();
+        let mut bob_rx = state.notif.register(&bob);
+
+        // carol is not a member, she is not mentioned
+        let input = SendMsgDto {
+            content: "@Bob @carol please review".to_owned(),
+            content_type: ContentType::Plain,
+            client_id: None,
+            quote_id: None,
+            files: vec![],
+        };
+        let sent = state.chat.send_msg(&alice, chat_id, input).await.unwrap();
+        assert_eq!(sent.mentions, vec![bob.id as i64]);
+        wait_for(&mut bob_rx, "mentioned").await;
+        assert_eq!(next_alerted(&pool).await, vec![bob.id as i64]);
+
+        let mentions = state
+            .chat
+            .list_mentions(&bob, i64::MAX as _, 10)
+            .await
+            .unwrap();
+        assert_eq!(mentions.len(), 1);
+        assert!(state
+            .chat
+            .list_mentions(&carol, i64::MAX as _, 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[sqlx::test]
+    async fn removed_member_should_not_get_the_events_of_the_workspace(pool: PgPool) {
+        let state = state(pool).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "acme").await;
+        let _rx = state.notif.register(&bob);
+        assert!(is_registered(&state.notif, bob.ws_id, bob.id));
+
+        state.workspace.remove_member(&alice, bob.id).await.unwrap();
+        // the removal reaches the replica as a notification
+        for _ in 0..50 {
+            if !is_registered(&state.notif, bob.ws_id, bob.id) {
+                return;
             }
-            _ => return Err(AppError::InvalidError(rtype.to_owned())),
+            tokio::time::sleep(Duration::from_millis(100)).await;
         }
+        panic!("bob is still registered to the workspace");
     }
 }
@@ -0,0 +1,230 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    domain::{
+        chat::{ContentType, Msg},
+        poll::{Poll, PollRepo},
+    },
+    error::AppError,
+};
+
+use super::{auth::ClaimUser, chat::ChatService};
+
+pub struct PollService {
+    repo: Box<dyn PollRepo + Send + Sync>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatePollDto {
+    pub question: String,
+    pub options: Vec<String>,
+    #[serde(default)]
+    pub multiple: bool,
+    #[serde(default)]
+    pub anonymous: bool,
+    pub closes_at: Option<DateTime<Utc>>,
+}
+
+/// indexes of the chosen options, empty to retract
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VoteDto {
+    pub options: Vec<i16>,
+}
+
+/// a poll as seen by one member
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PollView {
+    #[serde(flatten)]
+    pub poll: Poll,
+    pub my_votes: Vec<i16>,
+    /// voters per option, not given for anonymous polls
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub option_voters: Option<Vec<Vec<i64>>>,
+}
+
+impl PollService {
+    pub fn new(repo: Box<dyn PollRepo + Send + Sync>) -> Self {
+        Self { repo }
+    }
+
+    pub async fn create(
+        &self,
+        chat: &ChatService,
+        user: &ClaimUser,
+        chat_id: u64,
+        input: CreatePollDto,
+    ) -> Result<Poll, AppError> {
+        chat.check_can_post(user, chat_id).await?;
+        let mut poll = Poll::new(chat_id as _, input.question, input.options, user.id as _);
+        poll.multiple = input.multiple;
+        poll.anonymous = input.anonymous;
+        poll.closes_at = input.closes_at;
+        poll.validate()?;
+        let mut msg = Msg::new(chat_id as _, user.id as _, poll.question.clone());
+        msg.content_type = ContentType::Poll;
+        let (_, poll) = self.repo.save(&msg, &poll).await?;
+        Ok(poll)
+    }
+
+    pub async fn get(
+        &self,
+        user: &ClaimUser,
+        chat_id: u64,
+        msg_id: u64,
+    ) -> Result<PollView, AppError> {
+        let poll = self.find_poll(chat_id, msg_id).await?;
+        self.view(user, poll).await
+    }
+
+    pub async fn vote(
+        &self,
+        chat: &ChatService,
+        user: &ClaimUser,
+        chat_id: u64,
+        msg_id: u64,
+        mut input: VoteDto,
+    ) -> Result<PollView, AppError> {
+        chat.find_active_chat(chat_id).await?;
+        let poll = self.find_poll(chat_id, msg_id).await?;
+        if !poll.is_open() {
+            return Err(AppError::InvalidError(format!("poll {} is closed", msg_id)));
+        }
+        poll.check_choice(&mut input.options)?;
+        let poll = self
+            .repo
+            .vote(poll.msg_id, user.id as _, &input.options)
+            .await?
+            .ok_or_else(|| AppError::InvalidError(format!("poll {} is closed", msg_id)))?;
+        self.view(user, poll).await
+    }
+
+    /// only the creator closes a poll before its closing time
+    pub async fn close(
+        &self,
+        chat: &ChatService,
+        user: &ClaimUser,
+        chat_id: u64,
+        msg_id: u64,
+    ) -> Result<Poll, AppError> {
+        chat.find_active_chat(chat_id).await?;
+        let poll = self.find_poll(chat_id, msg_id).await?;
+        if poll.created_by != user.id as i64 {
+            return Err(AppError::PermissionDenyError(
+                "only the creator closes a poll".to_owned(),
+            ));
+        }
+        Ok(self.repo.close(poll.msg_id).await?.unwrap_or(poll))
+    }
+
+    async fn find_poll(&self, chat_id: u64, msg_id: u64) -> Result<Poll, AppError> {
+        self.repo
+            .extract_by_msg(chat_id as _, msg_id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("poll {}", msg_id)))
+    }
+
+    async fn view(&self, user: &ClaimUser, poll: Poll) -> Result<PollView, AppError> {
+        let votes = self.repo.extract_votes(poll.msg_id).await?;
+        let my_votes = votes
+            .iter()
+            .filter(|v| v.user_id == user.id as i64)
+            .map(|v| v.option)
+            .collect();
+        let option_voters = (!poll.anonymous).then(|| {
+            let mut voters = vec![vec![]; poll.options.len()];
+            for vote in &votes {
+                if let Some(users) = voters.get_mut(vote.option as usize) {
+                    users.push(vote.user_id);
+                }
+            }
+            voters
+        });
+        Ok(PollView {
+            poll,
+            my_votes,
+            option_voters,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{
+        domain::chat::ChatType,
+        service::chat::CreateChatDto,
+        test_util::{signup, state},
+    };
+
+    #[sqlx::test]
+    async fn vote_should_count_each_member_once(pool: PgPool) {
+        let state = state(pool).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "acme").await;
+        let carol = signup(&state, "carol", "acme").await;
+        let input = CreateChatDto {
+            name: Some("general".to_owned()),
+            chat_type: ChatType::PublicChannel,
+            members: vec![alice.id, bob.id, carol.id],
+        };
+        let chat_id = state.chat.create(&alice, input).await.unwrap().id as u64;
+        let input = CreatePollDto {
+            question: " Lunch? ".to_owned(),
+            options: vec!["pizza".to_owned(), "sushi".to_owned()],
+            multiple: false,
+            anonymous: true,
+            closes_at: None,
+        };
+        let poll = state
+            .poll
+            .create(&state.chat, &alice, chat_id, input)
+            .await
+            .unwrap();
+        assert_eq!(poll.question, "Lunch?");
+        let msg_id = poll.msg_id as u64;
+        let vote = |user, options| {
+            state
+                .poll
+                .vote(&state.chat, user, chat_id, msg_id, VoteDto { options })
+        };
+
+        for options in [vec![0, 1], vec![2]] {
+            assert!(matches!(
+                vote(&alice, options).await,
+                Err(AppError::InvalidError(_))
+            ));
+        }
+        let (a, b, c) = tokio::join!(
+            vote(&alice, vec![0]),
+            vote(&bob, vec![0]),
+            vote(&carol, vec![1])
+        );
+        let (a, b, c) = (a.unwrap(), b.unwrap(), c.unwrap());
+        assert_eq!(a.my_votes, vec![0]);
+        assert!(a.option_voters.is_none() && b.option_voters.is_none());
+        assert_eq!(c.my_votes, vec![1]);
+        // a member changes and retracts a vote
+        vote(&bob, vec![1]).await.unwrap();
+        let view = vote(&carol, vec![]).await.unwrap();
+        assert!(view.my_votes.is_empty());
+        assert_eq!((view.poll.counts, view.poll.voters), (vec![1, 1], 2));
+
+        let ret = state.poll.close(&state.chat, &bob, chat_id, msg_id).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenyError(_))));
+        let poll = state
+            .poll
+            .close(&state.chat, &alice, chat_id, msg_id)
+            .await
+            .unwrap();
+        assert!(poll.closed);
+        assert!(matches!(
+            vote(&carol, vec![0]).await,
+            Err(AppError::InvalidError(_))
+        ));
+        let view = state.poll.get(&bob, chat_id, msg_id).await.unwrap();
+        assert_eq!((view.poll.counts, view.my_votes), (vec![1, 1], vec![1]));
+    }
+}
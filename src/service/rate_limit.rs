@@ -0,0 +1,237 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use axum::async_trait;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    domain::rate_limit::{Quota, RateLimit, RateLimitRepo},
+    error::AppError,
+};
+
+use super::auth::ClaimUser;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    /// buckets are kept in postgres so every replica sees them,
+    /// the in-process buckets are used when it is off or unreachable
+    #[serde(default = "default_shared")]
+    pub shared: bool,
+    /// for every route without its own limit
+    pub default: RateLimit,
+    /// keyed by method and route, like `POST /api/chats/:id`
+    #[serde(default)]
+    pub routes: HashMap<String, RateLimit>,
+}
+
+fn default_shared() -> bool {
+    true
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            shared: true,
+            default: RateLimit {
+                burst: 60,
+                per_minute: 600,
+            },
+            routes: HashMap::from([(
+                "POST /api/chats/:id".to_owned(),
+                RateLimit {
+                    burst: 10,
+                    per_minute: 60,
+                },
+            )]),
+        }
+    }
+}
+
+/// buckets of this process only
+#[derive(Default)]
+pub struct LocalRateLimitRepo {
+    buckets: DashMap<String, Instant>,
+}
+
+#[async_trait]
+impl RateLimitRepo for LocalRateLimitRepo {
+    async fn take(&self, key: &str, quota: &Quota) -> Result<Option<Duration>, AppError> {
+        let now = Instant::now();
+        let mut tat = self.buckets.entry(key.to_owned()).or_insert(now);
+        let next = (*tat).max(now) + quota.interval;
+        let booked = next - now;
+        if booked > quota.tolerance() {
+            return Ok(Some(booked - quota.tolerance()));
+        }
+        *tat = next;
+        Ok(None)
+    }
+
+    async fn prune(&self) -> Result<u64, AppError> {
+        let now = Instant::now();
+        let before = self.buckets.len();
+        self.buckets.retain(|_, tat| *tat > now);
+        Ok((before - self.buckets.len()) as _)
+    }
+}
+
+pub struct RateLimitService {
+    shared: Option<Box<dyn RateLimitRepo + Send + Sync>>,
+    local: LocalRateLimitRepo,
+    default: Quota,
+    routes: HashMap<String, Quota>,
+}
+
+impl RateLimitService {
+    pub fn try_new(
+        config: &RateLimitConfig,
+        shared: Box<dyn RateLimitRepo + Send + Sync>,
+    ) -> Result<Self, AppError> {
+        config.default.validate()?;
+        let mut routes = HashMap::new();
+        for (route, limit) in &config.routes {
+            limit.validate()?;
+            routes.insert(route.clone(), limit.quota());
+        }
+        Ok(Self {
+            shared: config.shared.then_some(shared),
+            local: Default::default(),
+            default: config.default.quota(),
+            routes,
+        })
+    }
+
+    /// take a token from the bucket of the user, route and chat,
+    /// the wait until the request may be retried is returned as an error
+    pub async fn check(
+        &self,
+        user: &ClaimUser,
+        route: &str,
+        chat_id: Option<u64>,
+    ) -> Result<(), AppError> {
+        let quota = self.routes.get(route).unwrap_or(&self.default);
+        let key = match chat_id {
+            Some(chat_id) => format!("{}:{}:{}", user.id, route, chat_id),
+            None => format!("{}:{}", user.id, route),
+        };
+        self.take(&key, quota).await
+    }
+
+    /// one message of the user per `secs` in the chat, whatever posts it
+    pub async fn slow_mode(&self, chat_id: u64, user_id: u64, secs: u32) -> Result<(), AppError> {
+        let key = format!("slow:{}:{}", chat_id, user_id);
+        self.take(&key, &Quota::slow_mode(secs)).await
+    }
+
+    async fn take(&self, key: &str, quota: &Quota) -> Result<(), AppError> {
+        let wait = match &self.shared {
+            Some(shared) => match shared.take(key, quota).await {
+                Ok(wait) => wait,
+                Err(e) => {
+                    warn!("Failed to use shared rate limits, error: {}", e);
+                    self.local.take(key, quota).await?
+                }
+            },
+            None => self.local.take(key, quota).await?,
+        };
+        match wait {
+            Some(wait) => Err(AppError::TooManyRequests(
+                wait.as_secs_f64().ceil().max(1.0) as _,
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// forget the full buckets, the count of forgotten ones is returned
+    pub async fn prune(&self) -> Result<u64, AppError> {
+        let mut pruned = self.local.prune().await?;
+        if let Some(shared) = &self.shared {
+            pruned += shared.prune().await?;
+        }
+        Ok(pruned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::rate_limit::RateLimit;
+
+    /// a route allowing a burst of 3, then one request a minute
+    fn config(shared: bool) -> RateLimitConfig {
+        RateLimitConfig {
+            shared,
+            default: RateLimit {
+                burst: 100,
+                per_minute: 600,
+            },
+            routes: HashMap::from([(
+                "POST /api/chats/:id".to_owned(),
+                RateLimit {
+                    burst: 3,
+                    per_minute: 1,
+                },
+            )]),
+        }
+    }
+
+    fn user(id: u64) -> ClaimUser {
+        ClaimUser {
+            id,
+            ws_id: 1,
+            ver: 0,
+            client: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn local_bucket_should_allow_the_burst_then_refuse() {
+        let repo = LocalRateLimitRepo::default();
+        let quota = Quota {
+            burst: 3,
+            interval: Duration::from_secs(60),
+        };
+        for _ in 0..3 {
+            assert_eq!(repo.take("key", &quota).await.unwrap(), None);
+        }
+        let wait = repo.take("key", &quota).await.unwrap().unwrap();
+        assert!(wait > Duration::from_secs(59) && wait <= Duration::from_secs(60));
+        // a refused request books nothing
+        let again = repo.take("key", &quota).await.unwrap().unwrap();
+        assert!(again <= wait);
+        assert_eq!(repo.take("other", &quota).await.unwrap(), None);
+        assert_eq!(repo.prune().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn check_should_limit_each_user_route_and_chat() {
+        let limits =
+            RateLimitService::try_new(&config(false), Box::new(LocalRateLimitRepo::default()))
+                .unwrap();
+        let route = "POST /api/chats/:id";
+        for _ in 0..3 {
+            limits.check(&user(1), route, Some(1)).await.unwrap();
+        }
+        let ret = limits.check(&user(1), route, Some(1)).await;
+        assert!(matches!(ret, Err(AppError::TooManyRequests(60))));
+        limits.check(&user(1), route, Some(2)).await.unwrap();
+        limits.check(&user(2), route, Some(1)).await.unwrap();
+        limits
+            .check(&user(1), "GET /api/chats", None)
+            .await
+            .unwrap();
+
+        limits.slow_mode(1, 1, 30).await.unwrap();
+        let ret = limits.slow_mode(1, 1, 30).await;
+        assert!(matches!(ret, Err(AppError::TooManyRequests(30))));
+
+        let mut invalid = config(false);
+        invalid.default.burst = 0;
+        let ret = RateLimitService::try_new(&invalid, Box::new(LocalRateLimitRepo::default()));
+        assert!(matches!(ret, Err(AppError::InvalidError(_))));
+    }
+}
@@ -0,0 +1,278 @@
+use serde::Deserialize;
+use serde_json::json;
+use tracing::warn;
+
+use crate::{
+    domain::{
+        audit::{AuditAction, AuditEvent, AuditRepo},
+        retention::{ExpiringChat, RetentionMode, RetentionPolicy, RetentionRepo},
+    },
+    error::AppError,
+};
+
+use super::{auth::ClaimUser, file::FileService};
+
+const SWEEP_CHATS: i64 = 50;
+const DELETE_BATCH: i64 = 500;
+/// a chat with a long backlog is finished over several sweeps
+const MAX_BATCHES_PER_CHAT: usize = 20;
+
+pub struct RetentionService {
+    repo: Box<dyn RetentionRepo + Send + Sync>,
+    audit: Box<dyn AuditRepo + Send + Sync>,
+}
+
+/// omitted fields keep their value
+#[derive(Debug, Deserialize)]
+pub struct UpdateRetentionDto {
+    pub retention: Option<RetentionMode>,
+    pub retention_days: Option<i32>,
+    pub legal_hold: Option<bool>,
+}
+
+impl UpdateRetentionDto {
+    fn apply(self, policy: &mut RetentionPolicy) {
+        if let Some(retention) = self.retention {
+            policy.retention = retention;
+        }
+        if self.retention_days.is_some() {
+            policy.retention_days = self.retention_days;
+        }
+        if let Some(legal_hold) = self.legal_hold {
+            policy.legal_hold = legal_hold;
+        }
+    }
+}
+
+impl RetentionService {
+    pub fn new(
+        repo: Box<dyn RetentionRepo + Send + Sync>,
+        audit: Box<dyn AuditRepo + Send + Sync>,
+    ) -> Self {
+        Self { repo, audit }
+    }
+
+    pub async fn get_ws_policy(&self, ws_id: u64) -> Result<RetentionPolicy, AppError> {
+        self.repo
+            .extract_ws_policy(ws_id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("workspace {}", ws_id)))
+    }
+
+    pub async fn update_ws_policy(
+        &self,
+        actor: &ClaimUser,
+        input: UpdateRetentionDto,
+    ) -> Result<RetentionPolicy, AppError> {
+        let old = self.get_ws_policy(actor.ws_id).await?;
+        let mut policy = old.clone();
+        input.apply(&mut policy);
+        policy.validate(true)?;
+        let policy = self
+            .repo
+            .update_ws_policy(actor.ws_id as _, &policy)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("workspace {}", actor.ws_id)))?;
+        self.record(actor, format!("workspace:{}", actor.ws_id), &old, &policy)
+            .await?;
+        Ok(policy)
+    }
+
+    pub async fn get_chat_policy(
+        &self,
+        ws_id: u64,
+        chat_id: u64,
+    ) -> Result<RetentionPolicy, AppError> {
+        self.repo
+            .extract_chat_policy(ws_id as _, chat_id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("chat {}", chat_id)))
+    }
+
+    pub async fn update_chat_policy(
+        &self,
+        actor: &ClaimUser,
+        chat_id: u64,
+        input: UpdateRetentionDto,
+    ) -> Result<RetentionPolicy, AppError> {
+        let old = self.get_chat_policy(actor.ws_id, chat_id).await?;
+        let mut policy = old.clone();
+        input.apply(&mut policy);
+        policy.validate(false)?;
+        let policy = self
+            .repo
+            .update_chat_policy(actor.ws_id as _, chat_id as _, &policy)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("chat {}", chat_id)))?;
+        self.record(actor, format!("chat:{}", chat_id), &old, &policy)
+            .await?;
+        Ok(policy)
+    }
+
+    /// delete messages older than the retention of their chat, every replica may run it
+    /// since a message is deleted once, the count of deleted messages is returned
+    pub async fn sweep(&self, files: &FileService) -> Result<usize, AppError> {
+        let chats = self.repo.extract_expiring_chats(SWEEP_CHATS).await?;
+        let mut deleted = 0;
+        for chat in chats {
+            match self.sweep_chat(files, &chat).await {
+                Ok(count) => deleted += count,
+                Err(e) => warn!("Failed to sweep chat {}, error: {}", chat.chat_id, e),
+            }
+        }
+        Ok(deleted)
+    }
+
+    async fn sweep_chat(
+        &self,
+        files: &FileService,
+        chat: &ExpiringChat,
+    ) -> Result<usize, AppError> {
+        let mut deleted = 0;
+        for _ in 0..MAX_BATCHES_PER_CHAT {
+            let msgs = self
+                .repo
+                .delete_expired_messages(chat.chat_id, chat.before, DELETE_BATCH)
+                .await?;
+            deleted += msgs.len();
+            // files are found through the deleted messages, so they go after them
+            files.erase_msg_files(chat.ws_id as _, &msgs).await?;
+            if (msgs.len() as i64) < DELETE_BATCH {
+                break;
+            }
+        }
+        Ok(deleted)
+    }
+
+    async fn record(
+        &self,
+        actor: &ClaimUser,
+        target: String,
+        old: &RetentionPolicy,
+        new: &RetentionPolicy,
+    ) -> Result<(), AppError> {
+        let event = AuditEvent::new(
+            actor.ws_id as _,
+            actor.id as _,
+            AuditAction::RetentionUpdate,
+            target,
+            json!({ "from": old, "to": new }),
+        )
+        .with_client(&actor.client);
+        self.audit.append(&event).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{
+        domain::chat::{ChatType, ContentType},
+        service::chat::{CreateChatDto, SendMsgDto},
+        test_util::{signup, state},
+        AppState,
+    };
+
+    fn retention(
+        retention: Option<RetentionMode>,
+        retention_days: Option<i32>,
+        legal_hold: Option<bool>,
+    ) -> UpdateRetentionDto {
+        UpdateRetentionDto {
+            retention,
+            retention_days,
+            legal_hold,
+        }
+    }
+
+    /// a channel with an "old" and a "new" message, both sent now
+    async fn chat_with_history(state: &AppState, user: &ClaimUser, name: &str) -> u64 {
+        let input = CreateChatDto {
+            name: Some(name.to_owned()),
+            chat_type: ChatType::PublicChannel,
+            members: vec![user.id],
+        };
+        let chat_id = state.chat.create(user, input).await.unwrap().id as u64;
+        for content in ["old", "new"] {
+            let input = SendMsgDto {
+                content: content.to_owned(),
+                content_type: ContentType::Plain,
+                client_id: None,
+                quote_id: None,
+                files: vec![],
+            };
+            state.chat.send_msg(user, chat_id, input).await.unwrap();
+        }
+        chat_id
+    }
+
+    async fn contents(pool: &PgPool, chat_id: u64) -> Vec<String> {
+        sqlx::query_scalar("SELECT content FROM messages WHERE chat_id = $1 ORDER BY id")
+            .bind(chat_id as i64)
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn sweep_should_delete_expired_messages_of_chats_not_on_hold(pool: PgPool) {
+        let state = state(pool.clone()).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let general = chat_with_history(&state, &alice, "general").await;
+        let forever = chat_with_history(&state, &alice, "forever").await;
+        let held = chat_with_history(&state, &alice, "held").await;
+        sqlx::query(
+            "UPDATE messages SET created_at = now() - interval '40 days' WHERE content = 'old'",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let input = retention(Some(RetentionMode::Inherit), None, None);
+        let ret = state.retention.update_ws_policy(&alice, input).await;
+        assert!(matches!(ret, Err(AppError::InvalidError(_))));
+        let input = retention(Some(RetentionMode::Days), Some(30), None);
+        state
+            .retention
+            .update_ws_policy(&alice, input)
+            .await
+            .unwrap();
+        let input = retention(Some(RetentionMode::Forever), None, None);
+        state
+            .retention
+            .update_chat_policy(&alice, forever, input)
+            .await
+            .unwrap();
+        let input = retention(None, None, Some(true));
+        let policy = state
+            .retention
+            .update_chat_policy(&alice, held, input)
+            .await
+            .unwrap();
+        assert_eq!(policy.retention, RetentionMode::Inherit);
+
+        assert_eq!(state.retention.sweep(&state.file).await.unwrap(), 1);
+        assert_eq!(contents(&pool, general).await, vec!["new"]);
+        assert_eq!(contents(&pool, forever).await, vec!["old", "new"]);
+        assert_eq!(contents(&pool, held).await, vec!["old", "new"]);
+
+        // a hold of the workspace keeps the messages of every chat
+        let input = retention(None, None, Some(false));
+        state
+            .retention
+            .update_chat_policy(&alice, held, input)
+            .await
+            .unwrap();
+        let input = retention(None, None, Some(true));
+        state
+            .retention
+            .update_ws_policy(&alice, input)
+            .await
+            .unwrap();
+        assert_eq!(state.retention.sweep(&state.file).await.unwrap(), 0);
+        assert_eq!(contents(&pool, held).await, vec!["old", "new"]);
+    }
+}
@@ -0,0 +1,284 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    domain::{
+        chat::ContentType,
+        schedule::{ScheduleKind, ScheduleRepo, ScheduleStatus, ScheduledItem},
+    },
+    error::AppError,
+};
+
+use super::{
+    auth::ClaimUser,
+    chat::{ChatService, SendMsgDto},
+};
+
+const DELIVER_BATCH: i64 = 50;
+/// long enough to send a batch
+const LEASE_SECS: i64 = 60;
+const MAX_ATTEMPTS: i32 = 5;
+
+pub struct ScheduleService {
+    repo: Box<dyn ScheduleRepo + Send + Sync>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScheduleMsgDto {
+    pub content: String,
+    #[serde(default)]
+    pub content_type: ContentType,
+    pub run_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RemindDto {
+    /// a reminder without a message only has its note
+    #[serde(default)]
+    pub msg_id: Option<u64>,
+    pub run_at: DateTime<Utc>,
+    #[serde(default)]
+    pub note: String,
+}
+
+/// omitted fields keep their value, `content` is the note of a reminder
+#[derive(Debug, Deserialize)]
+pub struct UpdateScheduledDto {
+    pub content: Option<String>,
+    pub content_type: Option<ContentType>,
+    pub run_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListScheduledDto {
+    #[serde(default)]
+    pub status: ScheduleStatus,
+}
+
+impl ScheduleService {
+    pub fn new(repo: Box<dyn ScheduleRepo + Send + Sync>) -> Self {
+        Self { repo }
+    }
+
+    pub async fn schedule_msg(
+        &self,
+        user: &ClaimUser,
+        chat_id: u64,
+        input: ScheduleMsgDto,
+    ) -> Result<ScheduledItem, AppError> {
+        let mut item = ScheduledItem::new(
+            ScheduleKind::Message,
+            user.id as _,
+            user.ws_id as _,
+            chat_id as _,
+            input.content,
+            input.run_at,
+        );
+        item.content_type = input.content_type;
+        item.validate()?;
+        self.repo.save(&item).await
+    }
+
+    pub async fn remind(
+        &self,
+        chat: &ChatService,
+        user: &ClaimUser,
+        chat_id: u64,
+        input: RemindDto,
+    ) -> Result<ScheduledItem, AppError> {
+        let msg_id = match input.msg_id {
+            Some(msg_id) => Some(chat.get_msg(chat_id, msg_id).await?.id),
+            None => None,
+        };
+        let mut item = ScheduledItem::new(
+            ScheduleKind::Reminder,
+            user.id as _,
+            user.ws_id as _,
+            chat_id as _,
+            input.note,
+            input.run_at,
+        );
+        item.msg_id = msg_id;
+        item.validate()?;
+        self.repo.save(&item).await
+    }
+
+    pub async fn list(
+        &self,
+        user: &ClaimUser,
+        input: ListScheduledDto,
+    ) -> Result<Vec<ScheduledItem>, AppError> {
+        self.repo
+            .extract_by_user(user.id as _, user.ws_id as _, input.status)
+            .await
+    }
+
+    pub async fn update(
+        &self,
+        user: &ClaimUser,
+        id: u64,
+        input: UpdateScheduledDto,
+    ) -> Result<ScheduledItem, AppError> {
+        let mut item = self.find_pending(user, id).await?;
+        if let Some(content) = input.content {
+            item.content = content;
+        }
+        if let Some(content_type) = input.content_type {
+            item.content_type = content_type;
+        }
+        if let Some(run_at) = input.run_at {
+            item.run_at = run_at;
+        }
+        item.validate()?;
+        self.repo
+            .update_pending(&item)
+            .await?
+            .ok_or_else(|| AppError::ConflictError(format!("scheduled item {} is being sent", id)))
+    }
+
+    pub async fn cancel(&self, user: &ClaimUser, id: u64) -> Result<ScheduledItem, AppError> {
+        self.find_pending(user, id).await?;
+        self.repo
+            .cancel(id as _)
+            .await?
+            .ok_or_else(|| AppError::ConflictError(format!("scheduled item {} is being sent", id)))
+    }
+
+    async fn find_pending(&self, user: &ClaimUser, id: u64) -> Result<ScheduledItem, AppError> {
+        let item = self
+            .repo
+            .extract_by_id(id as _)
+            .await?
+            .filter(|item| item.user_id == user.id as i64 && item.ws_id == user.ws_id as i64)
+            .ok_or_else(|| AppError::NotFound(format!("scheduled item {}", id)))?;
+        if item.status != ScheduleStatus::Pending {
+            return Err(AppError::InvalidError(format!(
+                "scheduled item {} is not pending",
+                id
+            )));
+        }
+        Ok(item)
+    }
+
+    /// send due messages and fire due reminders, every replica may run it,
+    /// an item is claimed by one of them and sent once
+    pub async fn deliver_due(&self, chat: &ChatService) -> Result<usize, AppError> {
+        let items = self.repo.claim_due(DELIVER_BATCH, LEASE_SECS).await?;
+        let mut delivered = 0;
+        for item in items {
+            match self.deliver(chat, &item).await {
+                Ok(sent_msg_id) => {
+                    self.repo
+                        .finish(item.id, ScheduleStatus::Done, sent_msg_id, None)
+                        .await?;
+                    delivered += 1;
+                }
+                Err(
+                    e @ (AppError::NotFound(_)
+                    | AppError::InvalidError(_)
+                    | AppError::PermissionDenyError(_)),
+                ) => {
+                    let error = e.to_string();
+                    self.repo
+                        .finish(item.id, ScheduleStatus::Failed, None, Some(&error))
+                        .await?;
+                }
+                Err(e) if item.attempts >= MAX_ATTEMPTS => {
+                    let error = e.to_string();
+                    self.repo
+                        .finish(item.id, ScheduleStatus::Failed, None, Some(&error))
+                        .await?;
+                }
+                // tried again when the lease is over
+                Err(e) => warn!("Failed to deliver scheduled item {}, error: {}", item.id, e),
+            }
+        }
+        Ok(delivered)
+    }
+
+    async fn deliver(
+        &self,
+        chat: &ChatService,
+        item: &ScheduledItem,
+    ) -> Result<Option<i64>, AppError> {
+        let user = ClaimUser {
+            id: item.user_id as _,
+            ws_id: item.ws_id as _,
+            ver: 0,
+            client: Default::default(),
+        };
+        if !chat.can_access(&user, item.chat_id as _).await? {
+            return Err(AppError::PermissionDenyError(format!(
+                "user {} is not a member of chat {}",
+                item.user_id, item.chat_id
+            )));
+        }
+        match item.kind {
+            ScheduleKind::Message => {
+                // the client id makes a retry after a crash return the message already sent
+                let input = SendMsgDto {
+                    content: item.content.clone(),
+                    content_type: item.content_type,
+                    client_id: Some(format!("scheduled:{}", item.id)),
+                    quote_id: None,
+                    files: vec![],
+                };
+                // scheduled text is posted as it is, even when it looks like a command
+                let msg = chat.send_msg(&user, item.chat_id as _, input).await?;
+                Ok(Some(msg.id))
+            }
+            // the user is alerted when the item is done
+            ScheduleKind::Reminder => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{
+        domain::chat::ChatType,
+        service::chat::CreateChatDto,
+        test_util::{signup, state},
+    };
+
+    #[sqlx::test]
+    async fn deliver_due_should_post_a_scheduled_command_as_text(pool: PgPool) {
+        let state = state(pool.clone()).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let input = CreateChatDto {
+            name: Some("general".to_owned()),
+            chat_type: ChatType::PublicChannel,
+            members: vec![alice.id],
+        };
+        let chat = state.chat.create(&alice, input).await.unwrap();
+        let input = ScheduleMsgDto {
+            content: "/help".to_owned(),
+            content_type: ContentType::Plain,
+            run_at: Utc::now() + Duration::minutes(1),
+        };
+        let item = state
+            .schedule
+            .schedule_msg(&alice, chat.id as _, input)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE scheduled_items SET run_at = now() WHERE id = $1")
+            .bind(item.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let delivered = state.schedule.deliver_due(&state.chat).await.unwrap();
+        assert_eq!(delivered, 1);
+        let content: String = sqlx::query_scalar("SELECT content FROM messages WHERE chat_id = $1")
+            .bind(chat.id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(content, "/help");
+    }
+}
@@ -0,0 +1,302 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use axum::async_trait;
+use futures::future::join_all;
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    header::CONTENT_TYPE,
+    redirect::Policy,
+    Client, Url,
+};
+use tracing::{info, warn};
+
+use crate::{
+    domain::link::{LinkPreview, LinkRepo, UnfurlJob},
+    error::AppError,
+};
+
+const CLAIM_BATCH: i64 = 5;
+/// long enough for every link of a job to time out
+const LEASE_SECS: i64 = 60;
+const MAX_ATTEMPTS: i32 = 3;
+/// cached previews older than this are fetched again
+const PREVIEW_TTL: chrono::Duration = chrono::Duration::hours(24);
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REDIRECTS: usize = 3;
+/// the head of a page is all a preview needs
+const MAX_BODY: usize = 256 * 1024;
+
+#[async_trait]
+pub trait LinkFetcher {
+    /// html of the page at `url`
+    async fn fetch(&self, url: &str) -> Result<String, AppError>;
+}
+
+/// fetches public http(s) pages, hosts on loopback and private networks are refused
+pub struct HttpLinkFetcher {
+    client: Client,
+}
+
+impl HttpLinkFetcher {
+    pub fn try_new() -> Result<Self, AppError> {
+        let client = Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            .dns_resolver(Arc::new(PublicResolver))
+            // the hosts of redirect targets are checked again when they are resolved
+            .redirect(Policy::custom(|attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS || !is_public_url(attempt.url()) {
+                    attempt.stop()
+                } else {
+                    attempt.follow()
+                }
+            }))
+            .user_agent(concat!("chat-unfurl/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Self { client })
+    }
+}
+
+#[async_trait]
+impl LinkFetcher for HttpLinkFetcher {
+    async fn fetch(&self, url: &str) -> Result<String, AppError> {
+        let url = check_public_url(url).await?;
+        let mut response = self.client.get(url).send().await?.error_for_status()?;
+        let is_html = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/html") || v.starts_with("application/xhtml"));
+        if !is_html {
+            return Err(AppError::InvalidError("not an html page".to_owned()));
+        }
+        let mut body = vec![];
+        while let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() >= MAX_BODY {
+                break;
+            }
+        }
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+}
+
+/// canned pages for local development, nothing leaves the host
+pub struct StubLinkFetcher;
+
+#[async_trait]
+impl LinkFetcher for StubLinkFetcher {
+    async fn fetch(&self, url: &str) -> Result<String, AppError> {
+        Ok(format!(
+            r#"<html><head><title>{url}</title><meta property="og:description" content="Preview of {url}"></head></html>"#
+        ))
+    }
+}
+
+/// parse the url and resolve its host, refusing loopback and private addresses
+pub(crate) async fn check_public_url(url: &str) -> Result<Url, AppError> {
+    let url = Url::parse(url).map_err(|e| AppError::InvalidError(e.to_string()))?;
+    let (host, port) = match (url.host_str(), url.port_or_known_default()) {
+        (Some(host), Some(port)) if is_public_url(&url) => (host.to_owned(), port),
+        _ => return Err(AppError::InvalidError(format!("url {} is not public", url))),
+    };
+    let mut addrs = tokio::net::lookup_host((host.trim_matches(['[', ']']), port)).await?;
+    if addrs.any(|addr| !is_public_ip(addr.ip())) {
+        return Err(AppError::InvalidError(format!("url {} is not public", url)));
+    }
+    Ok(url)
+}
+
+/// resolves the hosts requests connect to, refusing loopback and private addresses; a host
+/// checked when it was saved may since resolve to a private address, so the addresses
+/// connected to are checked too
+pub(crate) struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            // the port is replaced by the one of the url
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            if addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+                return Err(format!("host {} is not public", name.as_str()).into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+fn is_public_url(url: &Url) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    let host = match url.host_str() {
+        Some(host) => host.trim_matches(['[', ']']).to_ascii_lowercase(),
+        None => return false,
+    };
+    match host.parse::<IpAddr>() {
+        Ok(ip) => is_public_ip(ip),
+        Err(_) => {
+            !(host == "localhost" || host.ends_with(".localhost") || host.ends_with(".local"))
+        }
+    }
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => !is_private_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => !is_private_v4(ip),
+            None => !is_private_v6(ip),
+        },
+    }
+}
+
+fn is_private_v4(ip: Ipv4Addr) -> bool {
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        // carrier grade nat, 100.64.0.0/10
+        || (ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64)
+}
+
+fn is_private_v6(ip: Ipv6Addr) -> bool {
+    ip.is_loopback()
+        || ip.is_unspecified()
+        // unique local, fc00::/7
+        || ip.segments()[0] & 0xfe00 == 0xfc00
+        // link local, fe80::/10
+        || ip.segments()[0] & 0xffc0 == 0xfe80
+}
+
+pub struct UnfurlService {
+    repo: Box<dyn LinkRepo + Send + Sync>,
+    fetcher: Box<dyn LinkFetcher + Send + Sync>,
+}
+
+impl UnfurlService {
+    pub fn new(
+        repo: Box<dyn LinkRepo + Send + Sync>,
+        fetcher: Box<dyn LinkFetcher + Send + Sync>,
+    ) -> Self {
+        Self { repo, fetcher }
+    }
+
+    /// unfurl a batch of pending messages, the count of claimed jobs is returned
+    pub async fn unfurl_pending(&self) -> Result<usize, AppError> {
+        let jobs = self.repo.claim_jobs(CLAIM_BATCH, LEASE_SECS).await?;
+        for ret in join_all(jobs.iter().map(|job| self.unfurl_job(job))).await {
+            ret?;
+        }
+        Ok(jobs.len())
+    }
+
+    async fn unfurl_job(&self, job: &UnfurlJob) -> Result<(), AppError> {
+        if job.attempts > MAX_ATTEMPTS {
+            warn!("Giving up unfurling message {}", job.msg_id);
+            return self.repo.delete_job(job.msg_id).await;
+        }
+        let cached = self
+            .repo
+            .extract_previews(&job.urls, chrono::Utc::now() - PREVIEW_TTL)
+            .await?;
+        let mut previews = vec![];
+        for url in &job.urls {
+            let preview = match cached.iter().find(|v| &v.url == url) {
+                Some(preview) => preview.clone(),
+                None => {
+                    let preview = match self.fetcher.fetch(url).await {
+                        Ok(html) => LinkPreview::from_html(url, &html),
+                        Err(e) => {
+                            info!("Failed to unfurl {}, error: {}", url, e);
+                            LinkPreview::failed(url)
+                        }
+                    };
+                    self.repo.save_preview(&preview).await?;
+                    preview
+                }
+            };
+            if preview.ok {
+                previews.push(preview);
+            }
+        }
+        self.repo.complete_job(job.msg_id, &previews).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{
+        domain::chat::{markdown::Node, ChatType, ContentType},
+        service::chat::{CreateChatDto, SendMsgDto},
+        test_util::{signup, state},
+    };
+
+    #[tokio::test]
+    async fn public_resolver_should_refuse_a_private_host() {
+        let name = Name::from_str("localhost").unwrap();
+        assert!(PublicResolver.resolve(name).await.is_err());
+    }
+
+    #[sqlx::test]
+    async fn markdown_message_should_be_sanitized_and_unfurled(pool: PgPool) {
+        let state = state(pool).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let input = CreateChatDto {
+            name: Some("general".to_owned()),
+            chat_type: ChatType::PublicChannel,
+            members: vec![alice.id],
+        };
+        let chat_id = state.chat.create(&alice, input).await.unwrap().id as u64;
+        let input = SendMsgDto {
+            content: "[docs](https://example.com/docs) [x](javascript:alert(1)) <b>hi</b>"
+                .to_owned(),
+            content_type: ContentType::Markdown,
+            client_id: None,
+            quote_id: None,
+            files: vec![],
+        };
+        let msg = state.chat.send_msg(&alice, chat_id, input).await.unwrap();
+        let rich = msg.rich.as_ref().unwrap().0.clone();
+        let expected = vec![Node::Paragraph {
+            children: vec![
+                Node::Link {
+                    url: "https://example.com/docs".to_owned(),
+                    children: vec![Node::Text {
+                        text: "docs".to_owned(),
+                    }],
+                },
+                Node::Text {
+                    text: " x <b>hi</b>".to_owned(),
+                },
+            ],
+        }];
+        assert_eq!(rich, expected);
+        assert!(msg.previews.is_empty());
+
+        assert_eq!(state.unfurl.unfurl_pending().await.unwrap(), 1);
+        assert_eq!(state.unfurl.unfurl_pending().await.unwrap(), 0);
+        let msg = state.chat.get_msg(chat_id, msg.id as _).await.unwrap();
+        assert_eq!(msg.previews.len(), 1);
+        let preview = &msg.previews[0];
+        assert_eq!(preview.url, "https://example.com/docs");
+        assert_eq!(preview.title.as_deref(), Some("https://example.com/docs"));
+        assert_eq!(
+            preview.description.as_deref(),
+            Some("Preview of https://example.com/docs")
+        );
+    }
+}
@@ -1,4 +1,6 @@
-use serde::Serialize;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     domain::user::{User, UserRepo},
@@ -9,27 +11,61 @@ pub struct UserService {
     repo: Box<dyn UserRepo + Send + Sync>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatUserDto {
     pub id: u64,
     pub display: String,
+    pub avatar_url: Option<String>,
+    pub title: Option<String>,
+    pub status: Option<UserStatusDto>,
+    pub timezone: Option<String>,
+    pub bot: bool,
 }
 impl From<User> for ChatUserDto {
     fn from(u: User) -> Self {
+        let status = u.has_status().then(|| UserStatusDto {
+            text: u.status_text.clone(),
+            emoji: u.status_emoji.clone(),
+            expires_at: u.status_expires_at,
+        });
         Self {
             id: u.id as _,
-            display: u.fullname,
+            display: u.display().to_owned(),
+            avatar_url: u.avatar_url,
+            title: u.title,
+            status,
+            timezone: u.timezone,
+            bot: u.is_bot,
         }
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserStatusDto {
+    pub text: Option<String>,
+    pub emoji: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct UserInfoDto {
-    pub id: u64,
-    pub display: String,
+    #[serde(flatten)]
+    pub user: ChatUserDto,
+    pub email: String,
     pub workspace: String,
 }
 
+/// fields left out are unchanged, an empty string clears the field
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdateProfileDto {
+    pub display_name: Option<String>,
+    pub title: Option<String>,
+    pub status_text: Option<String>,
+    pub status_emoji: Option<String>,
+    pub status_expires_at: Option<DateTime<Utc>>,
+    pub timezone: Option<String>,
+}
+
 impl UserService {
     pub fn new(repo: Box<dyn UserRepo + Send + Sync>) -> Self {
         Self { repo }
@@ -38,26 +74,191 @@ impl UserService {
     pub async fn fetch_all_in_ws(&self, ws_id: u64) -> Result<Vec<ChatUserDto>, AppError> {
         Ok(self
             .repo
-            .extract_all_users(ws_id)
+            .extract_active_users(ws_id)
             .await?
             .into_iter()
             .map(ChatUserDto::from)
             .collect::<Vec<_>>())
     }
 
-    pub async fn get_user_info(&self, user_id: u64) -> Result<Option<UserInfoDto>, AppError> {
+    pub async fn get_user_info(
+        &self,
+        user_id: u64,
+        ws_id: u64,
+    ) -> Result<Option<UserInfoDto>, AppError> {
         let user = match self.repo.find_by_id(user_id as _).await? {
             Some(user) => user,
             None => return Ok(None),
         };
-        let ws = match self.repo.find_ws_by_id(user.ws_id).await? {
+        let ws = match self.repo.find_ws_by_id(ws_id as _).await? {
             Some(ws) => ws,
             None => return Ok(None),
         };
         Ok(Some(UserInfoDto {
-            id: user.id as _,
-            display: user.fullname,
+            email: user.email.clone(),
+            user: ChatUserDto::from(user),
             workspace: ws.name,
         }))
     }
+
+    pub async fn update_profile(
+        &self,
+        user_id: u64,
+        input: UpdateProfileDto,
+    ) -> Result<ChatUserDto, AppError> {
+        let mut user = self.find_user(user_id).await?;
+        if let Some(tz) = &input.timezone {
+            if !tz.is_empty() && tz.parse::<Tz>().is_err() {
+                return Err(AppError::InvalidError(format!("unknown timezone {}", tz)));
+            }
+        }
+        if let Some(expires_at) = input.status_expires_at {
+            if expires_at <= Utc::now() {
+                return Err(AppError::InvalidError(
+                    "status expiry should be in the future".to_owned(),
+                ));
+            }
+        }
+        apply(
+            &mut user.display_name,
+            input.display_name,
+            64,
+            "display_name",
+        )?;
+        apply(&mut user.title, input.title, 64, "title")?;
+        let status = (user.status_text.clone(), user.status_emoji.clone());
+        apply(&mut user.status_text, input.status_text, 100, "status_text")?;
+        apply(
+            &mut user.status_emoji,
+            input.status_emoji,
+            32,
+            "status_emoji",
+        )?;
+        apply(&mut user.timezone, input.timezone, 64, "timezone")?;
+        let status_changed = status != (user.status_text.clone(), user.status_emoji.clone());
+        if user.status_text.is_none() && user.status_emoji.is_none() {
+            user.status_expires_at = None;
+        } else if input.status_expires_at.is_some() || status_changed {
+            // a new status does not inherit the expiry of the old one
+            user.status_expires_at = input.status_expires_at;
+        }
+        let user = self.repo.update(&user).await?;
+        Ok(ChatUserDto::from(user))
+    }
+
+    pub async fn set_avatar(&self, user_id: u64, url: String) -> Result<ChatUserDto, AppError> {
+        let mut user = self.find_user(user_id).await?;
+        user.avatar_url = Some(url);
+        let user = self.repo.update(&user).await?;
+        Ok(ChatUserDto::from(user))
+    }
+
+    async fn find_user(&self, user_id: u64) -> Result<User, AppError> {
+        self.repo
+            .find_by_id(user_id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(user_id.to_string()))
+    }
+}
+
+/// `None` keeps the field, an empty string clears it
+pub(super) fn apply(
+    field: &mut Option<String>,
+    value: Option<String>,
+    max_len: usize,
+    name: &str,
+) -> Result<(), AppError> {
+    let value = match value {
+        Some(value) => value.trim().to_owned(),
+        None => return Ok(()),
+    };
+    if value.chars().count() > max_len {
+        return Err(AppError::InvalidError(format!(
+            "{} should be at most {} characters",
+            name, max_len
+        )));
+    }
+    *field = (!value.is_empty()).then_some(value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::test_util::{signup, state};
+
+    fn status(text: &str, expires_at: Option<DateTime<Utc>>) -> UpdateProfileDto {
+        UpdateProfileDto {
+            status_text: Some(text.to_owned()),
+            status_expires_at: expires_at,
+            ..Default::default()
+        }
+    }
+
+    #[sqlx::test]
+    async fn update_profile_should_not_keep_the_expiry_of_another_status(pool: PgPool) {
+        let state = state(pool).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let lunch = Utc::now() + Duration::hours(1);
+
+        let user = state
+            .user
+            .update_profile(alice.id, status("lunch", Some(lunch)))
+            .await
+            .unwrap();
+        assert!(user.status.unwrap().expires_at.is_some());
+        // the same status keeps its expiry
+        let user = state
+            .user
+            .update_profile(alice.id, status("lunch", None))
+            .await
+            .unwrap();
+        assert!(user.status.unwrap().expires_at.is_some());
+        let user = state
+            .user
+            .update_profile(alice.id, status("on leave", None))
+            .await
+            .unwrap();
+        assert_eq!(user.status.unwrap().expires_at, None);
+    }
+
+    #[sqlx::test]
+    async fn update_profile_should_check_and_clear_fields(pool: PgPool) {
+        let state = state(pool).await;
+        let alice = signup(&state, "alice", "acme").await;
+
+        let input = UpdateProfileDto {
+            timezone: Some("Mars/Olympus".to_owned()),
+            ..Default::default()
+        };
+        let ret = state.user.update_profile(alice.id, input).await;
+        assert!(matches!(ret, Err(AppError::InvalidError(_))));
+        let input = UpdateProfileDto {
+            status_text: Some("lunch".to_owned()),
+            status_expires_at: Some(Utc::now() - Duration::minutes(1)),
+            ..Default::default()
+        };
+        let ret = state.user.update_profile(alice.id, input).await;
+        assert!(matches!(ret, Err(AppError::InvalidError(_))));
+
+        let input = UpdateProfileDto {
+            display_name: Some(" Al ".to_owned()),
+            timezone: Some("Europe/Paris".to_owned()),
+            ..Default::default()
+        };
+        let user = state.user.update_profile(alice.id, input).await.unwrap();
+        assert_eq!(user.display, "Al");
+        assert_eq!(user.timezone.as_deref(), Some("Europe/Paris"));
+        // an empty string clears the field, the display falls back to the full name
+        let input = UpdateProfileDto {
+            display_name: Some(String::new()),
+            ..Default::default()
+        };
+        let user = state.user.update_profile(alice.id, input).await.unwrap();
+        assert_eq!(user.display, "alice");
+        assert_eq!(user.timezone.as_deref(), Some("Europe/Paris"));
+    }
 }
@@ -0,0 +1,498 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use futures::future::join_all;
+use reqwest::{header::CONTENT_TYPE, redirect::Policy, Client, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::{
+    common::utils::random_hex,
+    domain::{
+        audit::{AuditAction, AuditEvent, AuditRepo},
+        webhook::{
+            sign, Delivery, DeliveryJob, DeliveryStatus, Webhook, WebhookRepo, WEBHOOK_EVENTS,
+        },
+    },
+    error::AppError,
+};
+
+use super::{
+    auth::ClaimUser,
+    notif::AppEvent,
+    unfurl::{check_public_url, PublicResolver},
+};
+
+const CLAIM_BATCH: i64 = 10;
+/// long enough for every delivery of a batch to time out
+const LEASE_SECS: i64 = 60;
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+const SECRET_BYTES: usize = 32;
+const DEFAULT_LOG_SIZE: u8 = 20;
+
+pub const EVENT_HEADER: &str = "x-webhook-event";
+pub const DELIVERY_HEADER: &str = "x-webhook-delivery";
+pub const TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+/// `sha256=<hex hmac of "{timestamp}.{body}">`
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+
+pub struct WebhookService {
+    repo: Box<dyn WebhookRepo + Send + Sync>,
+    audit: Box<dyn AuditRepo + Send + Sync>,
+    client: Client,
+    // plain http and private hosts are allowed, to test against a local stub
+    insecure: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookDto {
+    pub url: String,
+    pub events: Vec<String>,
+}
+
+/// omitted fields keep their value
+#[derive(Debug, Deserialize)]
+pub struct UpdateWebhookDto {
+    pub url: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub active: Option<bool>,
+    #[serde(default)]
+    pub rotate_secret: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListDeliveriesDto {
+    pub status: Option<DeliveryStatus>,
+    pub before_id: Option<u64>,
+    pub limit: Option<u8>,
+}
+
+/// the secret is only part of the response when it was just made
+#[derive(Debug, Serialize)]
+pub struct WebhookDto {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+impl From<Webhook> for WebhookDto {
+    fn from(webhook: Webhook) -> Self {
+        Self {
+            webhook,
+            secret: None,
+        }
+    }
+}
+
+impl WebhookService {
+    pub fn try_new(
+        repo: Box<dyn WebhookRepo + Send + Sync>,
+        audit: Box<dyn AuditRepo + Send + Sync>,
+        insecure: bool,
+    ) -> Result<Self, AppError> {
+        let mut builder = Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .redirect(Policy::none())
+            .user_agent(concat!("chat-webhook/", env!("CARGO_PKG_VERSION")));
+        if !insecure {
+            builder = builder.dns_resolver(Arc::new(PublicResolver));
+        }
+        let client = builder.build()?;
+        Ok(Self {
+            repo,
+            audit,
+            client,
+            insecure,
+        })
+    }
+
+    pub async fn create(
+        &self,
+        actor: &ClaimUser,
+        input: CreateWebhookDto,
+    ) -> Result<WebhookDto, AppError> {
+        let secret = random_hex(SECRET_BYTES);
+        let webhook = Webhook::new(
+            actor.ws_id as _,
+            actor.id as _,
+            input.url.trim().to_owned(),
+            secret.clone(),
+            input.events,
+        );
+        self.check_webhook(&webhook).await?;
+        let webhook = self.repo.save(&webhook).await?;
+        self.record(actor, AuditAction::WebhookCreate, &webhook)
+            .await?;
+        Ok(WebhookDto {
+            webhook,
+            secret: Some(secret),
+        })
+    }
+
+    pub async fn list(&self, ws_id: u64) -> Result<Vec<WebhookDto>, AppError> {
+        let webhooks = self.repo.extract_by_ws(ws_id as _).await?;
+        Ok(webhooks.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn update(
+        &self,
+        actor: &ClaimUser,
+        id: u64,
+        input: UpdateWebhookDto,
+    ) -> Result<WebhookDto, AppError> {
+        let mut webhook = self.find(actor.ws_id, id).await?;
+        if let Some(url) = input.url {
+            webhook.url = url.trim().to_owned();
+        }
+        if let Some(events) = input.events {
+            webhook.events = events;
+        }
+        if let Some(active) = input.active {
+            webhook.active = active;
+        }
+        let secret = input.rotate_secret.then(|| random_hex(SECRET_BYTES));
+        if let Some(secret) = &secret {
+            webhook.secret = secret.clone();
+        }
+        self.check_webhook(&webhook).await?;
+        let webhook = self.repo.save(&webhook).await?;
+        self.record(actor, AuditAction::WebhookUpdate, &webhook)
+            .await?;
+        Ok(WebhookDto { webhook, secret })
+    }
+
+    pub async fn delete(&self, actor: &ClaimUser, id: u64) -> Result<WebhookDto, AppError> {
+        let webhook = self
+            .repo
+            .delete(actor.ws_id as _, id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("webhook {}", id)))?;
+        self.record(actor, AuditAction::WebhookDelete, &webhook)
+            .await?;
+        Ok(webhook.into())
+    }
+
+    /// the delivery log of the webhook, newest first
+    pub async fn list_deliveries(
+        &self,
+        ws_id: u64,
+        id: u64,
+        input: ListDeliveriesDto,
+    ) -> Result<Vec<Delivery>, AppError> {
+        let webhook = self.find(ws_id, id).await?;
+        self.repo
+            .extract_deliveries(
+                webhook.id,
+                input.status,
+                input.before_id.map_or(i64::MAX, |id| id as _),
+                input.limit.unwrap_or(DEFAULT_LOG_SIZE) as _,
+            )
+            .await
+    }
+
+    /// send a dead delivery again
+    pub async fn redeliver(
+        &self,
+        ws_id: u64,
+        id: u64,
+        delivery_id: u64,
+    ) -> Result<Delivery, AppError> {
+        let webhook = self.find(ws_id, id).await?;
+        self.repo
+            .redeliver(webhook.id, delivery_id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("dead delivery {}", delivery_id)))
+    }
+
+    /// queue the event for the webhooks subscribed to it, the count of queued deliveries
+    /// is returned, an event already queued under the key is not queued again
+    pub async fn enqueue(&self, key: &str, event: &AppEvent) -> Result<u64, AppError> {
+        let name = event.get_name();
+        let chat_id = match event.chat_id() {
+            Some(chat_id) if WEBHOOK_EVENTS.contains(&name) => chat_id,
+            _ => return Ok(0),
+        };
+        let payload = serde_json::to_value(event)?;
+        self.repo.enqueue(chat_id, name, key, &payload).await
+    }
+
+    /// send a batch of due deliveries, the count of claimed deliveries is returned
+    pub async fn deliver_due(&self) -> Result<usize, AppError> {
+        let jobs = self.repo.claim_deliveries(CLAIM_BATCH, LEASE_SECS).await?;
+        for ret in join_all(jobs.iter().map(|job| self.deliver(job))).await {
+            ret?;
+        }
+        Ok(jobs.len())
+    }
+
+    async fn deliver(&self, job: &DeliveryJob) -> Result<(), AppError> {
+        let (status, error) = match self.send(job).await {
+            Ok(status) if (200..300).contains(&status) => {
+                return self.repo.complete_delivery(job.id, status as _).await;
+            }
+            Ok(status) => (
+                Some(status as i16),
+                format!("endpoint responded {}", status),
+            ),
+            Err(e) => (None, e.to_string()),
+        };
+        let retry = job.retry_delay();
+        match retry {
+            Some(delay) => info!(
+                "Failed to deliver {} to webhook {}, retry in {:?}, error: {}",
+                job.id, job.webhook_id, delay, error
+            ),
+            None => warn!(
+                "Giving up delivering {} to webhook {}, error: {}",
+                job.id, job.webhook_id, error
+            ),
+        }
+        self.repo
+            .fail_delivery(
+                job.id,
+                status,
+                &error,
+                retry.map(|delay| delay.as_secs() as _),
+            )
+            .await
+    }
+
+    async fn send(&self, job: &DeliveryJob) -> Result<u16, AppError> {
+        let url = self.check_url(&job.url).await?;
+        let body = serde_json::to_vec(&job.payload.0)?;
+        let timestamp = Utc::now().timestamp();
+        let signature = sign(&job.secret, timestamp, &body);
+        let response = self
+            .client
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, &job.event)
+            .header(DELIVERY_HEADER, job.id)
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(SIGNATURE_HEADER, format!("sha256={}", signature))
+            .body(body)
+            .send()
+            .await?;
+        Ok(response.status().as_u16())
+    }
+
+    async fn check_webhook(&self, webhook: &Webhook) -> Result<(), AppError> {
+        webhook.validate()?;
+        self.check_url(&webhook.url).await?;
+        Ok(())
+    }
+
+    async fn check_url(&self, url: &str) -> Result<Url, AppError> {
+        check_endpoint_url(url, self.insecure).await
+    }
+
+    async fn find(&self, ws_id: u64, id: u64) -> Result<Webhook, AppError> {
+        self.repo
+            .find_by_id(ws_id as _, id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("webhook {}", id)))
+    }
+
+    async fn record(
+        &self,
+        actor: &ClaimUser,
+        action: AuditAction,
+        webhook: &Webhook,
+    ) -> Result<(), AppError> {
+        let event = AuditEvent::new(
+            actor.ws_id as _,
+            actor.id as _,
+            action,
+            format!("webhook:{}", webhook.id),
+            json!({ "url": webhook.url, "events": webhook.events, "active": webhook.active }),
+        )
+        .with_client(&actor.client);
+        self.audit.append(&event).await?;
+        Ok(())
+    }
+}
+
+/// https on a public host, unless insecure endpoints are allowed
+pub(crate) async fn check_endpoint_url(url: &str, insecure: bool) -> Result<Url, AppError> {
+    if insecure {
+        let url = Url::parse(url).map_err(|e| AppError::InvalidError(e.to_string()))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(AppError::InvalidError(format!("url {} is not http", url)));
+        }
+        return Ok(url);
+    }
+    let url = check_public_url(url).await?;
+    if url.scheme() != "https" {
+        return Err(AppError::InvalidError(format!("url {} is not https", url)));
+    }
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU16, Ordering},
+        Mutex,
+    };
+
+    use axum::{
+        body::Bytes,
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+    use sqlx::PgPool;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::{
+        adapter::driving::db::{audit::AuditRepoImpl, webhook::WebhookRepoImpl},
+        domain::chat::{ChatType, ContentType},
+        service::chat::{CreateChatDto, SendMsgDto},
+        test_util::{signup, state},
+    };
+
+    /// the requests the endpoint got, it responds with `status`
+    #[derive(Default)]
+    struct Endpoint {
+        status: AtomicU16,
+        requests: Mutex<Vec<(HeaderMap, Bytes)>>,
+    }
+
+    async fn receive(
+        State(endpoint): State<Arc<Endpoint>>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> StatusCode {
+        endpoint.requests.lock().unwrap().push((headers, body));
+        StatusCode::from_u16(endpoint.status.load(Ordering::SeqCst)).unwrap()
+    }
+
+    async fn serve(endpoint: Arc<Endpoint>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new()
+            .route("/hook", post(receive))
+            .with_state(endpoint);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{}/hook", addr)
+    }
+
+    fn webhooks(pool: &PgPool, insecure: bool) -> WebhookService {
+        WebhookService::try_new(
+            Box::new(WebhookRepoImpl::new(pool.clone())),
+            Box::new(AuditRepoImpl::new(pool.clone())),
+            insecure,
+        )
+        .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn create_should_refuse_endpoints_which_are_not_public_https(pool: PgPool) {
+        let state = state(pool.clone()).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let service = webhooks(&pool, false);
+        // addresses rather than names, nothing has to be resolved
+        for url in [
+            "http://93.184.216.34/hook",
+            "https://localhost/hook",
+            "https://127.0.0.1/hook",
+            "https://[::1]/hook",
+            "https://10.0.0.1/hook",
+            "ftp://93.184.216.34/hook",
+        ] {
+            let input = CreateWebhookDto {
+                url: url.to_owned(),
+                events: vec!["new_message".to_owned()],
+            };
+            let ret = service.create(&alice, input).await;
+            assert!(matches!(ret, Err(AppError::InvalidError(_))), "{}", url);
+        }
+        let input = CreateWebhookDto {
+            url: "https://example.com/hook".to_owned(),
+            events: vec!["unknown".to_owned()],
+        };
+        let ret = service.create(&alice, input).await;
+        assert!(matches!(ret, Err(AppError::InvalidError(_))));
+    }
+
+    #[sqlx::test]
+    async fn deliver_due_should_sign_the_event_and_retry_a_failure(pool: PgPool) {
+        let state = state(pool.clone()).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let endpoint = Arc::new(Endpoint::default());
+        endpoint.status.store(500, Ordering::SeqCst);
+        let url = serve(endpoint.clone()).await;
+        let service = webhooks(&pool, true);
+        let input = CreateWebhookDto {
+            url,
+            events: vec!["new_message".to_owned()],
+        };
+        let webhook = service.create(&alice, input).await.unwrap();
+        let secret = webhook.secret.unwrap();
+        let input = CreateChatDto {
+            name: Some("general".to_owned()),
+            chat_type: ChatType::PublicChannel,
+            members: vec![alice.id],
+        };
+        let chat = state.chat.create(&alice, input).await.unwrap();
+        let input = SendMsgDto {
+            content: "hi".to_owned(),
+            content_type: ContentType::Plain,
+            client_id: None,
+            quote_id: None,
+            files: vec![],
+        };
+        let msg = state
+            .chat
+            .send_msg(&alice, chat.id as _, input)
+            .await
+            .unwrap();
+        let event = AppEvent::NewMessage(msg);
+        assert_eq!(service.enqueue("msg:1", &event).await.unwrap(), 1);
+
+        assert_eq!(service.deliver_due().await.unwrap(), 1);
+        let list = || ListDeliveriesDto {
+            status: None,
+            before_id: None,
+            limit: None,
+        };
+        let id = webhook.webhook.id as u64;
+        let deliveries = service
+            .list_deliveries(alice.ws_id, id, list())
+            .await
+            .unwrap();
+        assert_eq!(deliveries[0].status, DeliveryStatus::Pending);
+        assert_eq!(
+            (deliveries[0].attempts, deliveries[0].last_status),
+            (1, Some(500))
+        );
+        // not due before its retry
+        assert_eq!(service.deliver_due().await.unwrap(), 0);
+
+        endpoint.status.store(204, Ordering::SeqCst);
+        sqlx::query("UPDATE webhook_deliveries SET next_attempt_at = now()")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(service.deliver_due().await.unwrap(), 1);
+        let deliveries = service
+            .list_deliveries(alice.ws_id, id, list())
+            .await
+            .unwrap();
+        assert_eq!(deliveries[0].status, DeliveryStatus::Delivered);
+
+        let requests = endpoint.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        let (headers, body) = &requests[1];
+        let header = |name| headers[name].to_str().unwrap();
+        assert_eq!(header(EVENT_HEADER), "new_message");
+        assert_eq!(header(DELIVERY_HEADER), deliveries[0].id.to_string());
+        let timestamp: i64 = header(TIMESTAMP_HEADER).parse().unwrap();
+        let signature = format!("sha256={}", sign(&secret, timestamp, body));
+        assert_eq!(header(SIGNATURE_HEADER), signature);
+    }
+}
@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    domain::{
+        audit::{AuditAction, AuditEvent, AuditRepo},
+        user::{User, UserRepo, UserStatus, Workspace, WsMember, WsRole},
+    },
+    error::AppError,
+};
+
+use super::auth::ClaimUser;
+
+pub struct WorkspaceService {
+    repo: Box<dyn UserRepo + Send + Sync>,
+    audit: Box<dyn AuditRepo + Send + Sync>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkspaceDto {
+    pub id: u64,
+    pub name: String,
+    pub owner_id: u64,
+    pub member_count: usize,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WsMemberDto {
+    pub id: u64,
+    pub display: String,
+    pub email: String,
+    pub role: WsRole,
+    pub is_owner: bool,
+    pub status: UserStatus,
+    pub joined_at: DateTime<Utc>,
+}
+
+impl WsMemberDto {
+    fn new(u: User, m: WsMember, ws: &Workspace) -> Self {
+        Self {
+            id: u.id as _,
+            is_owner: u.id == ws.owner_id,
+            display: u.fullname,
+            email: u.email,
+            role: m.role,
+            status: m.status,
+            joined_at: m.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWsDto {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenameWsDto {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransferOwnerDto {
+    pub user_id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddMemberDto {
+    pub email: String,
+    pub role: Option<WsRole>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateMemberDto {
+    pub role: Option<WsRole>,
+    pub status: Option<UserStatus>,
+}
+
+impl WorkspaceService {
+    pub fn new(
+        repo: Box<dyn UserRepo + Send + Sync>,
+        audit: Box<dyn AuditRepo + Send + Sync>,
+    ) -> Self {
+        Self { repo, audit }
+    }
+
+    pub async fn is_admin(&self, user: &ClaimUser) -> Result<bool, AppError> {
+        let (ws, member) = match (
+            self.repo.find_ws_by_id(user.ws_id as _).await?,
+            self.repo.find_member(user.ws_id as _, user.id as _).await?,
+        ) {
+            (Some(ws), Some(member)) => (ws, member),
+            _ => return Ok(false),
+        };
+        Ok(ws.is_admin(&member))
+    }
+
+    pub async fn list_user_ws(&self, user_id: u64) -> Result<Vec<Workspace>, AppError> {
+        self.repo.extract_user_ws(user_id as _).await
+    }
+
+    pub async fn create(
+        &self,
+        user: &ClaimUser,
+        input: CreateWsDto,
+    ) -> Result<Workspace, AppError> {
+        let name = Self::validate_name(&input.name)?;
+        if self.repo.find_ws_by_name(name).await?.is_some() {
+            return Err(AppError::ConflictError(format!("workspace {} exist", name)));
+        }
+        let ws = Workspace {
+            name: name.to_owned(),
+            owner_id: user.id as _,
+            ..Default::default()
+        };
+        let ws = self.repo.save_ws(&ws).await?;
+        self.repo
+            .save_member(&WsMember::new(ws.id, user.id as _, WsRole::Admin))
+            .await?;
+        Ok(ws)
+    }
+
+    pub async fn get(&self, ws_id: u64) -> Result<WorkspaceDto, AppError> {
+        let ws = self.find_ws(ws_id).await?;
+        let members = self.repo.extract_members(ws_id as _).await?;
+        Ok(WorkspaceDto {
+            id: ws.id as _,
+            name: ws.name,
+            owner_id: ws.owner_id as _,
+            member_count: members.len(),
+            created_at: ws.created_at,
+        })
+    }
+
+    pub async fn rename(
+        &self,
+        actor: &ClaimUser,
+        input: RenameWsDto,
+    ) -> Result<Workspace, AppError> {
+        let name = Self::validate_name(&input.name)?;
+        if self.repo.find_ws_by_name(name).await?.is_some() {
+            return Err(AppError::ConflictError(format!("workspace {} exist", name)));
+        }
+        let mut ws = self.find_ws(actor.ws_id).await?;
+        let old_name = std::mem::replace(&mut ws.name, name.to_owned());
+        let ws = self.repo.update_ws(&ws).await?;
+        self.record(
+            actor,
+            AuditAction::WorkspaceRename,
+            format!("workspace:{}", ws.id),
+            json!({ "from": old_name, "to": ws.name }),
+        )
+        .await?;
+        Ok(ws)
+    }
+
+    pub async fn transfer_owner(
+        &self,
+        actor: &ClaimUser,
+        input: TransferOwnerDto,
+    ) -> Result<Workspace, AppError> {
+        let mut ws = self.find_ws(actor.ws_id).await?;
+        if ws.owner_id != actor.id as i64 {
+            return Err(AppError::PermissionDenyError(
+                "only the owner can transfer the workspace".to_owned(),
+            ));
+        }
+        let mut new_owner = self.find_member(actor.ws_id, input.user_id).await?;
+        if !new_owner.is_active() {
+            return Err(AppError::InvalidError(
+                "new owner is deactivated".to_owned(),
+            ));
+        }
+        if new_owner.role != WsRole::Admin {
+            new_owner.role = WsRole::Admin;
+            self.repo.save_member(&new_owner).await?;
+        }
+        ws.owner_id = new_owner.user_id;
+        let ws = self.repo.update_ws(&ws).await?;
+        self.record(
+            actor,
+            AuditAction::WorkspaceTransferOwner,
+            format!("user:{}", new_owner.user_id),
+            json!({ "from": actor.id, "to": new_owner.user_id }),
+        )
+        .await?;
+        Ok(ws)
+    }
+
+    pub async fn list_members(&self, ws_id: u64) -> Result<Vec<WsMemberDto>, AppError> {
+        let ws = self.find_ws(ws_id).await?;
+        let mut users: HashMap<i64, User> = self
+            .repo
+            .extract_all_users(ws_id)
+            .await?
+            .into_iter()
+            .map(|u| (u.id, u))
+            .collect();
+        Ok(self
+            .repo
+            .extract_members(ws_id as _)
+            .await?
+            .into_iter()
+            .filter_map(|m| {
+                users
+                    .remove(&m.user_id)
+                    .map(|u| WsMemberDto::new(u, m, &ws))
+            })
+            .collect())
+    }
+
+    pub async fn add_member(
+        &self,
+        actor: &ClaimUser,
+        input: AddMemberDto,
+    ) -> Result<WsMemberDto, AppError> {
+        let ws = self.find_ws(actor.ws_id).await?;
+        let user = match self.repo.find_by_email(&input.email).await? {
+            Some(user) => user,
+            None => return Err(AppError::NotFound(input.email)),
+        };
+        if self.repo.find_member(ws.id, user.id).await?.is_some() {
+            return Err(AppError::ConflictError(format!(
+                "{} already in workspace",
+                user.email
+            )));
+        }
+        let member = WsMember::new(ws.id, user.id, input.role.unwrap_or_default());
+        let member = self.repo.save_member(&member).await?;
+        self.record(
+            actor,
+            AuditAction::MemberAdd,
+            format!("user:{}", user.id),
+            json!({ "role": member.role }),
+        )
+        .await?;
+        Ok(WsMemberDto::new(user, member, &ws))
+    }
+
+    pub async fn update_member(
+        &self,
+        actor: &ClaimUser,
+        user_id: u64,
+        input: UpdateMemberDto,
+    ) -> Result<WsMemberDto, AppError> {
+        let ws = self.find_ws(actor.ws_id).await?;
+        let mut member = self.find_member(actor.ws_id, user_id).await?;
+        if member.user_id == ws.owner_id {
+            return Err(AppError::PermissionDenyError(
+                "the owner can not be changed".to_owned(),
+            ));
+        }
+        if let Some(role) = input.role {
+            member.role = role;
+        }
+        if let Some(status) = input.status {
+            member.status = status;
+        }
+        let member = self.repo.save_member(&member).await?;
+        self.record(
+            actor,
+            AuditAction::MemberUpdate,
+            format!("user:{}", member.user_id),
+            json!({ "role": member.role, "status": member.status }),
+        )
+        .await?;
+        let user = self.find_user(member.user_id).await?;
+        Ok(WsMemberDto::new(user, member, &ws))
+    }
+
+    pub async fn remove_member(&self, actor: &ClaimUser, user_id: u64) -> Result<(), AppError> {
+        let ws = self.find_ws(actor.ws_id).await?;
+        let member = self.find_member(actor.ws_id, user_id).await?;
+        if member.user_id == ws.owner_id {
+            return Err(AppError::PermissionDenyError(
+                "the owner can not be removed".to_owned(),
+            ));
+        }
+        self.repo.delete_member(ws.id, member.user_id).await?;
+        self.record(
+            actor,
+            AuditAction::MemberRemove,
+            format!("user:{}", member.user_id),
+            json!({}),
+        )
+        .await?;
+        Ok(())
+    }
+
+    fn validate_name(name: &str) -> Result<&str, AppError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(AppError::InvalidError("workspace name is empty".to_owned()));
+        }
+        Ok(name)
+    }
+
+    async fn find_ws(&self, ws_id: u64) -> Result<Workspace, AppError> {
+        self.repo
+            .find_ws_by_id(ws_id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("workspace {}", ws_id)))
+    }
+
+    async fn find_user(&self, user_id: i64) -> Result<User, AppError> {
+        self.repo
+            .find_by_id(user_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("user {}", user_id)))
+    }
+
+    async fn find_member(&self, ws_id: u64, user_id: u64) -> Result<WsMember, AppError> {
+        self.repo
+            .find_member(ws_id as _, user_id as _)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("user {}", user_id)))
+    }
+
+    async fn record(
+        &self,
+        actor: &ClaimUser,
+        action: AuditAction,
+        target: String,
+        detail: serde_json::Value,
+    ) -> Result<(), AppError> {
+        let event = AuditEvent::new(actor.ws_id as _, actor.id as _, action, target, detail)
+            .with_client(&actor.client);
+        self.audit.append(&event).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::test_util::{signup, state};
+
+    #[sqlx::test]
+    async fn owner_should_stay_until_the_workspace_is_transferred(pool: PgPool) {
+        let state = state(pool).await;
+        let alice = signup(&state, "alice", "acme").await;
+        let bob = signup(&state, "bob", "acme").await;
+        assert!(state.workspace.is_admin(&alice).await.unwrap());
+        assert!(!state.workspace.is_admin(&bob).await.unwrap());
+
+        let ret = state.workspace.remove_member(&alice, alice.id).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenyError(_))));
+        let input = UpdateMemberDto {
+            role: Some(WsRole::Member),
+            status: None,
+        };
+        let ret = state.workspace.update_member(&alice, alice.id, input).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenyError(_))));
+        let input = TransferOwnerDto { user_id: alice.id };
+        let ret = state.workspace.transfer_owner(&bob, input).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenyError(_))));
+
+        let input = TransferOwnerDto { user_id: bob.id };
+        let ws = state.workspace.transfer_owner(&alice, input).await.unwrap();
+        assert_eq!(ws.owner_id, bob.id as i64);
+        // the new owner is an admin, the old one stays one until demoted
+        assert!(state.workspace.is_admin(&bob).await.unwrap());
+        state.workspace.remove_member(&bob, alice.id).await.unwrap();
+        let members = state.workspace.list_members(bob.ws_id).await.unwrap();
+        let ids: Vec<u64> = members.iter().map(|v| v.id).collect();
+        assert_eq!(ids, vec![bob.id]);
+    }
+}
@@ -1,14 +1,36 @@
 use anyhow::{bail, Result};
-use sqlx::postgres::PgPoolOptions;
+use sqlx::{postgres::PgPoolOptions, PgPool};
 use std::{env, fmt, fs::File, io::Read, ops::Deref, sync::Arc, time::Duration};
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    adapter::driving::db::{chat::ChatRepoImpl, user::UserRepoImpl},
+    adapter::driving::db::{
+        alert::AlertRepoImpl, audit::AuditRepoImpl, bot::BotRepoImpl, chat::ChatRepoImpl,
+        command::CommandRepoImpl, file::FileRepoImpl, link::LinkRepoImpl,
+        moderation::ModerationRepoImpl, poll::PollRepoImpl, rate_limit::RateLimitRepoImpl,
+        retention::RetentionRepoImpl, schedule::ScheduleRepoImpl, user::UserRepoImpl,
+        webhook::WebhookRepoImpl,
+    },
     common::utils::token::TokenSignVerify,
     service::{
-        chat::ChatService, file::FileService, notif::NotifService, user::UserService, AuthService,
+        account::AccountService,
+        audit::AuditService,
+        bot::BotService,
+        chat::ChatService,
+        command::CommandService,
+        file::FileService,
+        moderation::ModerationService,
+        notif::{LogAlerter, NotifService},
+        poll::PollService,
+        rate_limit::{RateLimitConfig, RateLimitService},
+        retention::RetentionService,
+        schedule::ScheduleService,
+        unfurl::{HttpLinkFetcher, LinkFetcher, StubLinkFetcher, UnfurlService},
+        user::UserService,
+        webhook::WebhookService,
+        workspace::WorkspaceService,
+        AuthService,
     },
 };
 
@@ -30,6 +52,25 @@ pub struct ServerConfig {
     pub port: u16,
     pub db_url: String,
     pub upload_base_dir: String,
+    /// deleted chats can be restored for this many days before they are purged
+    #[serde(default = "default_purge_after_days")]
+    pub purge_after_days: u32,
+    /// link previews are made from canned pages instead of fetching them
+    #[serde(default)]
+    pub stub_link_fetcher: bool,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    /// client addresses are taken from `X-Forwarded-For`, only set it behind a proxy
+    #[serde(default)]
+    pub behind_proxy: bool,
+    /// webhooks and slash commands may target plain http and private hosts, only set it to
+    /// test against a local stub
+    #[serde(default)]
+    pub insecure_webhooks: bool,
+}
+
+fn default_purge_after_days() -> u32 {
+    30
 }
 
 impl ServerConfig {
@@ -79,19 +120,93 @@ impl AppState {
             .acquire_timeout(Duration::from_millis(1000))
             .connect(&config.server.db_url)
             .await?;
+        Self::try_new_with_pool(config, pool).await
+    }
+    /// the pool must be connected to `db_url`, which notifications are listened to on
+    pub(crate) async fn try_new_with_pool(config: AppConfig, pool: PgPool) -> Result<Self> {
         let tokensv = TokenSignVerify::try_new(&config.auth.pk, &config.auth.sk)?;
         let user_repo = Box::new(UserRepoImpl::new(pool.clone()));
         let chat_repo = Box::new(ChatRepoImpl::new(pool.clone()));
-        let file_svc = FileService::new(&config.server.upload_base_dir);
-        let notif_svc = NotifService::try_new(&config.server.db_url).await?;
+        let audit_repo = Box::new(AuditRepoImpl::new(pool.clone()));
+        let file_repo = Box::new(FileRepoImpl::new(pool.clone()));
+        let file_svc = FileService::new(
+            &config.server.upload_base_dir,
+            file_repo,
+            audit_repo.clone(),
+        );
+        let rate_limit_svc = Arc::new(RateLimitService::try_new(
+            &config.server.rate_limit,
+            Box::new(RateLimitRepoImpl::new(pool.clone())),
+        )?);
+        let workspace_svc = Arc::new(WorkspaceService::new(user_repo.clone(), audit_repo.clone()));
+        let schedule_svc = Arc::new(ScheduleService::new(Box::new(ScheduleRepoImpl::new(
+            pool.clone(),
+        ))));
+        let moderation_svc = Arc::new(ModerationService::new(
+            Box::new(ModerationRepoImpl::new(pool.clone())),
+            audit_repo.clone(),
+        ));
+        let command_svc = Arc::new(CommandService::try_new(
+            Box::new(CommandRepoImpl::new(pool.clone())),
+            audit_repo.clone(),
+            schedule_svc.clone(),
+            moderation_svc.clone(),
+            workspace_svc.clone(),
+            config.server.insecure_webhooks,
+        )?);
+        let chat_svc = ChatService::new(
+            chat_repo.clone(),
+            audit_repo.clone(),
+            rate_limit_svc.clone(),
+            command_svc.clone(),
+            workspace_svc.clone(),
+            config.server.purge_after_days,
+        );
+        let fetcher: Box<dyn LinkFetcher + Send + Sync> = if config.server.stub_link_fetcher {
+            Box::new(StubLinkFetcher)
+        } else {
+            Box::new(HttpLinkFetcher::try_new()?)
+        };
+        let unfurl_svc = UnfurlService::new(Box::new(LinkRepoImpl::new(pool.clone())), fetcher);
+        let webhook_svc = WebhookService::try_new(
+            Box::new(WebhookRepoImpl::new(pool.clone())),
+            audit_repo.clone(),
+            config.server.insecure_webhooks,
+        )?;
+        let notif_svc = NotifService::try_new(
+            &config.server.db_url,
+            Arc::new(LogAlerter),
+            Arc::new(AlertRepoImpl::new(pool.clone())),
+        )
+        .await?;
         Ok(Self {
             inner: Arc::new(AppStateInner {
                 config,
-                auth: AuthService::new(user_repo.clone(), tokensv),
-                user: UserService::new(user_repo),
-                chat: ChatService::new(chat_repo),
+                auth: AuthService::new(
+                    user_repo.clone(),
+                    tokensv,
+                    audit_repo.clone(),
+                    Box::new(BotRepoImpl::new(pool.clone())),
+                ),
+                user: UserService::new(user_repo.clone()),
+                workspace: workspace_svc,
+                account: AccountService::new(user_repo, chat_repo),
+                chat: chat_svc,
                 file: file_svc,
                 notif: notif_svc,
+                unfurl: unfurl_svc,
+                schedule: schedule_svc,
+                poll: PollService::new(Box::new(PollRepoImpl::new(pool.clone()))),
+                retention: RetentionService::new(
+                    Box::new(RetentionRepoImpl::new(pool.clone())),
+                    audit_repo.clone(),
+                ),
+                rate_limit: rate_limit_svc,
+                moderation: moderation_svc,
+                bot: BotService::new(Box::new(BotRepoImpl::new(pool.clone())), audit_repo.clone()),
+                command: command_svc,
+                audit: AuditService::new(audit_repo),
+                webhook: webhook_svc,
             }),
         })
     }
@@ -101,9 +216,21 @@ pub struct AppStateInner {
     pub config: AppConfig,
     pub auth: AuthService,
     pub user: UserService,
+    pub account: AccountService,
+    pub workspace: Arc<WorkspaceService>,
     pub chat: ChatService,
     pub file: FileService,
     pub notif: NotifService,
+    pub unfurl: UnfurlService,
+    pub schedule: Arc<ScheduleService>,
+    pub poll: PollService,
+    pub retention: RetentionService,
+    pub moderation: Arc<ModerationService>,
+    pub rate_limit: Arc<RateLimitService>,
+    pub audit: AuditService,
+    pub webhook: WebhookService,
+    pub bot: BotService,
+    pub command: Arc<CommandService>,
 }
 
 impl fmt::Debug for AppStateInner {
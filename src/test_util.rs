@@ -0,0 +1,42 @@
+use sqlx::PgPool;
+
+use crate::{
+    domain::audit::ClientInfo,
+    service::auth::{ClaimUser, SignupUserDto},
+    state::AppConfig,
+    AppState,
+};
+
+/// the services of the app on the database of a `#[sqlx::test]`
+pub async fn state(pool: PgPool) -> AppState {
+    let mut config = AppConfig::try_load().expect("app.yml");
+    // notifications are listened to on the database of the test
+    let url = std::env::var("DATABASE_URL").expect("DATABASE_URL");
+    let db = pool
+        .connect_options()
+        .get_database()
+        .unwrap_or_default()
+        .to_owned();
+    let (server, _) = url.rsplit_once('/').expect("DATABASE_URL with a database");
+    config.server.db_url = format!("{}/{}", server, db);
+    config.server.stub_link_fetcher = true;
+    AppState::try_new_with_pool(config, pool)
+        .await
+        .expect("app state")
+}
+
+/// a member of the workspace, its first member is the admin
+pub async fn signup(state: &AppState, name: &str, workspace: &str) -> ClaimUser {
+    let input = SignupUserDto {
+        fullname: name.to_owned(),
+        email: format!("{}@{}.org", name, workspace),
+        workspace: workspace.to_owned(),
+        password: "123456".to_owned(),
+    };
+    let token = state
+        .auth
+        .signup(input, &ClientInfo::default())
+        .await
+        .expect("signup");
+    state.auth.verify_token(token).await.expect("token")
+}